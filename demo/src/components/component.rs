@@ -1,4 +1,18 @@
+use wme_core::render_device::RenderDevice;
+
 use crate::game_objects::GameObject;
+use crate::model::Model;
 
 pub trait Component {
+    // geometry for picking and bounds, if the component draws any
+    fn model(self: &Self) -> Option<&Model> {
+        None
+    }
+
+    fn model_mut(self: &mut Self) -> Option<&mut Model> {
+        None
+    }
+
+    // frees the GPU resources the component owns
+    fn destroy(self: &Self, _device: &mut dyn RenderDevice) {}
 }
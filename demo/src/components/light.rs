@@ -1,11 +1,11 @@
-use wme_core::shader::Shader;
+use wme_core::{render_device::RenderDevice, shader::Shader};
 
 use super::component::Component;
 
 extern crate nalgebra_glm as glm;
 
-const POINT_LIGHT_VERTEX_SOURCE: &str = "../resources/shaders/point-light.vert";
-const POINT_LIGHT_FRAGMENT_SOURCE: &str = "../resources/shaders/point-light.frag";
+const POINT_LIGHT_VERTEX_SOURCE: &str = "../resources/shaders/point-light-vs.glsl";
+const POINT_LIGHT_FRAGMENT_SOURCE: &str = "../resources/shaders/point-light-fs.glsl";
 
 pub enum LightType {
     POINT,
@@ -20,20 +20,22 @@ pub struct Light {
 }
 
 impl Light {
-
-}
-
-impl Component for Light {
-}
-
-impl Default for Light {
-    fn default() -> Light {
+    pub fn new(device: &mut dyn RenderDevice) -> Light {
         Light {
             light_type: LightType::POINT,
             color: glm::Vec3::zeros(),
             intensity: 1.0,
-            shader: Shader::new(&[POINT_LIGHT_VERTEX_SOURCE, POINT_LIGHT_FRAGMENT_SOURCE])
-                .expect("Unable to create shader!"),
+            shader: Shader::new(
+                device,
+                &[POINT_LIGHT_VERTEX_SOURCE, POINT_LIGHT_FRAGMENT_SOURCE],
+            )
+            .expect("Unable to create shader!"),
         }
     }
 }
+
+impl Component for Light {
+    fn destroy(self: &Self, device: &mut dyn RenderDevice) {
+        self.shader.destroy(device);
+    }
+}
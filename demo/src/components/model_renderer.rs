@@ -1,3 +1,5 @@
+use wme_core::render_device::RenderDevice;
+
 use crate::model::Model;
 use crate::renderer::Renderer;
 
@@ -16,10 +18,22 @@ impl ModelRenderer {
     }
 }
 
-impl Component for ModelRenderer {}
+impl Component for ModelRenderer {
+    fn model(self: &Self) -> Option<&Model> {
+        Some(&self.model)
+    }
+
+    fn model_mut(self: &mut Self) -> Option<&mut Model> {
+        Some(&mut self.model)
+    }
+
+    fn destroy(self: &Self, device: &mut dyn RenderDevice) {
+        self.model.destroy(device);
+    }
+}
 
 impl Renderer for ModelRenderer {
-    fn render(self: Self) {
-        self.model.draw_meshes();
+    fn render(self: &Self, device: &mut dyn RenderDevice) {
+        self.model.draw_meshes(device);
     }
 }
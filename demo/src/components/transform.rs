@@ -6,16 +6,27 @@ extern crate nalgebra_glm as glm;
 
 pub struct Transform {
     pub position: glm::Vec3,
+    // euler angles in degrees, applied x then y then z
     pub rotation: glm::Vec3,
     pub scale: glm::Vec3,
 }
 
+impl Transform {
+    pub fn model_matrix(self: &Self) -> glm::Mat4 {
+        let mut model: glm::Mat4 = glm::translation(&self.position);
+        model = glm::rotate_z(&model, self.rotation.z.to_radians());
+        model = glm::rotate_y(&model, self.rotation.y.to_radians());
+        model = glm::rotate_x(&model, self.rotation.x.to_radians());
+        glm::scale(&model, &self.scale)
+    }
+}
+
 impl Default for Transform {
     fn default() -> Transform {
         Transform {
             position: glm::Vec3::zeros(),
             rotation: glm::Vec3::zeros(),
-            scale: glm::Vec3::identity(),
+            scale: glm::Vec3::repeat(1.0),
         }
     }
 }
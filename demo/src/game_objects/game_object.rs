@@ -3,6 +3,8 @@ use crate::components::{Component, Transform};
 #[derive(Default)]
 pub struct GameObject {
     pub transform: Transform,
+    // 0 to 31, drawn by the camera views whose layer_mask has this bit set
+    pub layer: u32,
     pub components: Vec<Box<dyn Component>>,
 }
 
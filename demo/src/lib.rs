@@ -4,5 +4,6 @@ pub mod light_cube;
 pub mod material;
 pub mod mesh;
 pub mod model;
+pub mod picking;
 pub mod renderer;
 pub mod scene;
@@ -1,6 +1,9 @@
-extern crate gl;
 extern crate nalgebra_glm as glm;
 
+use wme_core::render_device::{
+    as_bytes, BufferTarget, ObjectKind, PrimitiveType, RenderDevice, VertexAttribute,
+};
+
 pub struct LightCube {
     pub position: glm::Vec3,
     pub vao: u32,
@@ -10,55 +13,28 @@ pub struct LightCube {
 }
 
 impl LightCube {
-    pub fn new(position: glm::Vec3) -> LightCube {
-        let mut vao: u32 = 0;
-        let mut light_vao: u32 = 0;
-        let mut vbo: u32 = 0;
-
-        unsafe {
-            gl::GenVertexArrays(1, &mut vao);
-            gl::GenBuffers(1, &mut vbo);
+    pub fn new(device: &mut dyn RenderDevice, position: glm::Vec3) -> LightCube {
+        let stride = 6 * std::mem::size_of::<f32>();
+        let position_attribute = VertexAttribute {
+            index: 0,
+            components: 3,
+            stride,
+            offset: 0,
+        };
+        let normal_attribute = VertexAttribute {
+            index: 1,
+            components: 3,
+            stride,
+            offset: 3 * std::mem::size_of::<f32>(),
+        };
 
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                std::mem::size_of_val(&VERTICES) as gl::types::GLsizeiptr,
-                VERTICES.as_ptr().cast(),
-                gl::STATIC_DRAW,
-            );
-            gl::BindVertexArray(vao);
-            gl::VertexAttribPointer(
-                0,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                6 * std::mem::size_of::<f32>() as gl::types::GLint,
-                0 as *const gl::types::GLvoid,
-            );
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(
-                1,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                6 * std::mem::size_of::<f32>() as gl::types::GLint,
-                (3 * std::mem::size_of::<f32>()) as *const gl::types::GLvoid,
-            );
-            gl::EnableVertexAttribArray(1);
+        let vbo = device.create_buffer(BufferTarget::Array, as_bytes(&VERTICES));
+        let vao = device.create_vertex_array(vbo, None, &[position_attribute, normal_attribute]);
+        let light_vao = device.create_vertex_array(vbo, None, &[position_attribute]);
 
-            gl::GenVertexArrays(1, &mut light_vao);
-            gl::BindVertexArray(light_vao);
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-            gl::VertexAttribPointer(
-                0,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                6 * std::mem::size_of::<f32>() as gl::types::GLint,
-                0 as *const gl::types::GLvoid,
-            );
-            gl::EnableVertexAttribArray(0);
-        }
+        device.label(ObjectKind::VertexArray, vao, "light_cube");
+        device.label(ObjectKind::VertexArray, light_vao, "light_cube (light)");
+        device.label(ObjectKind::Buffer, vbo, "light_cube vertices");
 
         LightCube {
             position,
@@ -68,20 +44,15 @@ impl LightCube {
         }
     }
 
-    pub fn draw(self: &Self) {
-        unsafe {
-            gl::BindVertexArray(self.vao);
-            gl::DrawArrays(gl::TRIANGLES, 0, 36);
-            gl::BindVertexArray(0);
-        }
+    pub fn draw(self: &Self, device: &mut dyn RenderDevice) {
+        device.bind_vertex_array(self.vao);
+        device.draw_arrays(PrimitiveType::Triangles, 0, 36);
     }
 
-    pub fn destroy(self: &Self) {
-        unsafe {
-            gl::DeleteVertexArrays(1, &self.vao);
-            gl::DeleteVertexArrays(1, &self.light_vao);
-            gl::DeleteBuffers(1, &self.vbo);
-        }
+    pub fn destroy(self: &Self, device: &mut dyn RenderDevice) {
+        device.delete_vertex_array(self.vao);
+        device.delete_vertex_array(self.light_vao);
+        device.delete_buffer(self.vbo);
     }
 }
 
@@ -1,102 +1,517 @@
 extern crate nalgebra_glm as glm;
 
 use std::error::Error;
+use std::path::Path;
 
 use demo::light_cube::LightCube;
-use demo::model::Model;
-use wme_core::camera::Camera;
+use demo::scene::{CullStats, Scene};
+use wme_core::app::{App, Runner};
+use wme_core::camera::{Camera, MovementMode, Projection};
+use wme_core::camera_path::CameraPath;
+use wme_core::camera_view::{end_views, render_order, CameraView, ViewportRect};
+use wme_core::capture::{FrameCapture, FrameRecorder};
 use wme_core::constants::ShaderStrings;
-use wme_core::graphics::Graphics;
-use wme_core::mouse::Mouse;
+use wme_core::graphics::{CursorMode, Graphics, Resizable};
+use wme_core::graphics_config::{GraphicsConfig, HeadlessBackend};
+use wme_core::input_map::{Binding, InputMap};
+use wme_core::input_recording::{InputRecorder, InputReplay};
+use wme_core::orbit_controller::OrbitController;
+use wme_core::render_device::RenderDevice;
 use wme_core::shader::Shader;
+use wme_core::state_cache::StateCounters;
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let mut graphics: Graphics = Graphics::new(1280, 720).expect("Failed to init glfw");
+// views[MAIN_VIEW] holds the camera the player controls
+const MAIN_VIEW: usize = 0;
+
+// seconds between checks for edited shader files
+const SHADER_POLL_INTERVAL: f32 = 0.5;
 
-    let shader_strings: ShaderStrings = ShaderStrings::default();
+// cursor travel in window coordinates below which a press and release is a click, not a drag
+const CLICK_DRAG_DISTANCE: f32 = 4.0;
 
-    let cube_shaders: [&str; 2] = [
-        "../resources/shaders/phong-shader-vs.glsl",
-        "../resources/shaders/phong-shader-fs.glsl",
-    ];
+enum ViewLayout {
+    Single,
+    Minimap,
+    Quad,
+}
 
-    let cube_model: Model = Model::new(&"../resources/meshes/cube.obj", &cube_shaders);
+struct Demo {
+    shader_strings: ShaderStrings,
+    scene: Scene,
+    light_shader: Shader,
+    light_cube_mesh: LightCube,
+    views: Vec<CameraView>,
+    // view following the main camera from above
+    minimap: Option<usize>,
+    orbit: Option<OrbitController>,
+    camera_path: Option<CameraPath>,
+    input_map: InputMap,
+    // cursor travel since select went down, orbiting drags with the same button
+    select_drag: f32,
+    stats_timer: f32,
+    shader_poll_timer: f32,
+    state_counters: StateCounters,
+    cull_stats: CullStats,
+    capture: FrameCapture,
+    screenshot_requested: bool,
+}
 
-    let light_shaders: [&str; 2] = [
-        "../resources/shaders/point-light-vs.glsl",
-        "../resources/shaders/point-light-fs.glsl",
-    ];
+impl Demo {
+    fn new(
+        graphics: &mut Graphics,
+        orbit: bool,
+        camera_path: Option<CameraPath>,
+        layout: ViewLayout,
+    ) -> Result<Demo, Box<dyn Error>> {
+        let shader_strings: ShaderStrings = ShaderStrings::default();
 
-    let light_shader = Shader::new(&light_shaders)?;
+        let scene: Scene = Scene::new(&mut graphics.device);
 
-    let light_cube_mesh = LightCube::new(glm::Vec3::zeros());
+        let light_shaders: [&str; 2] = [
+            "../resources/shaders/point-light-vs.glsl",
+            "../resources/shaders/point-light-fs.glsl",
+        ];
 
-    let mut camera: Camera = Camera::new(glm::Vec3::new(0.0, 0.0, 5.0));
-    camera.mouse_sensitivity = 40.0;
-    camera.aspect = graphics.screen_width as f32 / graphics.screen_height as f32;
+        let light_shader = Shader::new(&mut graphics.device, &light_shaders)?;
 
-    let mut mouse_data: Mouse = Mouse::new(
-        (graphics.screen_width / 2) as f32,
-        (graphics.screen_height / 2) as f32,
-    );
+        let light_cube_mesh = LightCube::new(&mut graphics.device, glm::Vec3::zeros());
 
-    while !graphics.window_should_close() {
-        graphics.update_time();
+        let mut camera: Camera = Camera::new(glm::Vec3::new(0.0, 0.0, 5.0));
+        camera.mouse_sensitivity = 40.0;
+        camera.aspect = graphics.aspect_ratio();
 
-        // process input
-        wme_core::keyboard::process_inputs(&mut graphics.window, &mut camera, graphics.delta_time);
-        mouse_data.process_mouse(&graphics.events, &mut camera, graphics.delta_time);
+        // orbiting drags with a free cursor, flying uses mouse look until
+        // the cursor is released with toggle_cursor
+        let orbit = if orbit {
+            let mut orbit = OrbitController::default();
+            orbit.frame(&camera, &scene.bounds());
+            orbit.apply(&mut camera);
+            Some(orbit)
+        } else {
+            graphics.set_cursor_mode(CursorMode::Captured);
+            None
+        };
 
-        // render
-        unsafe {
-            gl::ClearColor(0.1, 0.1, 0.1, 1.0);
-            gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        let mut views: Vec<CameraView> = vec![CameraView::new(camera)];
+        let mut minimap: Option<usize> = None;
+        match layout {
+            ViewLayout::Single => (),
+            // picture-in-picture in the top right corner, drawn over the main view
+            ViewLayout::Minimap => {
+                minimap = Some(views.len());
+                views.push(
+                    CameraView::new(orthographic_camera(glm::Vec3::new(0.0, 20.0, 0.0), 10.0))
+                        .viewport(ViewportRect::new(0.72, 0.68, 0.25, 0.3))
+                        .order(1)
+                        .clear_color(Some(glm::Vec4::new(0.05, 0.05, 0.15, 1.0))),
+                );
+            }
+            // editor layout, perspective top left then top, front and side
+            ViewLayout::Quad => {
+                views[MAIN_VIEW].viewport = ViewportRect::new(0.0, 0.5, 0.5, 0.5);
+                let fixed_views = [
+                    (
+                        glm::Vec3::new(0.0, 10.0, 0.0),
+                        ViewportRect::new(0.5, 0.5, 0.5, 0.5),
+                    ),
+                    (
+                        glm::Vec3::new(0.0, 0.0, 10.0),
+                        ViewportRect::new(0.0, 0.0, 0.5, 0.5),
+                    ),
+                    (
+                        glm::Vec3::new(10.0, 0.0, 0.0),
+                        ViewportRect::new(0.5, 0.0, 0.5, 0.5),
+                    ),
+                ];
+                for (position, viewport) in fixed_views {
+                    views.push(
+                        CameraView::new(orthographic_camera(position, 6.0)).viewport(viewport),
+                    );
+                }
+            }
         }
 
+        // GLFW ships its own mappings, the file only adds to them
+        let mappings = Path::new("../resources/gamecontrollerdb.txt");
+        if mappings.exists() {
+            if let Err(error) = graphics.gamepads.load_mappings(mappings) {
+                log::warn!("Failed to load gamepad mappings: {}", error);
+            }
+        }
+
+        // falls back to the default bindings so a missing file is not fatal
+        let input_map = match InputMap::load(Path::new("../resources/input.cfg")) {
+            Ok(input_map) => input_map,
+            Err(error) => {
+                log::warn!("Using default input bindings: {}", error);
+                let mut input_map = InputMap::default();
+                input_map.bind_action("screenshot", Binding::Key(glfw::Key::F12));
+                input_map.bind_action("toggle_cursor", Binding::Key(glfw::Key::Tab));
+                input_map.bind_action("select", Binding::MouseButton(glfw::MouseButtonLeft));
+                input_map.bind_action("cycle_projection", Binding::Key(glfw::Key::P));
+                input_map.bind_action("toggle_movement_mode", Binding::Key(glfw::Key::F));
+                input_map
+            }
+        };
+
+        Ok(Demo {
+            shader_strings,
+            scene,
+            light_shader,
+            light_cube_mesh,
+            views,
+            minimap,
+            orbit,
+            camera_path,
+            input_map,
+            select_drag: 0.0,
+            stats_timer: 0.0,
+            shader_poll_timer: 0.0,
+            state_counters: StateCounters::default(),
+            cull_stats: CullStats::default(),
+            capture: FrameCapture::default(),
+            screenshot_requested: false,
+        })
+    }
+
+    fn draw_view(self: &Self, device: &mut dyn RenderDevice, view: &CameraView) -> CullStats {
+        let shader_strings = &self.shader_strings;
+        let camera = &view.camera;
+
         let projection: glm::Mat4 = camera.get_projection_matrix();
-        let view: glm::Mat4 = camera.get_view_matrix();
+        let view_matrix: glm::Mat4 = camera.get_view_matrix();
 
         // Render point light
         let mut light_model: glm::Mat4 = glm::Mat4::identity();
         light_model = glm::translate(&light_model, &glm::Vec3::new(1.2, 1.0, 2.0));
         light_model = glm::scale(&light_model, &glm::Vec3::new(0.2, 0.2, 0.2));
 
-        light_shader.use_program();
-        light_shader.set_mat4(&shader_strings.projection_uniform, projection);
-        light_shader.set_mat4(&shader_strings.view_uniform, view);
-        light_shader.set_mat4(&shader_strings.model_uniform, light_model);
-        light_cube_mesh.draw();
-
-        // Render cube
-        let model: glm::Mat4 = glm::Mat4::identity();
-        glm::translate(&model, &glm::Vec3::new(0.0, 0.0, 0.0));
-        glm::scale(&model, &glm::Vec3::new(1.0, 1.0, 1.0));
-
-        cube_model.shader.use_program();
-        cube_model
-            .shader
-            .set_vec3(&shader_strings.u_light_color, glm::Vec3::new(1.0, 1.0, 1.0));
-        cube_model
-            .shader
-            .set_vec3(&shader_strings.u_light_pos, glm::Vec3::new(1.2, 1.0, 2.0));
-        cube_model
-            .shader
-            .set_vec3(&shader_strings.u_view_pos, camera.position);
-        cube_model
-            .shader
-            .set_mat4(&shader_strings.projection_uniform, projection);
-        cube_model
-            .shader
-            .set_mat4(&shader_strings.view_uniform, view);
-        cube_model
-            .shader
-            .set_mat4(&shader_strings.model_uniform, model);
-        cube_model.draw_meshes();
-
-        // check events and swap buffers
-        graphics.check_events();
-        graphics.swap_buffers();
+        self.light_shader.use_program(device);
+        self.light_shader
+            .set_mat4(device, &shader_strings.projection_uniform, projection);
+        self.light_shader
+            .set_mat4(device, &shader_strings.view_uniform, view_matrix);
+        self.light_shader
+            .set_mat4(device, &shader_strings.model_uniform, light_model);
+        self.light_cube_mesh.draw(device);
+
+        // Render the scene, Scene::render sets each child's model matrix and culls
+        for model in self.scene.models() {
+            model.shader.use_program(device);
+            model.shader.set_vec3(
+                device,
+                &shader_strings.u_light_color,
+                glm::Vec3::new(1.0, 1.0, 1.0),
+            );
+            model.shader.set_vec3(
+                device,
+                &shader_strings.u_light_pos,
+                glm::Vec3::new(1.2, 1.0, 2.0),
+            );
+            model
+                .shader
+                .set_vec3(device, &shader_strings.u_view_pos, camera.position);
+            model
+                .shader
+                .set_mat4(device, &shader_strings.projection_uniform, projection);
+            model
+                .shader
+                .set_mat4(device, &shader_strings.view_uniform, view_matrix);
+        }
+        self.scene
+            .render(device, view, &shader_strings.model_uniform)
+    }
+}
+
+impl App for Demo {
+    fn resize(self: &mut Self, _graphics: &mut Graphics, width: u32, height: u32) {
+        for view in self.views.iter_mut() {
+            let pixels = view.viewport.to_pixels(width, height);
+            view.camera
+                .resize(pixels.width as u32, pixels.height as u32);
+        }
     }
 
-    Ok(())
+    fn update(self: &mut Self, graphics: &mut Graphics, dt: f32) {
+        // process input
+        self.input_map.update(&graphics.input, &graphics.gamepads);
+        if let Some(path) = &mut self.camera_path {
+            if self.input_map.action_pressed("quit") {
+                graphics.set_should_close(true);
+            }
+            // a finished flythrough is a finished benchmark run
+            path.update(dt, &mut self.views[MAIN_VIEW].camera);
+            if path.is_finished() {
+                log::info!("Camera path finished: {}", graphics.frame_stats);
+                graphics.set_should_close(true);
+            }
+        } else if let Some(orbit) = &mut self.orbit {
+            if self.input_map.action_pressed("quit") {
+                graphics.set_should_close(true);
+            }
+            // cursor deltas are in window coordinates, not framebuffer pixels
+            let height = graphics.window_size().1 as f32;
+            orbit.update(
+                &graphics.input,
+                &graphics.gamepads,
+                &mut self.views[MAIN_VIEW].camera,
+                height,
+                dt,
+            );
+        } else {
+            wme_core::keyboard::process_inputs(
+                graphics,
+                &self.input_map,
+                &mut self.views[MAIN_VIEW].camera,
+                dt,
+            );
+        }
+
+        // the minimap follows the main camera from above
+        if let Some(index) = self.minimap {
+            let position = self.views[MAIN_VIEW].camera.position;
+            let minimap = &mut self.views[index].camera;
+            minimap.position = position + glm::Vec3::new(0.0, 20.0, 0.0);
+            minimap.look_at(&position);
+        }
+
+        let camera = &mut self.views[MAIN_VIEW].camera;
+        if self.input_map.action_pressed("toggle_movement_mode") {
+            camera.movement_mode = match camera.movement_mode {
+                MovementMode::Fly => MovementMode::Fps,
+                MovementMode::Fps => MovementMode::Fly,
+            };
+            log::info!("Movement mode: {:?}", camera.movement_mode);
+        }
+
+        // perspective, then an orthographic view of the same size at the origin, then reversed-Z
+        if self.input_map.action_pressed("cycle_projection") {
+            camera.projection = match camera.projection {
+                Projection::Perspective => Projection::Orthographic {
+                    height: camera.view_height(glm::length(&camera.position)),
+                },
+                Projection::Orthographic { .. } => Projection::ReversedZInfinite,
+                _ => Projection::Perspective,
+            };
+            log::info!("Projection: {:?}", camera.projection);
+        }
+
+        if self.input_map.action_pressed("toggle_cursor") {
+            graphics.toggle_cursor_capture();
+        }
+
+        // clicking with a free cursor reports what is under it, on release so a drag can orbit
+        if self.input_map.action_pressed("select") {
+            self.select_drag = 0.0;
+        }
+        if self.input_map.action_down("select") || self.input_map.action_released("select") {
+            let (x, y) = graphics.input.mouse_delta();
+            self.select_drag += glm::length(&glm::vec2(x, y));
+        }
+        if self.input_map.action_released("select")
+            && self.select_drag < CLICK_DRAG_DISTANCE
+            && !graphics.input.cursor_captured()
+        {
+            let (x, y) = graphics.input.cursor_position();
+            let (width, height) = graphics.window_size();
+            // the view drawn last is on top where views overlap
+            let ray = render_order(&self.views)
+                .into_iter()
+                .rev()
+                .find_map(|index| self.views[index].screen_ray(x, y, width as f32, height as f32));
+            let hit = ray.and_then(|ray| self.scene.pick(&ray));
+            match hit {
+                Some(hit) => log::info!(
+                    "Picked object {} mesh {} triangle {} at {:?}, {:.2} away",
+                    hit.object,
+                    hit.mesh,
+                    hit.triangle,
+                    hit.point,
+                    hit.distance
+                ),
+                None => log::info!("Picked nothing"),
+            }
+        }
+
+        // queues a screenshot of the frame rendered next
+        if self.input_map.action_pressed("screenshot") {
+            self.screenshot_requested = true;
+        }
+        if let Err(error) = self.capture.poll() {
+            log::error!("Screenshot failed: {}", error);
+        }
+
+        // edited shaders are picked up without a restart, uniforms are set every draw anyway
+        self.shader_poll_timer += dt;
+        if self.shader_poll_timer >= SHADER_POLL_INTERVAL {
+            self.shader_poll_timer = 0.0;
+            for model in self.scene.models_mut() {
+                model.shader.reload_if_changed(&mut graphics.device);
+            }
+            self.light_shader.reload_if_changed(&mut graphics.device);
+        }
+
+        self.stats_timer += dt;
+        if self.stats_timer >= 1.0 {
+            self.stats_timer = 0.0;
+            let mut title: String = format!("Wave Motion Engine - {}", graphics.frame_stats);
+            for (pass, ms) in graphics.gpu_timers.results() {
+                title.push_str(&format!(", {} {:.2} ms", pass, ms));
+            }
+            title.push_str(&format!(
+                ", state {} issued {} skipped",
+                self.state_counters.issued, self.state_counters.skipped
+            ));
+            title.push_str(&format!(
+                ", {} visible {} culled",
+                self.cull_stats.visible, self.cull_stats.culled
+            ));
+            graphics.set_title(&title);
+        }
+    }
+
+    fn render(self: &mut Self, graphics: &mut Graphics, _alpha: f32) {
+        self.state_counters = graphics.device.state.counters;
+        graphics.device.state.reset_counters();
+
+        // the scene is drawn once per view, each timed as its own pass
+        let (width, height) = (graphics.screen_width, graphics.screen_height);
+        self.cull_stats = CullStats::default();
+        for index in render_order(&self.views) {
+            graphics.gpu_timers.begin(&format!("view {}", index));
+            self.views[index].begin(&mut graphics.device, width, height);
+            let stats = self.draw_view(&mut graphics.device, &self.views[index]);
+            self.cull_stats.visible += stats.visible;
+            self.cull_stats.culled += stats.culled;
+        }
+        graphics.gpu_timers.end();
+        end_views(&mut graphics.device, width, height);
+
+        if self.screenshot_requested {
+            self.screenshot_requested = false;
+            let path = format!("screenshot_{:.0}.png", graphics.current_time * 1000.0);
+            if let Err(error) = self.capture.request(
+                graphics.default_framebuffer(),
+                graphics.screen_width,
+                graphics.screen_height,
+                Path::new(&path),
+            ) {
+                log::error!("Screenshot failed: {}", error);
+            }
+        }
+    }
+
+    fn shutdown(self: &mut Self, graphics: &mut Graphics) {
+        self.scene.destroy(&mut graphics.device);
+        self.light_shader.destroy(&mut graphics.device);
+        self.light_cube_mesh.destroy(&mut graphics.device);
+
+        if let Err(error) = self.capture.flush() {
+            log::error!("Screenshot failed: {}", error);
+        }
+        self.capture.destroy();
+    }
+}
+
+// fixed orthographic camera looking at the origin
+fn orthographic_camera(position: glm::Vec3, height: f32) -> Camera {
+    let mut camera: Camera = Camera::new(position);
+    camera.projection = Projection::Orthographic { height };
+    camera.look_at(&glm::Vec3::zeros());
+    camera
+}
+
+struct StderrLogger;
+
+impl log::Log for StderrLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    log::set_logger(&LOGGER).map_err(|error| error.to_string())?;
+    log::set_max_level(log::LevelFilter::Info);
+
+    let args: Vec<String> = std::env::args().collect();
+
+    // GL debug output and per-call error checks in debug builds
+    let mut config: GraphicsConfig = GraphicsConfig::new()
+        .title("Wave Motion Engine")
+        .size(1280, 720)
+        .debug_context(cfg!(debug_assertions));
+
+    // --headless [egl|osmesa] renders without a window or display server
+    if let Some(index) = args.iter().position(|arg| arg == "--headless") {
+        let backend = match args.get(index + 1).map(|arg| arg.as_str()) {
+            Some("osmesa") => HeadlessBackend::OsMesa,
+            _ => HeadlessBackend::Egl,
+        };
+        config = config.headless(backend);
+    }
+    let graphics: Graphics = config.build()?;
+
+    let mut runner: Runner = Runner::new(graphics);
+
+    // --frames <count> stops after that many frames, headless runs need it or a camera path
+    if let Some(index) = args.iter().position(|arg| arg == "--frames") {
+        let frames = args
+            .get(index + 1)
+            .ok_or("--frames needs a count")?
+            .parse::<u64>()?;
+        runner = runner.frame_limit(frames);
+    }
+
+    // --record <directory> writes every frame at a fixed 60 fps
+    if let Some(index) = args.iter().position(|arg| arg == "--record") {
+        let directory = args.get(index + 1).map_or("frames", |arg| arg.as_str());
+        runner = runner.record(FrameRecorder::new(Path::new(directory), 60.0)?);
+    }
+
+    // --record-input <file> and --replay-input <file> reproduce a session exactly
+    if let Some(index) = args.iter().position(|arg| arg == "--record-input") {
+        let path = args.get(index + 1).map_or("input.wmei", |arg| arg.as_str());
+        runner = runner.record_input(InputRecorder::new(Path::new(path))?);
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--replay-input") {
+        let path = args.get(index + 1).map_or("input.wmei", |arg| arg.as_str());
+        runner = runner.replay_input(InputReplay::load(Path::new(path))?);
+    }
+
+    // --orbit inspects the model with an orbit camera instead of flying
+    let orbit = args.iter().any(|arg| arg == "--orbit");
+
+    // --camera-path <file> flies the camera along a path and exits at its end
+    let camera_path = match args.iter().position(|arg| arg == "--camera-path") {
+        Some(index) => {
+            let path = args
+                .get(index + 1)
+                .map_or("../resources/paths/flythrough.path", |arg| arg.as_str());
+            Some(CameraPath::load(Path::new(path))?)
+        }
+        None => None,
+    };
+
+    // --minimap adds a top down picture-in-picture, --quad an editor style quad view
+    let layout = if args.iter().any(|arg| arg == "--quad") {
+        ViewLayout::Quad
+    } else if args.iter().any(|arg| arg == "--minimap") {
+        ViewLayout::Minimap
+    } else {
+        ViewLayout::Single
+    };
+
+    let mut demo: Demo = Demo::new(&mut runner.graphics, orbit, camera_path, layout)?;
+
+    runner.run(&mut demo)
 }
@@ -1,4 +1,4 @@
-use wme_core::shader::Shader;
+use wme_core::{render_device::RenderDevice, shader::Shader};
 
 extern crate nalgebra_glm as glm;
 
@@ -10,13 +10,14 @@ pub struct Material {
     pub shader: Shader,
 }
 
-impl Default for Material {
-    fn default() -> Material {
+impl Material {
+    pub fn new(device: &mut dyn RenderDevice) -> Material {
         let material_shaders: [&str; 2] = [
             "../resources/shaders/material.vert",
             "../resources/shaders/material.frag",
         ];
-        let shader: Shader = Shader::new(&material_shaders).expect("Unable to load shaders!");
+        let shader: Shader =
+            Shader::new(device, &material_shaders).expect("Unable to load shaders!");
 
         Material {
             ambient: glm::Vec3::zeros(),
@@ -1,99 +1,175 @@
-use std::{collections::HashSet, ffi::c_void};
+extern crate nalgebra_glm as glm;
 
-use wme_core::{shader::Shader, texture::Texture, vertex::Vertex};
+use std::collections::HashSet;
+
+use wme_core::{
+    bounds::Aabb,
+    render_device::{as_bytes, BufferTarget, PrimitiveType, RenderDevice, VertexAttribute},
+    shader::Shader,
+    texture::Texture,
+    vertex::Vertex,
+};
 
 pub struct Mesh {
     pub vao: u32,
+    pub vbo: u32,
     pub ebo: u32,
     pub indices: Vec<u32>,
     pub vertices: Vec<Vertex>,
     pub textures: HashSet<Texture>,
+    pub bounds: Aabb,
 }
 
 impl Mesh {
-    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>, textures: &HashSet<Texture>) -> Mesh {
-        let mut vbo: u32 = 0;
-        let mut vao: u32 = 0;
-        let mut ebo: u32 = 0;
-
-        unsafe {
-            gl::GenVertexArrays(1, &mut vao);
-            gl::GenBuffers(1, &mut vbo);
-            gl::GenBuffers(1, &mut ebo);
-
-            gl::BindVertexArray(vao);
-            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (vertices.len() * std::mem::size_of::<Vertex>()) as gl::types::GLsizeiptr,
-                &vertices[0] as *const Vertex as *const c_void,
-                gl::STATIC_DRAW,
-            );
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ebo);
-            gl::BufferData(
-                gl::ELEMENT_ARRAY_BUFFER,
-                (indices.len() * std::mem::size_of::<gl::types::GLint>()) as gl::types::GLsizeiptr,
-                &indices[0] as *const u32 as *const c_void,
-                gl::STATIC_DRAW,
-            );
-            gl::VertexAttribPointer(
-                0,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                8 * std::mem::size_of::<gl::types::GLfloat>() as gl::types::GLsizei,
-                std::ptr::null(),
-            );
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(
-                1,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                8 * std::mem::size_of::<gl::types::GLfloat>() as gl::types::GLsizei,
-                (3 * std::mem::size_of::<gl::types::GLfloat>()) as *const c_void,
-            );
-            gl::EnableVertexAttribArray(1);
-            gl::VertexAttribPointer(
-                2,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                8 * std::mem::size_of::<gl::types::GLfloat>() as gl::types::GLsizei,
-                (5 * std::mem::size_of::<gl::types::GLfloat>()) as *const c_void,
-            );
-            gl::EnableVertexAttribArray(2);
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl::BindVertexArray(0);
+    pub fn new(
+        device: &mut dyn RenderDevice,
+        vertices: Vec<Vertex>,
+        indices: Vec<u32>,
+        textures: &HashSet<Texture>,
+    ) -> Mesh {
+        let mut bounds = Aabb::empty();
+        for vertex in vertices.iter() {
+            // Vertex is packed, copy the field out before borrowing it
+            let position = vertex.position;
+            bounds.extend(&position);
         }
+
+        let vbo = device.create_buffer(BufferTarget::Array, as_bytes(&vertices));
+        let ebo = device.create_buffer(BufferTarget::ElementArray, as_bytes(&indices));
+
+        let stride = std::mem::size_of::<Vertex>();
+        let float_size = std::mem::size_of::<f32>();
+        let vao = device.create_vertex_array(
+            vbo,
+            Some(ebo),
+            &[
+                VertexAttribute {
+                    index: 0,
+                    components: 3,
+                    stride,
+                    offset: 0,
+                },
+                VertexAttribute {
+                    index: 1,
+                    components: 2,
+                    stride,
+                    offset: 3 * float_size,
+                },
+                VertexAttribute {
+                    index: 2,
+                    components: 3,
+                    stride,
+                    offset: 5 * float_size,
+                },
+            ],
+        );
+
         Mesh {
             vao,
+            vbo,
             ebo,
             indices,
             vertices,
             textures: textures.clone(),
+            bounds,
         }
     }
 
-    pub fn draw(self: &Self, shader: &Shader) {
-        unsafe {
-            for (idx, texture) in self.textures.iter().enumerate() {
-                gl::ActiveTexture(gl::TEXTURE0 + idx as u32);
-                shader.set_int(&texture.name, idx as i32);
-                gl::BindTexture(gl::TEXTURE_2D, texture.id);
-            }
-            gl::BindVertexArray(self.vao);
-
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
-            gl::DrawElements(
-                gl::TRIANGLES,
-                self.indices.len() as i32,
-                gl::UNSIGNED_INT,
-                std::ptr::null(),
-            );
-
-            gl::BindVertexArray(0);
-            gl::ActiveTexture(gl::TEXTURE0);
+    pub fn draw(self: &Self, device: &mut dyn RenderDevice, shader: &Shader) {
+        for (idx, texture) in self.textures.iter().enumerate() {
+            shader.set_int(device, &texture.name, idx as i32);
+            device.bind_texture(idx as u32, texture.id);
         }
+        device.bind_vertex_array(self.vao);
+
+        device.draw_elements(PrimitiveType::Triangles, self.indices.len() as i32);
+    }
+
+    // object space corners of the triangle starting at index 3 * triangle
+    pub fn triangle(self: &Self, triangle: usize) -> [glm::Vec3; 3] {
+        let corner = |index: usize| {
+            let vertex = &self.vertices[self.indices[3 * triangle + index] as usize];
+            vertex.position
+        };
+        [corner(0), corner(1), corner(2)]
+    }
+
+    pub fn triangle_count(self: &Self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn destroy(self: &Self, device: &mut dyn RenderDevice) {
+        device.delete_vertex_array(self.vao);
+        device.delete_buffer(self.vbo);
+        device.delete_buffer(self.ebo);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+
+    use wme_core::recording_device::{RecordingDevice, RenderCommand};
+    use wme_core::render_device::UniformValue;
+
+    use super::*;
+
+    fn vertex(x: f32, y: f32) -> Vertex {
+        Vertex {
+            position: glm::vec3(x, y, 0.0),
+            texcoord: glm::vec2(x, y),
+            normal: glm::vec3(0.0, 0.0, 1.0),
+        }
+    }
+
+    #[test]
+    fn draw_binds_textures_and_draws_every_index() {
+        let mut device = RecordingDevice::new();
+        let shader = Shader::new(
+            &mut device,
+            &[
+                "../resources/shaders/phong-shader-vs.glsl",
+                "../resources/shaders/phong-shader-fs.glsl",
+            ],
+        )
+        .unwrap();
+        let texture = Texture {
+            id: 42,
+            width: 1,
+            height: 1,
+            nr_channels: 3,
+            name: CString::new("texture_diffuse1").unwrap(),
+        };
+        let mesh = Mesh::new(
+            &mut device,
+            vec![vertex(0.0, 0.0), vertex(1.0, 0.0), vertex(0.0, 1.0)],
+            vec![0, 1, 2],
+            &HashSet::from([texture]),
+        );
+        assert_eq!(mesh.bounds.max, glm::vec3(1.0, 1.0, 0.0));
+        device.clear_commands();
+
+        shader.use_program(&mut device);
+        mesh.draw(&mut device, &shader);
+        assert_eq!(
+            device.take_commands(),
+            vec![
+                RenderCommand::UseProgram(shader.id),
+                RenderCommand::SetUniform {
+                    program: shader.id,
+                    name: "texture_diffuse1".to_string(),
+                    value: UniformValue::Int(0),
+                },
+                RenderCommand::BindTexture {
+                    unit: 0,
+                    texture: 42,
+                },
+                RenderCommand::BindVertexArray(mesh.vao),
+                RenderCommand::DrawElements {
+                    primitive: PrimitiveType::Triangles,
+                    count: 3,
+                },
+            ]
+        );
     }
 }
@@ -1,18 +1,25 @@
 use std::collections::HashSet;
 
 use nalgebra_glm::{Vec2, Vec3};
-use wme_core::{shader::Shader, texture::Texture, vertex::Vertex};
+use wme_core::{
+    bounds::Aabb,
+    render_device::{ObjectKind, RenderDevice},
+    shader::Shader,
+    texture::Texture,
+    vertex::Vertex,
+};
 
 use crate::mesh::Mesh;
 
 pub struct Model {
     pub shader: Shader,
     pub meshes: Vec<Mesh>,
+    pub bounds: Aabb,
 }
 
 impl Model {
-    pub fn new(path: &str, shader_paths: &[&str]) -> Model {
-        let shader: Shader = Shader::new(&shader_paths).expect("Failed to load shaders");
+    pub fn new(device: &mut dyn RenderDevice, path: &str, shader_paths: &[&str]) -> Model {
+        let shader: Shader = Shader::new(device, &shader_paths).expect("Failed to load shaders");
 
         let load_options = tobj::LoadOptions {
             triangulate: true,
@@ -53,20 +60,53 @@ impl Model {
                 let material = &materials[material_id];
                 if let Some(diffuse_texture) = &material.diffuse_texture {
                     textures.insert(
-                        Texture::new(diffuse_texture.as_str(), "diffuse_texture")
+                        Texture::new(device, diffuse_texture.as_str(), "diffuse_texture")
                             .expect("Failed to load texture"),
                     );
                 }
             }
-            meshes.push(Mesh::new(vertices, indices, &textures));
+            let mesh = Mesh::new(device, vertices, indices, &textures);
+            device.label(
+                ObjectKind::VertexArray,
+                mesh.vao,
+                &format!("{} ({})", path, m.name),
+            );
+            meshes.push(mesh);
         }
 
-        Model { shader, meshes }
+        // samplers the materials bind, a typo in the shader would leave them black
+        let samplers: HashSet<String> = meshes
+            .iter()
+            .flat_map(|mesh| mesh.textures.iter())
+            .map(|texture| texture.name.to_string_lossy().to_string())
+            .collect();
+        let samplers: Vec<&str> = samplers.iter().map(String::as_str).collect();
+        shader.warn_missing_uniforms(&samplers);
+
+        let bounds = meshes
+            .iter()
+            .fold(Aabb::empty(), |bounds, mesh| bounds.merge(&mesh.bounds));
+
+        Model {
+            shader,
+            meshes,
+            bounds,
+        }
+    }
+
+    pub fn draw_meshes(self: &Self, device: &mut dyn RenderDevice) {
+        for mesh in self.meshes.iter() {
+            mesh.draw(device, &self.shader);
+        }
     }
 
-    pub fn draw_meshes(self: &Self) {
+    pub fn destroy(self: &Self, device: &mut dyn RenderDevice) {
         for mesh in self.meshes.iter() {
-            mesh.draw(&self.shader);
+            mesh.destroy(device);
+            for texture in mesh.textures.iter() {
+                texture.destroy(device);
+            }
         }
+        self.shader.destroy(device);
     }
 }
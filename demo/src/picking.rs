@@ -0,0 +1,142 @@
+extern crate nalgebra_glm as glm;
+
+use wme_core::ray::Ray;
+
+use crate::game_objects::GameObject;
+use crate::model::Model;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickHit {
+    // index into the slice of objects that was picked from
+    pub object: usize,
+    pub distance: f32,
+    pub point: glm::Vec3,
+    pub mesh: usize,
+    // triangle within the mesh, its indices start at 3 * triangle
+    pub triangle: usize,
+}
+
+/*
+ * Closest triangle hit along a world space ray. Objects are rejected
+ * by their world bounds first, only the ones the ray enters closer
+ * than the best hit so far have their triangles tested.
+ */
+pub fn pick(objects: &[GameObject], ray: &Ray) -> Option<PickHit> {
+    let mut closest: Option<PickHit> = None;
+
+    for (object_index, object) in objects.iter().enumerate() {
+        let model_matrix = object.transform.model_matrix();
+        for model in object
+            .components
+            .iter()
+            .filter_map(|component| component.model())
+        {
+            let bounds = model.bounds.transformed(&model_matrix);
+            let max_distance = closest.map_or(f32::INFINITY, |hit| hit.distance);
+            match ray.intersect_aabb(&bounds) {
+                Some(distance) if distance <= max_distance => (),
+                _ => continue,
+            }
+
+            if let Some(hit) = pick_model(model, &model_matrix, ray, max_distance) {
+                closest = Some(PickHit {
+                    object: object_index,
+                    ..hit
+                });
+            }
+        }
+    }
+
+    closest
+}
+
+// object is left at 0 for the caller to fill in
+pub fn pick_model(
+    model: &Model,
+    model_matrix: &glm::Mat4,
+    ray: &Ray,
+    max_distance: f32,
+) -> Option<PickHit> {
+    // the local ray keeps world space distances, see Ray::transformed
+    let local_ray = ray.transformed(&glm::inverse(model_matrix));
+    let mut closest: Option<PickHit> = None;
+
+    for (mesh_index, mesh) in model.meshes.iter().enumerate() {
+        if local_ray.intersect_aabb(&mesh.bounds).is_none() {
+            continue;
+        }
+        for triangle in 0..mesh.triangle_count() {
+            let [a, b, c] = mesh.triangle(triangle);
+            let hit = match local_ray.intersect_triangle(&a, &b, &c) {
+                Some(hit) => hit,
+                None => continue,
+            };
+            let best = closest.map_or(max_distance, |closest| closest.distance);
+            if hit.distance < best {
+                closest = Some(PickHit {
+                    object: 0,
+                    distance: hit.distance,
+                    point: ray.at(hit.distance),
+                    mesh: mesh_index,
+                    triangle,
+                });
+            }
+        }
+    }
+
+    closest
+}
+
+#[cfg(test)]
+mod tests {
+    use wme_core::recording_device::RecordingDevice;
+
+    use super::*;
+    use crate::components::ModelRenderer;
+
+    fn cube(device: &mut RecordingDevice, position: glm::Vec3) -> GameObject {
+        let model = Model::new(
+            device,
+            "../resources/meshes/cube.obj",
+            &[
+                "../resources/shaders/phong-shader-vs.glsl",
+                "../resources/shaders/phong-shader-fs.glsl",
+            ],
+        );
+        let mut object = GameObject::default();
+        object.transform.position = position;
+        object.add_component(ModelRenderer::new(model));
+        object
+    }
+
+    #[test]
+    fn pick_returns_the_closest_object() {
+        let mut device = RecordingDevice::new();
+        let objects = vec![
+            cube(&mut device, glm::vec3(0.0, 0.0, -10.0)),
+            cube(&mut device, glm::vec3(0.0, 0.0, -5.0)),
+        ];
+        let half = objects[0].components[0].model().unwrap().bounds.max.z;
+
+        let ray = Ray::new(glm::Vec3::zeros(), glm::vec3(0.0, 0.0, -1.0));
+        let hit = pick(&objects, &ray).unwrap();
+        assert_eq!(hit.object, 1);
+        assert!((hit.distance - (5.0 - half)).abs() < 1e-4);
+        assert!(glm::distance(&hit.point, &glm::vec3(0.0, 0.0, half - 5.0)) < 1e-4);
+
+        let miss = Ray::new(glm::Vec3::zeros(), glm::vec3(0.0, 0.0, 1.0));
+        assert_eq!(pick(&objects, &miss), None);
+    }
+
+    #[test]
+    fn pick_measures_scaled_objects_in_world_units() {
+        let mut device = RecordingDevice::new();
+        let mut object = cube(&mut device, glm::vec3(10.0, 0.0, 0.0));
+        object.transform.scale = glm::Vec3::repeat(3.0);
+        let half = object.components[0].model().unwrap().bounds.max.x;
+
+        let ray = Ray::new(glm::Vec3::zeros(), glm::vec3(1.0, 0.0, 0.0));
+        let hit = pick(&[object], &ray).unwrap();
+        assert!((hit.distance - (10.0 - 3.0 * half)).abs() < 1e-4);
+    }
+}
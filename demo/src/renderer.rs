@@ -1,3 +1,5 @@
+use wme_core::render_device::RenderDevice;
+
 pub trait Renderer {
-    fn render(self: Self);
+    fn render(self: &Self, device: &mut dyn RenderDevice);
 }
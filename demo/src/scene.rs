@@ -1,4 +1,14 @@
+extern crate nalgebra_glm as glm;
+
+use std::ffi::CStr;
+
+use wme_core::bounds::Aabb;
+use wme_core::camera_view::CameraView;
+use wme_core::ray::Ray;
+use wme_core::render_device::RenderDevice;
+
 use crate::model::Model;
+use crate::picking::{self, PickHit};
 
 use crate::{components::{Light, ModelRenderer}, game_objects::GameObject};
 
@@ -6,12 +16,19 @@ pub struct Scene {
     pub children: Vec<GameObject>,
 }
 
-impl Default for Scene {
-    fn default() -> Scene {
+// objects Scene::render drew and skipped, objects without a model count as neither
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CullStats {
+    pub visible: usize,
+    pub culled: usize,
+}
+
+impl Scene {
+    pub fn new(device: &mut dyn RenderDevice) -> Scene {
         let mut children: Vec<GameObject> = Vec::new();
         let mut light_source: GameObject = GameObject::default();
 
-        let light_component: Light = Light::default();
+        let light_component: Light = Light::new(device);
         light_source.add_component(light_component);
 
         let cube_shaders: [&str; 2] = [
@@ -19,7 +36,7 @@ impl Default for Scene {
             "../resources/shaders/phong-shader-fs.glsl",
         ];
 
-        let cube_model: Model = Model::new(&"../resources/meshes/cube.obj", &cube_shaders);
+        let cube_model: Model = Model::new(device, &"../resources/meshes/cube.obj", &cube_shaders);
         let light_model_renderer: ModelRenderer = ModelRenderer::new(cube_model);
         light_source.add_component(light_model_renderer);
         children.push(light_source);
@@ -28,4 +45,170 @@ impl Default for Scene {
             children,
         }
     }
+
+    /*
+     * Draws the models of every child on a layer the view sees whose
+     * world bounds touch the view's frustum. Call once per view after
+     * CameraView::begin. Each model's shader gets the child's model
+     * matrix in model_uniform, view and projection must already be set
+     * on them.
+     */
+    pub fn render(
+        self: &Self,
+        device: &mut dyn RenderDevice,
+        view: &CameraView,
+        model_uniform: &CStr,
+    ) -> CullStats {
+        let mut stats: CullStats = CullStats::default();
+        let frustum = view.frustum();
+
+        for child in self
+            .children
+            .iter()
+            .filter(|child| view.sees_layer(child.layer))
+        {
+            let models: Vec<&Model> = child
+                .components
+                .iter()
+                .filter_map(|component| component.model())
+                .collect();
+            if models.is_empty() {
+                continue;
+            }
+
+            let model_matrix: glm::Mat4 = child.transform.model_matrix();
+            let bounds: Aabb = models.iter().fold(Aabb::empty(), |bounds, model| {
+                bounds.merge(&model.bounds.transformed(&model_matrix))
+            });
+            if !frustum.intersects_aabb(&bounds) {
+                stats.culled += 1;
+                continue;
+            }
+
+            stats.visible += 1;
+            for model in models {
+                model.shader.use_program(device);
+                model.shader.set_mat4(device, model_uniform, model_matrix);
+                model.draw_meshes(device);
+            }
+        }
+
+        stats
+    }
+
+    // PickHit::object indexes children
+    pub fn pick(self: &Self, ray: &Ray) -> Option<PickHit> {
+        picking::pick(&self.children, ray)
+    }
+
+    pub fn models(self: &Self) -> impl Iterator<Item = &Model> {
+        self.children
+            .iter()
+            .flat_map(|child| child.components.iter())
+            .filter_map(|component| component.model())
+    }
+
+    pub fn models_mut(self: &mut Self) -> impl Iterator<Item = &mut Model> {
+        self.children
+            .iter_mut()
+            .flat_map(|child| child.components.iter_mut())
+            .filter_map(|component| component.model_mut())
+    }
+
+    // world space bounds of every child's models
+    pub fn bounds(self: &Self) -> Aabb {
+        self.children.iter().fold(Aabb::empty(), |bounds, child| {
+            let model_matrix: glm::Mat4 = child.transform.model_matrix();
+            child
+                .components
+                .iter()
+                .filter_map(|component| component.model())
+                .fold(bounds, |bounds, model| {
+                    bounds.merge(&model.bounds.transformed(&model_matrix))
+                })
+        })
+    }
+
+    pub fn destroy(self: &Self, device: &mut dyn RenderDevice) {
+        for child in self.children.iter() {
+            for component in child.components.iter() {
+                component.destroy(device);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use wme_core::camera::Camera;
+    use wme_core::recording_device::{RecordingDevice, RenderCommand};
+    use wme_core::render_device::UniformValue;
+
+    use super::*;
+
+    fn view_towards(target: glm::Vec3) -> CameraView {
+        let mut camera = Camera::new(glm::vec3(0.0, 0.0, 5.0));
+        camera.look_at(&target);
+        CameraView::new(camera)
+    }
+
+    #[test]
+    fn render_draws_children_in_view() {
+        let mut device = RecordingDevice::new();
+        let scene = Scene::new(&mut device);
+        device.clear_commands();
+
+        let stats = scene.render(&mut device, &view_towards(glm::Vec3::zeros()), c"model");
+        assert_eq!(
+            stats,
+            CullStats {
+                visible: 1,
+                culled: 0
+            }
+        );
+        assert_eq!(device.draw_calls(), 1);
+
+        let model = scene.children[0].transform.model_matrix();
+        assert!(device.commands.iter().any(|command| matches!(
+            command,
+            RenderCommand::SetUniform { name, value: UniformValue::Mat4(matrix), .. }
+                if name == "model" && *matrix == model
+        )));
+    }
+
+    #[test]
+    fn render_culls_children_behind_the_camera() {
+        let mut device = RecordingDevice::new();
+        let scene = Scene::new(&mut device);
+        device.clear_commands();
+
+        let stats = scene.render(
+            &mut device,
+            &view_towards(glm::vec3(0.0, 0.0, 10.0)),
+            c"model",
+        );
+        assert_eq!(
+            stats,
+            CullStats {
+                visible: 0,
+                culled: 1
+            }
+        );
+        assert_eq!(device.draw_calls(), 0);
+    }
+
+    #[test]
+    fn render_skips_layers_the_view_hides() {
+        let mut device = RecordingDevice::new();
+        let mut scene = Scene::new(&mut device);
+        scene.children[0].layer = 3;
+        device.clear_commands();
+
+        let view = view_towards(glm::Vec3::zeros()).layer_mask(1);
+        assert_eq!(
+            scene.render(&mut device, &view, c"model"),
+            CullStats::default()
+        );
+        assert!(device.commands.is_empty());
+    }
 }
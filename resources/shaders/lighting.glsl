@@ -0,0 +1,34 @@
+// shared by every lit shader, pulled in with #include "lighting.glsl"
+
+#ifndef AMBIENT_STRENGTH
+#define AMBIENT_STRENGTH 0.1
+#endif
+
+#ifndef SPECULAR_STRENGTH
+#define SPECULAR_STRENGTH 0.5
+#endif
+
+#ifndef SHININESS
+#define SHININESS 32.0
+#endif
+
+// ambient, diffuse and specular light reaching a fragment from one point light
+vec3 phongLighting(vec3 normal, vec3 fragPos, vec3 viewPos, vec3 lightPos, vec3 lightColor)
+{
+    // ambient
+    vec3 ambient = AMBIENT_STRENGTH * lightColor;
+
+    // diffuse
+    vec3 norm = normalize(normal);
+    vec3 lightDir = normalize(lightPos - fragPos);
+    float diff = max(dot(norm, lightDir), 0.0);
+    vec3 diffuse = diff * lightColor;
+
+    // specular
+    vec3 viewDir = normalize(viewPos - fragPos);
+    vec3 reflectDir = reflect(-lightDir, norm);
+    float spec = pow(max(dot(viewDir, reflectDir), 0.0), SHININESS);
+    vec3 specular = SPECULAR_STRENGTH * spec * lightColor;
+
+    return ambient + diffuse + specular;
+}
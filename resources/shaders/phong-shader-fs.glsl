@@ -12,27 +12,11 @@ uniform vec3 viewPos;
 
 uniform sampler2D diffuse_texture;
 
+#include "lighting.glsl"
+
 void main()
 {
-    // ambient
-    float ambientStrength = 0.1;
-    vec3 ambient = ambientStrength * lightColor;
-  	
-    // diffuse 
-    vec3 norm = normalize(Normal);
-    vec3 lightDir = normalize(lightPos - FragPos);
-    float diff = max(dot(norm, lightDir), 0.0);
-    vec3 diffuse = diff * lightColor;
-
-    // specular
-    float specularStrength = 0.5;
-    vec3 viewDir = normalize(viewPos - FragPos);
-    vec3 reflectDir = reflect(-lightDir, norm);
-
-    float spec = pow(max(dot(viewDir, reflectDir), 0.0), 32);
-    vec3 specular = specularStrength * spec * lightColor;
-            
+    vec3 lighting = phongLighting(Normal, FragPos, viewPos, lightPos, lightColor);
     vec3 objectColor = texture(diffuse_texture, TexCoord).xyz;
-    vec3 result = (ambient + diffuse + specular) * objectColor;
-    FragColor = vec4(result, 1.0);
-} 
+    FragColor = vec4(lighting * objectColor, 1.0);
+}
@@ -0,0 +1,249 @@
+use std::error::Error;
+
+use crate::capture::FrameRecorder;
+use crate::graphics::Graphics;
+use crate::input_recording::{InputRecorder, InputReplay};
+
+/*
+ * Callbacks driven by Runner. fixed_update runs zero or more times per
+ * frame with a constant dt, update and render run once per frame.
+ * alpha is how far the current frame lies between the last two fixed
+ * steps, in [0, 1), for interpolating simulated state while rendering.
+ */
+pub trait App {
+    fn init(self: &mut Self, _graphics: &mut Graphics) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+
+    fn fixed_update(self: &mut Self, _graphics: &mut Graphics, _dt: f32) {}
+
+    fn update(self: &mut Self, _graphics: &mut Graphics, _dt: f32) {}
+
+    fn render(self: &mut Self, graphics: &mut Graphics, alpha: f32);
+
+    // called with the new framebuffer size before the next update
+    fn resize(self: &mut Self, _graphics: &mut Graphics, _width: u32, _height: u32) {}
+
+    fn shutdown(self: &mut Self, _graphics: &mut Graphics) {}
+}
+
+pub struct Runner {
+    pub graphics: Graphics,
+    pub fixed_timestep: f32,
+    pub max_steps: u32,
+    pub recorder: Option<FrameRecorder>,
+    pub input_recorder: Option<InputRecorder>,
+    pub input_replay: Option<InputReplay>,
+    // frames to run before stopping, headless runs never close on their own
+    pub frame_limit: Option<u64>,
+    accumulator: f32,
+}
+
+impl Runner {
+    pub fn new(graphics: Graphics) -> Self {
+        Runner {
+            graphics,
+            fixed_timestep: 1.0 / 60.0,
+            max_steps: 5,
+            recorder: None,
+            input_recorder: None,
+            input_replay: None,
+            frame_limit: None,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn fixed_timestep(mut self: Self, fixed_timestep: f32) -> Self {
+        self.fixed_timestep = fixed_timestep;
+        self
+    }
+
+    pub fn max_steps(mut self: Self, max_steps: u32) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    // frames are simulated at the recorder's framerate and captured after render
+    pub fn record(mut self: Self, recorder: FrameRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    pub fn record_input(mut self: Self, input_recorder: InputRecorder) -> Self {
+        self.input_recorder = Some(input_recorder);
+        self
+    }
+
+    // replays the recorded deltas and input instead of GLFW's, run stops at its end
+    pub fn replay_input(mut self: Self, input_replay: InputReplay) -> Self {
+        self.input_replay = Some(input_replay);
+        self
+    }
+
+    pub fn frame_limit(mut self: Self, frames: u64) -> Self {
+        self.frame_limit = Some(frames);
+        self
+    }
+
+    pub fn run(self: &mut Self, app: &mut impl App) -> Result<(), Box<dyn Error>> {
+        app.init(&mut self.graphics)?;
+
+        // keep the first frame from seeing the whole startup time as its delta
+        self.graphics.previous_time = self.graphics.get_time();
+        self.accumulator = 0.0;
+
+        // a failed frame still shuts down and flushes, then its error is returned
+        let mut result: Result<(), Box<dyn Error>> = Ok(());
+        let mut frames: u64 = 0;
+        while !self.graphics.window_should_close() {
+            if self.frame_limit.is_some_and(|limit| frames >= limit) {
+                break;
+            }
+
+            // pause rendering while minimized and hide the paused time from the next delta
+            if self.graphics.minimized {
+                self.graphics.wait_events();
+                self.graphics.previous_time = self.graphics.get_time();
+                continue;
+            }
+
+            if self.graphics.resized {
+                let (width, height) = (self.graphics.screen_width, self.graphics.screen_height);
+                app.resize(&mut self.graphics, width, height);
+            }
+
+            self.graphics.update_time();
+            if let Some(recorder) = &self.recorder {
+                self.graphics.delta_time = recorder.frame_delta();
+            }
+            let replay_frame = match &mut self.input_replay {
+                Some(replay) => match replay.next_frame() {
+                    Some(frame) => Some(frame),
+                    None => break,
+                },
+                None => None,
+            };
+            if let Some(frame) = &replay_frame {
+                self.graphics.delta_time = frame.delta_time;
+            }
+            self.step(app, self.graphics.delta_time);
+
+            if let Some(recorder) = &mut self.recorder {
+                if let Err(error) = recorder.capture(&self.graphics) {
+                    result = Err(error);
+                    break;
+                }
+            }
+
+            match &replay_frame {
+                Some(frame) => self.graphics.replay_events(frame),
+                None => self.graphics.check_events(),
+            }
+            if let Some(input_recorder) = &mut self.input_recorder {
+                let recorded = input_recorder.record_frame(
+                    self.graphics.delta_time,
+                    &self.graphics.input,
+                    &self.graphics.gamepads,
+                );
+                if let Err(error) = recorded {
+                    result = Err(error);
+                    break;
+                }
+            }
+            self.graphics.swap_buffers();
+            frames += 1;
+        }
+
+        if let Some(input_recorder) = &mut self.input_recorder {
+            result = result.and(input_recorder.finish());
+        }
+
+        if let Some(recorder) = &mut self.recorder {
+            result = result.and(recorder.finish());
+            recorder.destroy();
+        }
+
+        app.shutdown(&mut self.graphics);
+        result
+    }
+
+    fn step(self: &mut Self, app: &mut impl App, delta: f32) {
+        self.accumulator += delta;
+
+        let mut steps: u32 = 0;
+        while self.accumulator >= self.fixed_timestep && steps < self.max_steps {
+            app.fixed_update(&mut self.graphics, self.fixed_timestep);
+            self.accumulator -= self.fixed_timestep;
+            steps += 1;
+        }
+
+        // drop what could not be simulated instead of spiraling further behind
+        if self.accumulator >= self.fixed_timestep {
+            self.accumulator %= self.fixed_timestep;
+        }
+
+        app.update(&mut self.graphics, delta);
+
+        let alpha: f32 = self.accumulator / self.fixed_timestep;
+        app.render(&mut self.graphics, alpha);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics_config::{GraphicsConfig, HeadlessBackend};
+
+    #[derive(Default)]
+    struct CountingApp {
+        fixed_steps: u32,
+        fixed_dt: Vec<f32>,
+        alphas: Vec<f32>,
+    }
+
+    impl App for CountingApp {
+        fn fixed_update(self: &mut Self, _graphics: &mut Graphics, dt: f32) {
+            self.fixed_steps += 1;
+            self.fixed_dt.push(dt);
+        }
+
+        fn render(self: &mut Self, _graphics: &mut Graphics, alpha: f32) {
+            self.alphas.push(alpha);
+        }
+    }
+
+    #[test]
+    fn step_runs_fixed_steps_up_to_max_steps() {
+        // Runner needs a Graphics, which needs a GL context
+        let graphics = match GraphicsConfig::new()
+            .size(16, 16)
+            .headless(HeadlessBackend::Egl)
+            .build()
+        {
+            Ok(graphics) => graphics,
+            Err(error) => {
+                eprintln!("skipped, no headless GL context: {}", error);
+                return;
+            }
+        };
+        let mut runner = Runner::new(graphics).fixed_timestep(0.25).max_steps(3);
+        let mut app = CountingApp::default();
+
+        runner.step(&mut app, 0.1);
+        assert_eq!(app.fixed_steps, 0);
+        runner.step(&mut app, 0.2);
+        assert_eq!(app.fixed_steps, 1);
+        runner.step(&mut app, 0.5);
+        assert_eq!(app.fixed_steps, 3);
+        assert!(app.fixed_dt.iter().all(|dt| *dt == 0.25));
+
+        // a long frame is cut off at max_steps and the rest dropped
+        runner.step(&mut app, 10.0);
+        assert_eq!(app.fixed_steps, 6);
+        assert!(runner.accumulator < runner.fixed_timestep);
+
+        assert_eq!(app.alphas.len(), 4);
+        assert!((app.alphas[0] - 0.4).abs() < 1e-5);
+        assert!(app.alphas.iter().all(|alpha| (0.0..1.0).contains(alpha)));
+    }
+}
@@ -0,0 +1,140 @@
+extern crate nalgebra_glm as glm;
+
+// axis aligned bounding box, min > max on every axis while empty
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: glm::Vec3,
+    pub max: glm::Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: glm::Vec3, max: glm::Vec3) -> Self {
+        Aabb { min, max }
+    }
+
+    pub fn empty() -> Self {
+        Aabb {
+            min: glm::Vec3::repeat(f32::INFINITY),
+            max: glm::Vec3::repeat(f32::NEG_INFINITY),
+        }
+    }
+
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a glm::Vec3>) -> Self {
+        let mut aabb = Aabb::empty();
+        for point in points {
+            aabb.extend(point);
+        }
+        aabb
+    }
+
+    pub fn is_empty(self: &Self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn extend(self: &mut Self, point: &glm::Vec3) {
+        self.min = glm::min2(&self.min, point);
+        self.max = glm::max2(&self.max, point);
+    }
+
+    pub fn merge(self: &Self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: glm::min2(&self.min, &other.min),
+            max: glm::max2(&self.max, &other.max),
+        }
+    }
+
+    pub fn center(self: &Self) -> glm::Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(self: &Self) -> glm::Vec3 {
+        self.max - self.min
+    }
+
+    // half the size along each axis
+    pub fn extents(self: &Self) -> glm::Vec3 {
+        self.size() * 0.5
+    }
+
+    pub fn contains(self: &Self, point: &glm::Vec3) -> bool {
+        point.x >= self.min.x
+            && point.y >= self.min.y
+            && point.z >= self.min.z
+            && point.x <= self.max.x
+            && point.y <= self.max.y
+            && point.z <= self.max.z
+    }
+
+    pub fn corners(self: &Self) -> [glm::Vec3; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            glm::Vec3::new(min.x, min.y, min.z),
+            glm::Vec3::new(max.x, min.y, min.z),
+            glm::Vec3::new(min.x, max.y, min.z),
+            glm::Vec3::new(max.x, max.y, min.z),
+            glm::Vec3::new(min.x, min.y, max.z),
+            glm::Vec3::new(max.x, min.y, max.z),
+            glm::Vec3::new(min.x, max.y, max.z),
+            glm::Vec3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    // box around the transformed corners, grows under rotation
+    pub fn transformed(self: &Self, matrix: &glm::Mat4) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+        let corners: Vec<glm::Vec3> = self
+            .corners()
+            .iter()
+            .map(|corner| transform_point(matrix, corner))
+            .collect();
+        Aabb::from_points(corners.iter())
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb::empty()
+    }
+}
+
+// applies the full affine transform including translation, unlike a w = 0 vector
+pub fn transform_point(matrix: &glm::Mat4, point: &glm::Vec3) -> glm::Vec3 {
+    (matrix * glm::vec4(point.x, point.y, point.z, 1.0)).xyz()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_boxes_grow_and_merge() {
+        let mut aabb = Aabb::empty();
+        assert!(aabb.is_empty());
+        aabb.extend(&glm::vec3(1.0, 2.0, 3.0));
+        assert!(!aabb.is_empty());
+        assert_eq!(aabb.size(), glm::Vec3::zeros());
+
+        let merged = aabb.merge(&Aabb::new(glm::Vec3::zeros(), glm::Vec3::repeat(1.0)));
+        assert_eq!(
+            merged,
+            Aabb::new(glm::Vec3::zeros(), glm::vec3(1.0, 2.0, 3.0))
+        );
+        assert_eq!(Aabb::empty().merge(&merged), merged);
+    }
+
+    #[test]
+    fn transformed_boxes_enclose_the_rotated_corners() {
+        let aabb = Aabb::new(glm::Vec3::repeat(-1.0), glm::Vec3::repeat(1.0));
+        let moved = aabb.transformed(&glm::translation(&glm::vec3(5.0, 0.0, 0.0)));
+        assert_eq!(moved.center(), glm::vec3(5.0, 0.0, 0.0));
+
+        let rotated = aabb.transformed(&glm::rotation(45.0_f32.to_radians(), &glm::Vec3::z()));
+        let half_diagonal = 2.0_f32.sqrt();
+        assert!((rotated.max.x - half_diagonal).abs() < 1e-5);
+        assert!((rotated.max.z - 1.0).abs() < 1e-5);
+
+        assert!(Aabb::empty().transformed(&glm::Mat4::identity()).is_empty());
+    }
+}
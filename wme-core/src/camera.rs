@@ -1,27 +1,95 @@
 extern crate nalgebra_glm as glm;
 
+use crate::frustum::Frustum;
+use crate::graphics::Resizable;
+use crate::ray::{self, Ray};
+
 pub enum MovementType {
     FORWARD,
     BACKWARD,
     LEFT,
     RIGHT,
+    UP,
+    DOWN,
+}
+
+// Fps keeps movement on the plane normal to world_up, Fly follows the camera's axes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MovementMode {
+    Fly,
+    Fps,
 }
 
+/*
+ * How Camera builds its projection matrix. Perspective and
+ * ReversedZInfinite use the camera's fov, aspect and near, the
+ * orthographic modes its near and far, which may be negative.
+ * ReversedZInfinite maps near to depth 1 and infinity to 0, draw with
+ * DepthFunc::Greater, clear depth to 0 and ClipDepth::ZeroToOne for the
+ * precision it is meant for. ZeroToOne needs GL 4.5 or ARB_clip_control,
+ * without it the depth range stays -1 to 1 and half the precision is
+ * lost. Custom matrices follow the GL convention.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective,
+    // height of the view volume in world units, width follows the aspect
+    Orthographic {
+        height: f32,
+    },
+    OrthographicBounds {
+        left: f32,
+        right: f32,
+        bottom: f32,
+        top: f32,
+    },
+    ReversedZInfinite,
+    Custom(glm::Mat4),
+}
+
+impl Projection {
+    pub fn is_reversed_z(self: &Self) -> bool {
+        matches!(self, Projection::ReversedZInfinite)
+    }
+
+    pub fn is_orthographic(self: &Self) -> bool {
+        matches!(
+            self,
+            Projection::Orthographic { .. } | Projection::OrthographicBounds { .. }
+        )
+    }
+}
+
+/*
+ * A camera oriented by a quaternion, so it can roll and loop past
+ * vertical without gimbal lock. front, up and right are derived from
+ * the orientation, change it through the rotate methods, look_at or
+ * set_orientation rather than writing the vectors directly. pitch()
+ * and yaw() are derived from it too. fly_rotate_camera keeps the
+ * usual mouse look about world_up with the pitch clamped, rotate and
+ * free_rotate_camera turn about the camera's own axes.
+ */
 pub struct Camera {
     pub position: glm::Vec3,
+    // local to world, the camera looks down its local -Z with +Y up
+    orientation: glm::Quat,
     pub front: glm::Vec3,
     pub up: glm::Vec3,
     pub right: glm::Vec3,
-    pub world_right: glm::Vec3,
-    pub pitch: f32,
-    pub yaw: f32,
+    pub world_up: glm::Vec3,
+    pub movement_mode: MovementMode,
     pub movement_speed: f32,
+    // units per second squared towards and back from the wanted velocity
+    pub acceleration: f32,
+    pub deceleration: f32,
+    pub velocity: glm::Vec3,
     pub mouse_sensitivity: f32,
     pub invert_y: bool,
     pub fov: f32,
     pub aspect: f32,
     pub near: f32,
     pub far: f32,
+    pub projection: Projection,
 }
 
 impl Camera {
@@ -35,27 +103,32 @@ impl Camera {
             front: camera.front,
             up: camera.up,
             right: camera.right,
-            world_right: camera.world_right,
-            pitch: camera.pitch,
-            yaw: camera.yaw,
+            orientation: camera.orientation,
+            world_up: camera.world_up,
+            movement_mode: camera.movement_mode,
             movement_speed: camera.movement_speed,
+            acceleration: camera.acceleration,
+            deceleration: camera.deceleration,
+            velocity: camera.velocity,
             mouse_sensitivity: camera.movement_speed,
             invert_y: camera.invert_y,
             fov: camera.fov,
             aspect: camera.aspect,
             near: camera.near,
             far: camera.far,
+            projection: camera.projection,
         }
     }
 
     pub fn dolly_camera(self: &mut Self, movement_type: MovementType, delta: f32) {
         let velocity: f32 = self.movement_speed * delta;
+        let forward: glm::Vec3 = self.movement_axes().0;
         match movement_type {
             MovementType::FORWARD => {
-                self.position += self.front * velocity;
+                self.position += forward * velocity;
             }
             MovementType::BACKWARD => {
-                self.position -= self.front * velocity;
+                self.position -= forward * velocity;
             }
             _ => (),
         }
@@ -63,34 +136,135 @@ impl Camera {
 
     pub fn pan_camera(self: &mut Self, movement_type: MovementType, delta: f32) {
         let velocity: f32 = self.movement_speed * delta;
+        let right: glm::Vec3 = self.movement_axes().1;
         match movement_type {
             MovementType::LEFT => {
-                self.position -= self.right * velocity;
+                self.position -= right * velocity;
             }
             MovementType::RIGHT => {
-                self.position += self.right * velocity;
+                self.position += right * velocity;
+            }
+            _ => (),
+        }
+    }
+
+    // does nothing in Fps mode
+    pub fn lift_camera(self: &mut Self, movement_type: MovementType, delta: f32) {
+        let velocity: f32 = self.movement_speed * delta;
+        let up: glm::Vec3 = self.movement_axes().2;
+        match movement_type {
+            MovementType::UP => {
+                self.position += up * velocity;
+            }
+            MovementType::DOWN => {
+                self.position -= up * velocity;
             }
             _ => (),
         }
     }
 
+    /*
+     * Accelerates towards movement_speed * speed_scale along input, given
+     * as (right, up, forward) with each part in -1..1, and moves by the
+     * resulting velocity. Zero input decelerates to a stop. Set
+     * acceleration and deceleration to infinity for instant movement.
+     */
+    pub fn move_camera(self: &mut Self, input: glm::Vec3, speed_scale: f32, delta: f32) {
+        let (forward, right, up) = self.movement_axes();
+        let mut direction: glm::Vec3 = right * input.x + up * input.y + forward * input.z;
+        // diagonals are no faster than straight lines
+        if glm::length(&direction) > 1.0 {
+            direction = glm::normalize(&direction);
+        }
+        let target: glm::Vec3 = direction * self.movement_speed * speed_scale;
+
+        let rate: f32 = if glm::length(&target) >= glm::length(&self.velocity) {
+            self.acceleration
+        } else {
+            self.deceleration
+        };
+        let change: glm::Vec3 = target - self.velocity;
+        let max_change: f32 = rate * delta;
+        if rate.is_infinite() || glm::length(&change) <= max_change {
+            self.velocity = target;
+        } else {
+            self.velocity += glm::normalize(&change) * max_change;
+        }
+
+        self.position += self.velocity * delta;
+    }
+
     pub fn fly_rotate_camera(self: &mut Self, x_offset: f32, y_offset: f32, delta: f32) {
+        let new_x: f32 = self.mouse_sensitivity * x_offset * delta;
+        let mut new_y: f32 = self.mouse_sensitivity * y_offset * delta;
+
+        if self.invert_y {
+            new_y = -new_y;
+        }
+
+        // stop short of straight up or down so the yaw axis stays defined
+        let pitch: f32 = self.pitch();
+        let new_y: f32 = (pitch + new_y).clamp(-89.0, 89.0) - pitch;
+
+        // yaw about the world up, pitch about the camera's right
+        let yaw: glm::Quat = glm::quat_angle_axis(-new_x.to_radians(), &self.world_up);
+        let pitch: glm::Quat = glm::quat_angle_axis(new_y.to_radians(), &glm::Vec3::x());
+        self.set_orientation(yaw * self.orientation * pitch);
+    }
+
+    // mouse look about the camera's own axes, with no pitch limit
+    pub fn free_rotate_camera(self: &mut Self, x_offset: f32, y_offset: f32, delta: f32) {
         let new_x: f32 = self.mouse_sensitivity * x_offset * delta;
         let new_y: f32 = self.mouse_sensitivity * y_offset * delta;
 
         if self.invert_y {
-            self.pitch -= new_y;
+            self.rotate(new_x, -new_y, 0.0);
         } else {
-            self.pitch += new_y;
+            self.rotate(new_x, new_y, 0.0);
         }
-        self.yaw += new_x;
+    }
 
-        if self.pitch > 89.0 {
-            self.pitch = 89.0;
-        }
-        if self.pitch < -89.0 {
-            self.pitch = -89.0;
+    // positive amounts roll clockwise as seen from the camera
+    pub fn roll_camera(self: &mut Self, amount: f32, delta: f32) {
+        self.rotate(0.0, 0.0, self.mouse_sensitivity * amount * delta);
+    }
+
+    /*
+     * Turns the camera by the given degrees about its own axes: yaw
+     * turns right about up, pitch tilts up about right and roll turns
+     * clockwise about front.
+     */
+    pub fn rotate(self: &mut Self, yaw: f32, pitch: f32, roll: f32) {
+        let yaw: glm::Quat = glm::quat_angle_axis(-yaw.to_radians(), &glm::Vec3::y());
+        let pitch: glm::Quat = glm::quat_angle_axis(pitch.to_radians(), &glm::Vec3::x());
+        let roll: glm::Quat = glm::quat_angle_axis(roll.to_radians(), &-glm::Vec3::z());
+        self.set_orientation(self.orientation * yaw * pitch * roll);
+    }
+
+    // forward, right and up directions movement follows in the current mode
+    pub fn movement_axes(self: &Self) -> (glm::Vec3, glm::Vec3, glm::Vec3) {
+        match self.movement_mode {
+            MovementMode::Fly => (self.front, self.right, self.up),
+            MovementMode::Fps => {
+                let world_up: glm::Vec3 = glm::normalize(&self.world_up);
+                let right: glm::Vec3 = self.right - world_up * glm::dot(&self.right, &world_up);
+                // right stays level when looking straight up or down, unlike front
+                let right: glm::Vec3 = if glm::length(&right) <= f32::EPSILON {
+                    self.right
+                } else {
+                    glm::normalize(&right)
+                };
+                (glm::cross(&world_up, &right), right, glm::Vec3::zeros())
+            }
         }
+    }
+
+    pub fn orientation(self: &Self) -> glm::Quat {
+        self.orientation
+    }
+
+    pub fn set_orientation(self: &mut Self, orientation: glm::Quat) {
+        self.orientation = glm::quat_normalize(&orientation);
         recalculate_vectors(self);
     }
 
@@ -100,23 +274,135 @@ impl Camera {
         self.position.y = current_y_pos;
     }
 
+    // faces the target with up as close to world_up as possible, dropping any roll
+    pub fn look_at(self: &mut Self, target: &glm::Vec3) {
+        let direction = target - self.position;
+        if glm::length(&direction) <= f32::EPSILON {
+            return;
+        }
+        let front = glm::normalize(&direction);
+        let mut right = glm::cross(&front, &self.world_up);
+        // looking straight along world_up, keep the current heading
+        if glm::length(&right) <= 1e-4 {
+            right = self.right - front * glm::dot(&self.right, &front);
+        }
+        // rolled so far that the old right is the new front
+        if glm::length(&right) <= 1e-4 {
+            right = glm::cross(&front, &self.up);
+        }
+        let right = glm::normalize(&right);
+        let up = glm::cross(&right, &front);
+        let basis = glm::Mat3::from_columns(&[right, up, -front]);
+        self.set_orientation(glm::mat3_to_quat(&basis));
+    }
+
+    // degrees above the horizontal plane
+    pub fn pitch(self: &Self) -> f32 {
+        let world_up = glm::normalize(&self.world_up);
+        glm::dot(&self.front, &world_up)
+            .clamp(-1.0, 1.0)
+            .asin()
+            .to_degrees()
+    }
+
+    // degrees from +X towards +Z, -90 faces down -Z as a new camera does
+    pub fn yaw(self: &Self) -> f32 {
+        self.front.z.atan2(self.front.x).to_degrees()
+    }
+
     pub fn get_view_matrix(self: &Self) -> glm::Mat4 {
         glm::look_at(&self.position, &(self.position + self.front), &self.up)
     }
 
     pub fn get_projection_matrix(self: &Self) -> glm::Mat4 {
-        glm::perspective(self.aspect, self.fov.to_radians(), self.near, self.far)
+        match self.projection {
+            Projection::Perspective => {
+                glm::perspective(self.aspect, self.fov.to_radians(), self.near, self.far)
+            }
+            Projection::Orthographic { height } => {
+                let half_height: f32 = height * 0.5;
+                let half_width: f32 = half_height * self.aspect;
+                glm::ortho(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.near,
+                    self.far,
+                )
+            }
+            Projection::OrthographicBounds {
+                left,
+                right,
+                bottom,
+                top,
+            } => glm::ortho(left, right, bottom, top, self.near, self.far),
+            Projection::ReversedZInfinite => {
+                let f: f32 = 1.0 / (self.fov.to_radians() * 0.5).tan();
+                let mut projection: glm::Mat4 = glm::Mat4::zeros();
+                projection[(0, 0)] = f / self.aspect;
+                projection[(1, 1)] = f;
+                projection[(2, 3)] = self.near;
+                projection[(3, 2)] = -1.0;
+                projection
+            }
+            Projection::Custom(projection) => projection,
+        }
+    }
+
+    pub fn frustum(self: &Self) -> Frustum {
+        let view_projection = self.get_projection_matrix() * self.get_view_matrix();
+        if self.projection.is_reversed_z() {
+            Frustum::from_reversed_z_matrix(&view_projection)
+        } else {
+            Frustum::from_matrix(&view_projection)
+        }
+    }
+
+    // world units covered by the view's height at a distance in front of the camera
+    pub fn view_height(self: &Self, distance: f32) -> f32 {
+        let projection: glm::Mat4 = self.get_projection_matrix();
+        let scale: f32 = 2.0 / projection[(1, 1)];
+        // perspective divides by the distance, orthographic doesn't
+        if projection[(3, 2)] != 0.0 {
+            scale * distance
+        } else {
+            scale
+        }
+    }
+
+    // world space ray through a cursor position given in window coordinates
+    pub fn screen_ray(self: &Self, x: f32, y: f32, width: f32, height: f32) -> Ray {
+        let depths: (f32, f32) = if self.projection.is_reversed_z() {
+            (1.0, 0.5)
+        } else {
+            (-1.0, 0.0)
+        };
+        ray::screen_to_world_ray_between(
+            x,
+            y,
+            width,
+            height,
+            &self.get_view_matrix(),
+            &self.get_projection_matrix(),
+            depths,
+        )
+    }
+}
+
+impl Resizable for Camera {
+    fn resize(self: &mut Self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.aspect = width as f32 / height as f32;
+        }
     }
 }
 
 fn recalculate_vectors(camera: &mut Camera) {
-    let mut new_front: glm::Vec3 = glm::Vec3::zeros();
-    new_front.x = camera.yaw.to_radians().cos() * camera.pitch.to_radians().cos();
-    new_front.y = camera.pitch.to_radians().sin();
-    new_front.z = camera.yaw.to_radians().sin() * camera.pitch.to_radians().cos();
-    camera.front = glm::normalize(&new_front);
-    camera.right = glm::normalize(&glm::cross(&camera.front, &camera.world_right));
-    camera.up = glm::normalize(&glm::cross(&camera.right, &camera.front));
+    let orientation: &glm::Quat = &camera.orientation;
+    camera.front = glm::quat_rotate_vec3(orientation, &-glm::Vec3::z());
+    camera.up = glm::quat_rotate_vec3(orientation, &glm::Vec3::y());
+    camera.right = glm::quat_rotate_vec3(orientation, &glm::Vec3::x());
 }
 
 impl Default for Camera {
@@ -125,17 +411,75 @@ impl Default for Camera {
             position: glm::Vec3::zeros(),
             front: glm::Vec3::new(0.0, 0.0, -1.0),
             up: glm::Vec3::new(0.0, 1.0, 0.0),
-            right: glm::Vec3::zeros(),
-            world_right: glm::Vec3::new(0.0, 1.0, 0.0),
-            pitch: 0.0,
-            yaw: -90.0,
+            right: glm::Vec3::new(1.0, 0.0, 0.0),
+            orientation: glm::quat_identity(),
+            world_up: glm::Vec3::new(0.0, 1.0, 0.0),
+            movement_mode: MovementMode::Fly,
             movement_speed: 2.5,
+            acceleration: 20.0,
+            deceleration: 15.0,
+            velocity: glm::Vec3::zeros(),
             mouse_sensitivity: 0.1,
             invert_y: true,
             fov: 45.0,
-            aspect: (800 / 600) as f32,
+            aspect: 800.0 / 600.0,
             near: 0.1,
             far: 100.0,
+            projection: Projection::Perspective,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_orthonormal(camera: &Camera) {
+        for vector in [camera.front, camera.up, camera.right] {
+            assert!((glm::length(&vector) - 1.0).abs() < 1e-4, "{:?}", vector);
+        }
+        assert!(glm::dot(&camera.front, &camera.up).abs() < 1e-4);
+        assert!(glm::dot(&camera.front, &camera.right).abs() < 1e-4);
+        assert!(glm::dot(&camera.up, &camera.right).abs() < 1e-4);
+    }
+
+    #[test]
+    fn look_at_faces_the_target_upright() {
+        let mut camera = Camera::new(glm::vec3(0.0, 0.0, 5.0));
+        camera.look_at(&glm::vec3(5.0, 0.0, 5.0));
+        assert!(glm::distance(&camera.front, &glm::Vec3::x()) < 1e-4);
+        assert!(glm::distance(&camera.up, &glm::Vec3::y()) < 1e-4);
+        assert!((camera.yaw() - 0.0).abs() < 1e-3);
+        assert_orthonormal(&camera);
+    }
+
+    #[test]
+    fn look_at_along_world_up_keeps_an_orthonormal_basis() {
+        let mut camera = Camera::new(glm::Vec3::zeros());
+        // tilted so the old right is not perpendicular to the new front
+        camera.set_orientation(glm::quat_angle_axis(0.3, &glm::Vec3::z()));
+        camera.look_at(&glm::vec3(0.0, 10.0, 0.0));
+        assert!(glm::distance(&camera.front, &glm::Vec3::y()) < 1e-4);
+        assert_orthonormal(&camera);
+
+        // rolled a quarter turn, the old right points straight up
+        let mut camera = Camera::new(glm::Vec3::zeros());
+        camera.set_orientation(glm::quat_angle_axis(
+            std::f32::consts::FRAC_PI_2,
+            &glm::Vec3::z(),
+        ));
+        camera.look_at(&glm::vec3(0.0, 10.0, 0.0));
+        assert_orthonormal(&camera);
+    }
+
+    #[test]
+    fn set_orientation_updates_the_vectors() {
+        let mut camera = Camera::new(glm::Vec3::zeros());
+        let turn = glm::quat_angle_axis(std::f32::consts::FRAC_PI_2, &glm::Vec3::y());
+        camera.set_orientation(turn * 2.0);
+        // normalized on the way in
+        assert!((glm::quat_length(&camera.orientation()) - 1.0).abs() < 1e-5);
+        assert!(glm::distance(&camera.front, &-glm::Vec3::x()) < 1e-4);
+        assert_orthonormal(&camera);
+    }
+}
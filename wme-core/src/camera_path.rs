@@ -0,0 +1,588 @@
+extern crate nalgebra_glm as glm;
+
+use std::{error::Error, fmt, path::Path};
+
+use crate::camera::Camera;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interpolation {
+    // passes through every key
+    CatmullRom,
+    // keys 0, 3, 6, ... are passed through, the two between each pair are control points
+    Bezier,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    // cubic curves mapping 0..1 onto 0..1
+    pub fn apply(self: &Self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (2.0 - 2.0 * t).powi(3) * 0.5
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathKey {
+    // seconds from the start of the path
+    pub time: f32,
+    pub position: glm::Vec3,
+    // keys without one take it from the orientation keys around them
+    pub orientation: Option<glm::Quat>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CameraPathError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for CameraPathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for CameraPathError {}
+
+/*
+ * A timed rail for the camera. update() advances the playback time and
+ * moves the camera along the spline through the keys. The camera faces
+ * look_at when set, otherwise it follows the orientation keys, and
+ * with neither it faces along the path. Easing applies to the whole
+ * path, or to each lap when looping.
+ *
+ * Paths load from text files, one statement per line:
+ *
+ *   interpolation catmull_rom|bezier
+ *   easing linear|ease_in|ease_out|ease_in_out
+ *   loop
+ *   look_at <x> <y> <z>
+ *   key <time> <x> <y> <z> [<yaw> <pitch> <roll>]
+ *
+ * Key angles are in degrees and match Camera::yaw and Camera::pitch,
+ * roll turns clockwise.
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraPath {
+    pub keys: Vec<PathKey>,
+    pub interpolation: Interpolation,
+    pub easing: Easing,
+    pub looping: bool,
+    pub look_at: Option<glm::Vec3>,
+    // playback time in seconds since the first key
+    pub time: f32,
+    pub playing: bool,
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(source: &str) -> Result<Self, CameraPathError> {
+        let mut path = CameraPath::new();
+        let mut last_key_line = 0;
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let error = |message: &str| CameraPathError {
+                line: line_number,
+                message: String::from(message),
+            };
+
+            let line = line.split('#').next().unwrap_or("").trim();
+            let mut words = line.split_whitespace();
+            let statement = match words.next() {
+                Some(statement) => statement,
+                None => continue,
+            };
+            let arguments: Vec<&str> = words.collect();
+            let numbers = || -> Result<Vec<f32>, CameraPathError> {
+                arguments
+                    .iter()
+                    .map(|argument| {
+                        argument
+                            .parse::<f32>()
+                            .map_err(|_| error(&format!("invalid number '{}'", argument)))
+                    })
+                    .collect()
+            };
+
+            match (statement, arguments.as_slice()) {
+                ("interpolation", ["catmull_rom"]) => {
+                    path.interpolation = Interpolation::CatmullRom
+                }
+                ("interpolation", ["bezier"]) => path.interpolation = Interpolation::Bezier,
+                ("interpolation", _) => {
+                    return Err(error("expected 'interpolation catmull_rom|bezier'"))
+                }
+                ("easing", [easing]) => {
+                    path.easing = match *easing {
+                        "linear" => Easing::Linear,
+                        "ease_in" => Easing::EaseIn,
+                        "ease_out" => Easing::EaseOut,
+                        "ease_in_out" => Easing::EaseInOut,
+                        _ => return Err(error(&format!("unknown easing '{}'", easing))),
+                    }
+                }
+                ("easing", _) => return Err(error("expected 'easing <name>'")),
+                ("loop", []) => path.looping = true,
+                ("look_at", [_, _, _]) => {
+                    let numbers = numbers()?;
+                    path.look_at = Some(glm::Vec3::new(numbers[0], numbers[1], numbers[2]));
+                }
+                ("look_at", _) => return Err(error("expected 'look_at <x> <y> <z>'")),
+                ("key", [_, _, _, _]) | ("key", [_, _, _, _, _, _, _]) => {
+                    let numbers = numbers()?;
+                    if path.keys.last().is_some_and(|key| key.time > numbers[0]) {
+                        return Err(error("key times must not decrease"));
+                    }
+                    let orientation = match numbers[4..] {
+                        [yaw, pitch, roll] => Some(orientation_from_angles(yaw, pitch, roll)),
+                        _ => None,
+                    };
+                    path.keys.push(PathKey {
+                        time: numbers[0],
+                        position: glm::Vec3::new(numbers[1], numbers[2], numbers[3]),
+                        orientation,
+                    });
+                    last_key_line = line_number;
+                }
+                ("key", _) => {
+                    return Err(error(
+                        "expected 'key <time> <x> <y> <z> [<yaw> <pitch> <roll>]'",
+                    ))
+                }
+                _ => return Err(error(&format!("unknown statement '{}'", statement))),
+            }
+        }
+
+        if path.interpolation == Interpolation::Bezier && path.keys.len() % 3 != 1 {
+            return Err(CameraPathError {
+                line: last_key_line,
+                message: String::from("bezier paths need 3n + 1 keys"),
+            });
+        }
+
+        Ok(path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let source = std::fs::read_to_string(path)?;
+        CameraPath::parse(&source).map_err(|error| format!("{}: {}", path.display(), error).into())
+    }
+
+    // keeps the keys sorted by time, after any keys at the same time
+    pub fn add_key(self: &mut Self, key: PathKey) {
+        let index = self.keys.partition_point(|other| other.time <= key.time);
+        self.keys.insert(index, key);
+    }
+
+    pub fn duration(self: &Self) -> f32 {
+        match (self.keys.first(), self.keys.last()) {
+            (Some(first), Some(last)) => last.time - first.time,
+            _ => 0.0,
+        }
+    }
+
+    pub fn restart(self: &mut Self) {
+        self.time = 0.0;
+        self.playing = true;
+    }
+
+    // never true for looping paths
+    pub fn is_finished(self: &Self) -> bool {
+        !self.looping && self.time >= self.duration()
+    }
+
+    // advances playback and moves the camera, does nothing without keys
+    pub fn update(self: &mut Self, delta: f32, camera: &mut Camera) {
+        if self.playing {
+            self.time += delta;
+            if self.is_finished() {
+                self.time = self.duration();
+                self.playing = false;
+            }
+        }
+        self.apply(camera);
+    }
+
+    // places the camera where the path is at the current time
+    pub fn apply(self: &Self, camera: &mut Camera) {
+        if self.keys.is_empty() {
+            return;
+        }
+        let time = self.path_time(self.time);
+        let position = self.position_at(time);
+        camera.position = position;
+
+        if let Some(target) = self.look_at {
+            camera.look_at(&target);
+        } else if let Some(orientation) = self.orientation_at(time) {
+            camera.set_orientation(orientation);
+        } else {
+            camera.look_at(&(position + self.direction_at(time)));
+        }
+    }
+
+    // key time reached after playing for time seconds, with looping and easing applied
+    pub fn path_time(self: &Self, time: f32) -> f32 {
+        let start = self.keys.first().map_or(0.0, |key| key.time);
+        let duration = self.duration();
+        if duration <= 0.0 {
+            return start;
+        }
+        let time = if self.looping {
+            time.rem_euclid(duration)
+        } else {
+            time.clamp(0.0, duration)
+        };
+        start + self.easing.apply(time / duration) * duration
+    }
+
+    pub fn position_at(self: &Self, time: f32) -> glm::Vec3 {
+        let keys = &self.keys;
+        if keys.len() < 2 {
+            return keys.first().map_or(glm::Vec3::zeros(), |key| key.position);
+        }
+
+        match self.interpolation {
+            // keys past the last complete segment are ignored
+            Interpolation::Bezier if keys.len() >= 4 => {
+                let anchors: Vec<usize> = (0..keys.len()).step_by(3).collect();
+                let (segment, t) = segment_at(&anchors, keys, time);
+                let index = anchors[segment];
+                let last = keys.len() - 1;
+                cubic_bezier(
+                    &keys[index].position,
+                    &keys[(index + 1).min(last)].position,
+                    &keys[(index + 2).min(last)].position,
+                    &keys[(index + 3).min(last)].position,
+                    t,
+                )
+            }
+            _ => {
+                let all: Vec<usize> = (0..keys.len()).collect();
+                let (index, t) = segment_at(&all, keys, time);
+                let (before, after) = self.neighbours(index);
+                catmull_rom(
+                    &keys[before].position,
+                    &keys[index].position,
+                    &keys[index + 1].position,
+                    &keys[after].position,
+                    t,
+                )
+            }
+        }
+    }
+
+    /*
+     * The keys before index and after index + 1 that shape the
+     * Catmull-Rom segment between them. Open paths repeat their end
+     * keys, looping paths wrap around. A loop that repeats its first
+     * key at the end is closed and stays smooth through that key.
+     */
+    fn neighbours(self: &Self, index: usize) -> (usize, usize) {
+        let last = self.keys.len() - 1;
+        if !self.looping {
+            return (index.saturating_sub(1), (index + 2).min(last));
+        }
+        let closed =
+            glm::distance(&self.keys[0].position, &self.keys[last].position) <= f32::EPSILON;
+        let count = if closed { last } else { last + 1 };
+        ((index + count - 1) % count, (index + 2) % count)
+    }
+
+    // slerps between the orientation keys on either side, None without any
+    pub fn orientation_at(self: &Self, time: f32) -> Option<glm::Quat> {
+        let before = self
+            .keys
+            .iter()
+            .rev()
+            .find(|key| key.time <= time && key.orientation.is_some());
+        let after = self
+            .keys
+            .iter()
+            .find(|key| key.time >= time && key.orientation.is_some());
+
+        match (before, after) {
+            (Some(before), Some(after)) => {
+                let (from, to) = (before.orientation?, after.orientation?);
+                let span = after.time - before.time;
+                if span <= f32::EPSILON {
+                    return Some(from);
+                }
+                Some(glm::quat_slerp(&from, &to, (time - before.time) / span))
+            }
+            (Some(key), None) | (None, Some(key)) => key.orientation,
+            (None, None) => None,
+        }
+    }
+
+    // normalized direction of travel, -Z where the path doesn't move
+    pub fn direction_at(self: &Self, time: f32) -> glm::Vec3 {
+        let step = (self.duration() * 0.001).max(1e-4);
+        let direction = self.position_at(time + step) - self.position_at(time - step);
+        if glm::length(&direction) <= f32::EPSILON {
+            return -glm::Vec3::z();
+        }
+        glm::normalize(&direction)
+    }
+}
+
+impl Default for CameraPath {
+    fn default() -> Self {
+        CameraPath {
+            keys: Vec::new(),
+            interpolation: Interpolation::CatmullRom,
+            easing: Easing::Linear,
+            looping: false,
+            look_at: None,
+            time: 0.0,
+            playing: true,
+        }
+    }
+}
+
+// the orientation of a camera with these Camera::yaw, Camera::pitch and roll, in degrees
+pub fn orientation_from_angles(yaw: f32, pitch: f32, roll: f32) -> glm::Quat {
+    // an unrotated camera faces -Z, which is a yaw of -90
+    let yaw = glm::quat_angle_axis(-(yaw + 90.0).to_radians(), &glm::Vec3::y());
+    let pitch = glm::quat_angle_axis(pitch.to_radians(), &glm::Vec3::x());
+    let roll = glm::quat_angle_axis(roll.to_radians(), &-glm::Vec3::z());
+    yaw * pitch * roll
+}
+
+// index into points of the segment containing time and how far along it time is
+fn segment_at(points: &[usize], keys: &[PathKey], time: f32) -> (usize, f32) {
+    let segments = points.len().saturating_sub(1).max(1);
+    let segment = points[1..]
+        .iter()
+        .position(|index| time <= keys[*index].time)
+        .unwrap_or(segments - 1)
+        .min(segments - 1);
+    let start = keys[points[segment]].time;
+    let end = keys[points[(segment + 1).min(points.len() - 1)]].time;
+    if end - start <= f32::EPSILON {
+        return (segment, 1.0);
+    }
+    (segment, ((time - start) / (end - start)).clamp(0.0, 1.0))
+}
+
+fn catmull_rom(
+    p0: &glm::Vec3,
+    p1: &glm::Vec3,
+    p2: &glm::Vec3,
+    p3: &glm::Vec3,
+    t: f32,
+) -> glm::Vec3 {
+    let (t2, t3) = (t * t, t * t * t);
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+fn cubic_bezier(
+    p0: &glm::Vec3,
+    p1: &glm::Vec3,
+    p2: &glm::Vec3,
+    p3: &glm::Vec3,
+    t: f32,
+) -> glm::Vec3 {
+    let u = 1.0 - t;
+    p0 * (u * u * u) + p1 * (3.0 * u * u * t) + p2 * (3.0 * u * t * t) + p3 * (t * t * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(source: &str) -> CameraPathError {
+        CameraPath::parse(source).unwrap_err()
+    }
+
+    fn assert_near(a: &glm::Vec3, b: &glm::Vec3) {
+        assert!(glm::distance(a, b) < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    // a unit square in the xz plane, closed by repeating the first key
+    fn square(looping: bool) -> CameraPath {
+        let mut path = CameraPath::parse(
+            "key 0 0 0 0\n\
+             key 1 1 0 0\n\
+             key 2 1 0 1\n\
+             key 3 0 0 1\n\
+             key 4 0 0 0\n",
+        )
+        .unwrap();
+        path.looping = looping;
+        path
+    }
+
+    #[test]
+    fn parses_every_statement() {
+        let path = CameraPath::parse(
+            "# flythrough\n\
+             interpolation catmull_rom\n\
+             easing ease_in_out\n\
+             loop\n\
+             look_at 0 1 0\n\
+             key 0 0 0 5\n\
+             key 2.5 5 0 0 -90 10 0  # with an orientation\n",
+        )
+        .unwrap();
+        assert_eq!(path.interpolation, Interpolation::CatmullRom);
+        assert_eq!(path.easing, Easing::EaseInOut);
+        assert!(path.looping);
+        assert_eq!(path.look_at, Some(glm::vec3(0.0, 1.0, 0.0)));
+        assert_eq!(path.keys.len(), 2);
+        assert_eq!(path.keys[1].time, 2.5);
+        assert_eq!(path.keys[0].orientation, None);
+        assert!(path.keys[1].orientation.is_some());
+        assert_eq!(path.duration(), 2.5);
+    }
+
+    #[test]
+    fn reports_errors_with_their_line() {
+        for (source, line, message) in [
+            ("fly 1 2 3", 1, "unknown statement 'fly'"),
+            ("key 0 0 0 0\nkey 1 0 x 0", 2, "invalid number 'x'"),
+            ("key 1 0 0 0\nkey 0 0 0 0", 2, "key times must not decrease"),
+            (
+                "key 0 0 0",
+                1,
+                "expected 'key <time> <x> <y> <z> [<yaw> <pitch> <roll>]'",
+            ),
+            (
+                "key 0 0 0 0 90",
+                1,
+                "expected 'key <time> <x> <y> <z> [<yaw> <pitch> <roll>]'",
+            ),
+            ("easing bouncy", 1, "unknown easing 'bouncy'"),
+            (
+                "interpolation linear",
+                1,
+                "expected 'interpolation catmull_rom|bezier'",
+            ),
+            ("\nlook_at 1 2", 2, "expected 'look_at <x> <y> <z>'"),
+        ] {
+            let error = parse_error(source);
+            assert_eq!(
+                (error.line, error.message.as_str()),
+                (line, message),
+                "{}",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn bezier_paths_need_3n_plus_1_keys() {
+        let keys = |count: usize| -> String {
+            (0..count)
+                .map(|index| format!("key {} {} 0 0\n", index, index))
+                .collect()
+        };
+        for count in [1, 4, 7] {
+            assert!(CameraPath::parse(&format!("interpolation bezier\n{}", keys(count))).is_ok());
+        }
+        for count in [2, 3, 5, 6] {
+            let error = parse_error(&format!("interpolation bezier\n{}", keys(count)));
+            assert_eq!(error.message, "bezier paths need 3n + 1 keys");
+            // points at the last key
+            assert_eq!(error.line, count + 1);
+        }
+    }
+
+    #[test]
+    fn path_time_loops_clamps_and_eases() {
+        let mut path = CameraPath::parse("key 1 0 0 0\nkey 5 1 0 0").unwrap();
+        assert_eq!(path.path_time(-1.0), 1.0);
+        assert_eq!(path.path_time(2.0), 3.0);
+        assert_eq!(path.path_time(10.0), 5.0);
+
+        path.looping = true;
+        assert_eq!(path.path_time(5.0), 2.0);
+        assert_eq!(path.path_time(-1.0), 4.0);
+
+        path.looping = false;
+        path.easing = Easing::EaseIn;
+        // an eighth of the way at half time
+        assert_eq!(path.path_time(2.0), 1.5);
+        path.easing = Easing::EaseInOut;
+        assert_eq!(path.path_time(2.0), 3.0);
+        assert_eq!(Easing::EaseOut.apply(0.5), 0.875);
+    }
+
+    #[test]
+    fn splines_pass_through_their_keys() {
+        let path = square(false);
+        for key in &path.keys {
+            assert_near(&path.position_at(key.time), &key.position);
+        }
+
+        let mut bezier = square(false);
+        bezier.interpolation = Interpolation::Bezier;
+        bezier.keys.truncate(4);
+        assert_near(&bezier.position_at(0.0), &bezier.keys[0].position);
+        assert_near(&bezier.position_at(3.0), &bezier.keys[3].position);
+        // the control points pull the curve but are not on it
+        assert!(glm::distance(&bezier.position_at(1.0), &bezier.keys[1].position) > 0.1);
+    }
+
+    #[test]
+    fn looping_catmull_rom_is_smooth_through_the_closing_key() {
+        let step = 1e-3;
+        let velocities = |path: &CameraPath| {
+            let start = (path.position_at(step) - path.position_at(0.0)) / step;
+            let end = (path.position_at(4.0) - path.position_at(4.0 - step)) / step;
+            (start, end)
+        };
+
+        let (start, end) = velocities(&square(true));
+        assert!(glm::distance(&start, &end) < 1e-2, "{:?} {:?}", start, end);
+        // towards the second key and away from the one before the closing key
+        assert!(glm::distance(&start, &glm::vec3(0.5, 0.0, -0.5)) < 1e-2);
+
+        // open paths repeat their end keys instead
+        let (start, end) = velocities(&square(false));
+        assert!(glm::distance(&start, &end) > 0.5);
+    }
+
+    #[test]
+    fn update_stops_at_the_end_unless_looping() {
+        let mut camera = Camera::new(glm::Vec3::zeros());
+        let mut path = square(false);
+        path.update(3.0, &mut camera);
+        assert_near(&camera.position, &glm::vec3(0.0, 0.0, 1.0));
+        path.update(3.0, &mut camera);
+        assert!(path.is_finished());
+        assert!(!path.playing);
+        assert_near(&camera.position, &glm::Vec3::zeros());
+
+        let mut path = square(true);
+        path.update(5.0, &mut camera);
+        assert!(!path.is_finished());
+        assert_near(&camera.position, &glm::vec3(1.0, 0.0, 0.0));
+    }
+}
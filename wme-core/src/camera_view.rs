@@ -0,0 +1,280 @@
+extern crate nalgebra_glm as glm;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::camera::Camera;
+use crate::frustum::Frustum;
+use crate::ray::Ray;
+use crate::render_device::{ClearState, ClipDepth, DepthFunc, DepthState, RenderDevice, Viewport};
+
+pub const ALL_LAYERS: u32 = u32::MAX;
+
+// every view asks for the clip depth each frame, warn about it missing once
+static CLIP_CONTROL_WARNED: AtomicBool = AtomicBool::new(false);
+
+// fractions of the render target, origin at the bottom left like GL viewports
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewportRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl ViewportRect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        ViewportRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn full() -> Self {
+        ViewportRect::new(0.0, 0.0, 1.0, 1.0)
+    }
+
+    // edges are rounded so views that share an edge share it exactly in pixels
+    pub fn to_pixels(self: &Self, width: u32, height: u32) -> Viewport {
+        let (width, height) = (width as f32, height as f32);
+        let left = (self.x * width).round() as i32;
+        let bottom = (self.y * height).round() as i32;
+        let right = ((self.x + self.width) * width).round() as i32;
+        let top = ((self.y + self.height) * height).round() as i32;
+        Viewport::new(left, bottom, (right - left).max(0), (top - bottom).max(0))
+    }
+}
+
+impl Default for ViewportRect {
+    fn default() -> Self {
+        ViewportRect::full()
+    }
+}
+
+/*
+ * A camera and where on the render target it draws. Draw the scene once
+ * per enabled view, in render_order, between begin() and the next
+ * view's begin(). Views that only cover part of the target clear just
+ * their own rectangle, so a view without a clear color draws over the
+ * views ordered before it, as a picture-in-picture does.
+ */
+pub struct CameraView {
+    pub camera: Camera,
+    pub viewport: ViewportRect,
+    // lower orders render first
+    pub order: i32,
+    pub clear_color: Option<glm::Vec4>,
+    pub clear_depth: bool,
+    // bit n set draws objects on layer n
+    pub layer_mask: u32,
+    pub enabled: bool,
+}
+
+impl CameraView {
+    pub fn new(camera: Camera) -> Self {
+        CameraView {
+            camera,
+            viewport: ViewportRect::full(),
+            order: 0,
+            clear_color: Some(glm::Vec4::new(0.1, 0.1, 0.1, 1.0)),
+            clear_depth: true,
+            layer_mask: ALL_LAYERS,
+            enabled: true,
+        }
+    }
+
+    pub fn viewport(mut self: Self, viewport: ViewportRect) -> Self {
+        self.viewport = viewport;
+        self
+    }
+
+    pub fn order(mut self: Self, order: i32) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn clear_color(mut self: Self, clear_color: Option<glm::Vec4>) -> Self {
+        self.clear_color = clear_color;
+        self
+    }
+
+    pub fn clear_depth(mut self: Self, clear_depth: bool) -> Self {
+        self.clear_depth = clear_depth;
+        self
+    }
+
+    pub fn layer_mask(mut self: Self, layer_mask: u32) -> Self {
+        self.layer_mask = layer_mask;
+        self
+    }
+
+    // layers go from 0 to 31
+    pub fn sees_layer(self: &Self, layer: u32) -> bool {
+        layer < 32 && self.layer_mask & (1 << layer) != 0
+    }
+
+    /*
+     * Points the device at this view of a width x height target: sets
+     * the viewport and scissor, the depth state the camera's projection
+     * needs, and clears. Also updates the camera's aspect to the view's.
+     * Call end_views() once every view is drawn.
+     */
+    pub fn begin(self: &mut Self, device: &mut dyn RenderDevice, width: u32, height: u32) {
+        let pixels = self.viewport.to_pixels(width, height);
+        if pixels.width > 0 && pixels.height > 0 {
+            self.camera.aspect = pixels.width as f32 / pixels.height as f32;
+        }
+        device.set_viewport(pixels);
+        device.set_scissor(Some(pixels));
+
+        // without clip control reversed-Z still works, just with less precision
+        let reversed_z = self.camera.projection.is_reversed_z();
+        let clip_depth = if reversed_z {
+            ClipDepth::ZeroToOne
+        } else {
+            ClipDepth::NegativeOneToOne
+        };
+        if !device.set_clip_depth(clip_depth) && !CLIP_CONTROL_WARNED.swap(true, Ordering::Relaxed)
+        {
+            log::warn!("No glClipControl, reversed-Z depth stays in -1..1 with less precision");
+        }
+        device.set_depth(DepthState {
+            test: Some(if reversed_z {
+                DepthFunc::Greater
+            } else {
+                DepthFunc::Less
+            }),
+            write: true,
+        });
+
+        if self.clear_color.is_some() || self.clear_depth {
+            device.clear(ClearState {
+                color: self.clear_color,
+                depth: self
+                    .clear_depth
+                    .then_some(if reversed_z { 0.0 } else { 1.0 }),
+            });
+        }
+    }
+
+    pub fn frustum(self: &Self) -> Frustum {
+        self.camera.frustum()
+    }
+
+    // whether a cursor in window coordinates, as InputState reports it, is over the view
+    pub fn contains(self: &Self, x: f32, y: f32, window_width: f32, window_height: f32) -> bool {
+        self.cursor_to_view(x, y, window_width, window_height)
+            .is_some()
+    }
+
+    // world ray through the cursor, None when the cursor is outside this view
+    pub fn screen_ray(
+        self: &Self,
+        x: f32,
+        y: f32,
+        window_width: f32,
+        window_height: f32,
+    ) -> Option<Ray> {
+        let (x, y) = self.cursor_to_view(x, y, window_width, window_height)?;
+        Some(self.camera.screen_ray(x, y, 1.0, 1.0))
+    }
+
+    // cursor position as a fraction of the view, from its top left
+    fn cursor_to_view(
+        self: &Self,
+        x: f32,
+        y: f32,
+        window_width: f32,
+        window_height: f32,
+    ) -> Option<(f32, f32)> {
+        if window_width <= 0.0 || window_height <= 0.0 {
+            return None;
+        }
+        let rect = &self.viewport;
+        let x = (x / window_width - rect.x) / rect.width;
+        let y = (1.0 - y / window_height - rect.y) / rect.height;
+        if (0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y) {
+            Some((x, 1.0 - y))
+        } else {
+            None
+        }
+    }
+}
+
+// indices of the enabled views, in the order to draw them
+pub fn render_order(views: &[CameraView]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..views.len())
+        .filter(|index| views[*index].enabled)
+        .collect();
+    order.sort_by_key(|index| views[*index].order);
+    order
+}
+
+// drops the last view's scissor and restores the full viewport
+pub fn end_views(device: &mut dyn RenderDevice, width: u32, height: u32) {
+    device.set_scissor(None);
+    device.set_viewport(Viewport::new(0, 0, width as i32, height as i32));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(viewport: ViewportRect) -> CameraView {
+        CameraView::new(Camera::new(glm::Vec3::zeros())).viewport(viewport)
+    }
+
+    #[test]
+    fn neighbouring_views_share_their_edge_in_pixels() {
+        let left = ViewportRect::new(0.0, 0.0, 0.5, 1.0).to_pixels(101, 60);
+        let right = ViewportRect::new(0.5, 0.0, 0.5, 1.0).to_pixels(101, 60);
+        assert_eq!(left, Viewport::new(0, 0, 51, 60));
+        assert_eq!(right, Viewport::new(51, 0, 50, 60));
+
+        let thirds: Vec<Viewport> = (0..3)
+            .map(|index| {
+                ViewportRect::new(0.0, index as f32 / 3.0, 1.0, 1.0 / 3.0).to_pixels(8, 100)
+            })
+            .collect();
+        for pair in thirds.windows(2) {
+            assert_eq!(pair[0].y + pair[0].height, pair[1].y);
+        }
+        assert_eq!(thirds[2].y + thirds[2].height, 100);
+    }
+
+    #[test]
+    fn cursor_positions_flip_to_the_views_bottom_left_origin() {
+        // the bottom left quarter of a 200x100 window
+        let corner = view(ViewportRect::new(0.0, 0.0, 0.5, 0.5));
+        let (x, y) = corner.cursor_to_view(25.0, 90.0, 200.0, 100.0).unwrap();
+        assert!((x - 0.25).abs() < 1e-5);
+        assert!((y - 0.8).abs() < 1e-5);
+
+        // the top of the window is the top of the full view but outside the corner
+        assert_eq!(corner.cursor_to_view(25.0, 10.0, 200.0, 100.0), None);
+        assert!(!corner.contains(150.0, 90.0, 200.0, 100.0));
+        assert!(corner.contains(100.0, 50.0, 200.0, 100.0));
+        assert_eq!(corner.cursor_to_view(25.0, 90.0, 0.0, 100.0), None);
+
+        let full = view(ViewportRect::full());
+        assert_eq!(
+            full.cursor_to_view(0.0, 0.0, 200.0, 100.0),
+            Some((0.0, 0.0))
+        );
+        assert!(full.screen_ray(100.0, 50.0, 200.0, 100.0).is_some());
+    }
+
+    #[test]
+    fn render_order_skips_disabled_views_and_sorts_stably() {
+        let mut views: Vec<CameraView> = [2, 0, -1, 0]
+            .into_iter()
+            .map(|order| view(ViewportRect::full()).order(order))
+            .collect();
+        assert_eq!(render_order(&views), vec![2, 1, 3, 0]);
+
+        views[1].enabled = false;
+        assert_eq!(render_order(&views), vec![2, 3, 0]);
+        assert!(render_order(&[]).is_empty());
+    }
+}
@@ -0,0 +1,399 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    path::{Path, PathBuf},
+    thread::JoinHandle,
+};
+
+use image::{ImageBuffer, Rgba, Rgba32FImage, RgbaImage};
+
+use crate::debug;
+use crate::graphics::Graphics;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureFormat {
+    Png,
+    Exr,
+}
+
+impl CaptureFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("exr") => CaptureFormat::Exr,
+            _ => CaptureFormat::Png,
+        }
+    }
+
+    pub fn extension(self: &Self) -> &'static str {
+        match self {
+            CaptureFormat::Png => "png",
+            CaptureFormat::Exr => "exr",
+        }
+    }
+
+    fn bytes_per_pixel(self: &Self) -> usize {
+        match self {
+            CaptureFormat::Png => 4,
+            CaptureFormat::Exr => 4 * std::mem::size_of::<f32>(),
+        }
+    }
+
+    fn pixel_type(self: &Self) -> gl::types::GLenum {
+        match self {
+            CaptureFormat::Png => gl::UNSIGNED_BYTE,
+            CaptureFormat::Exr => gl::FLOAT,
+        }
+    }
+}
+
+/*
+ * Blocking capture of a framebuffer straight to disk.
+ * Pass 0 (or Graphics::default_framebuffer) for the window's back buffer.
+ */
+pub fn save_framebuffer(
+    framebuffer: u32,
+    width: u32,
+    height: u32,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let format = CaptureFormat::from_path(path);
+    let mut pixels: Vec<u8> = vec![0; width as usize * height as usize * format.bytes_per_pixel()];
+
+    unsafe {
+        bind_read_buffer(framebuffer);
+        gl::ReadPixels(
+            0,
+            0,
+            width as i32,
+            height as i32,
+            gl::RGBA,
+            format.pixel_type(),
+            pixels.as_mut_ptr().cast(),
+        );
+    }
+    debug::check_error(&format!("reading framebuffer {}", framebuffer))?;
+
+    write_image(pixels, width, height, format, path)
+}
+
+pub fn save_screenshot(graphics: &Graphics, path: &Path) -> Result<(), Box<dyn Error>> {
+    save_framebuffer(
+        graphics.default_framebuffer(),
+        graphics.screen_width,
+        graphics.screen_height,
+        path,
+    )
+}
+
+struct PendingCapture {
+    pbo: usize,
+    fence: gl::types::GLsync,
+    width: u32,
+    height: u32,
+    format: CaptureFormat,
+    path: PathBuf,
+}
+
+/*
+ * Asynchronous readback through a ring of pixel buffer objects.
+ * request() only queues a glReadPixels into a PBO, poll() maps the
+ * buffers whose fence has signaled and hands the pixels to a thread
+ * that encodes and writes the file.
+ */
+pub struct FrameCapture {
+    pbos: Vec<u32>,
+    pbo_sizes: Vec<usize>,
+    next_pbo: usize,
+    pending: VecDeque<PendingCapture>,
+    writers: Vec<JoinHandle<Result<(), String>>>,
+}
+
+impl FrameCapture {
+    pub fn new(buffer_count: usize) -> Self {
+        let mut pbos: Vec<u32> = vec![0; buffer_count.max(1)];
+        unsafe {
+            gl::GenBuffers(pbos.len() as i32, pbos.as_mut_ptr());
+        }
+        debug::check_call("glGenBuffers", "(capture buffers)");
+
+        FrameCapture {
+            pbo_sizes: vec![0; pbos.len()],
+            pbos,
+            next_pbo: 0,
+            pending: VecDeque::new(),
+            writers: Vec::new(),
+        }
+    }
+
+    pub fn request(
+        self: &mut Self,
+        framebuffer: u32,
+        width: u32,
+        height: u32,
+        path: &Path,
+    ) -> Result<(), Box<dyn Error>> {
+        // every buffer is still in flight, wait for the oldest one
+        if self.pending.len() == self.pbos.len() {
+            self.finish_oldest(true)?;
+        }
+
+        let format = CaptureFormat::from_path(path);
+        let size = width as usize * height as usize * format.bytes_per_pixel();
+        let pbo = self.next_pbo;
+        self.next_pbo = (self.next_pbo + 1) % self.pbos.len();
+
+        let fence = unsafe {
+            bind_read_buffer(framebuffer);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.pbos[pbo]);
+            if self.pbo_sizes[pbo] != size {
+                gl::BufferData(
+                    gl::PIXEL_PACK_BUFFER,
+                    size as gl::types::GLsizeiptr,
+                    std::ptr::null(),
+                    gl::STREAM_READ,
+                );
+                self.pbo_sizes[pbo] = size;
+            }
+            gl::ReadPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                gl::RGBA,
+                format.pixel_type(),
+                std::ptr::null_mut(),
+            );
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+            gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0)
+        };
+        debug::check_call(
+            "FrameCapture::request",
+            format_args!("({})", path.display()),
+        );
+
+        self.pending.push_back(PendingCapture {
+            pbo,
+            fence,
+            width,
+            height,
+            format,
+            path: path.to_path_buf(),
+        });
+
+        Ok(())
+    }
+
+    // call once per frame, returns how many captures were handed off for writing
+    pub fn poll(self: &mut Self) -> Result<usize, Box<dyn Error>> {
+        let mut count: usize = 0;
+        while self.finish_oldest(false)? {
+            count += 1;
+        }
+        self.join_writers(false)?;
+
+        Ok(count)
+    }
+
+    // blocks until every requested capture is on disk
+    pub fn flush(self: &mut Self) -> Result<(), Box<dyn Error>> {
+        while self.finish_oldest(true)? {}
+        self.join_writers(true)
+    }
+
+    pub fn pending(self: &Self) -> usize {
+        self.pending.len() + self.writers.len()
+    }
+
+    pub fn destroy(self: &mut Self) {
+        for capture in self.pending.drain(..) {
+            unsafe {
+                gl::DeleteSync(capture.fence);
+            }
+        }
+        unsafe {
+            gl::DeleteBuffers(self.pbos.len() as i32, self.pbos.as_ptr());
+        }
+        debug::check_call("FrameCapture::destroy", "");
+        self.pbos.clear();
+    }
+
+    fn finish_oldest(self: &mut Self, block: bool) -> Result<bool, Box<dyn Error>> {
+        let capture = match self.pending.front() {
+            Some(capture) => capture,
+            None => return Ok(false),
+        };
+
+        let timeout: u64 = if block { u64::MAX } else { 0 };
+        let status =
+            unsafe { gl::ClientWaitSync(capture.fence, gl::SYNC_FLUSH_COMMANDS_BIT, timeout) };
+        if status == gl::TIMEOUT_EXPIRED {
+            return Ok(false);
+        }
+
+        let capture = self.pending.pop_front().unwrap();
+        let size = self.pbo_sizes[capture.pbo];
+        let mut pixels: Vec<u8> = vec![0; size];
+        unsafe {
+            gl::DeleteSync(capture.fence);
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.pbos[capture.pbo]);
+            let mapped = gl::MapBufferRange(
+                gl::PIXEL_PACK_BUFFER,
+                0,
+                size as gl::types::GLsizeiptr,
+                gl::MAP_READ_BIT,
+            ) as *const u8;
+            if !mapped.is_null() {
+                std::ptr::copy_nonoverlapping(mapped, pixels.as_mut_ptr(), size);
+                gl::UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+            }
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+        debug::check_call(
+            "FrameCapture::poll",
+            format_args!("({})", capture.path.display()),
+        );
+        if status == gl::WAIT_FAILED {
+            return Err(format!("Capture of {} failed", capture.path.display()).into());
+        }
+
+        self.writers.push(std::thread::spawn(move || {
+            write_image(
+                pixels,
+                capture.width,
+                capture.height,
+                capture.format,
+                &capture.path,
+            )
+            .map_err(|error| format!("{}: {}", capture.path.display(), error))
+        }));
+
+        Ok(true)
+    }
+
+    fn join_writers(self: &mut Self, block: bool) -> Result<(), Box<dyn Error>> {
+        let mut index = 0;
+        while index < self.writers.len() {
+            if !block && !self.writers[index].is_finished() {
+                index += 1;
+                continue;
+            }
+            match self.writers.swap_remove(index).join() {
+                Ok(result) => result?,
+                Err(_) => return Err("Capture writer thread panicked".into()),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for FrameCapture {
+    fn default() -> Self {
+        FrameCapture::new(3)
+    }
+}
+
+/*
+ * Records numbered frames at a fixed simulated framerate.
+ * Runner feeds frame_delta() to the app instead of wall clock time
+ * while a recorder is attached, so output is independent of how
+ * long each frame actually takes to render and encode.
+ */
+pub struct FrameRecorder {
+    pub directory: PathBuf,
+    pub prefix: String,
+    pub format: CaptureFormat,
+    pub fps: f32,
+    pub frame: u32,
+    capture: FrameCapture,
+}
+
+impl FrameRecorder {
+    pub fn new(directory: &Path, fps: f32) -> Result<Self, Box<dyn Error>> {
+        std::fs::create_dir_all(directory)?;
+
+        Ok(FrameRecorder {
+            directory: directory.to_path_buf(),
+            prefix: String::from("frame"),
+            format: CaptureFormat::Png,
+            fps,
+            frame: 0,
+            capture: FrameCapture::default(),
+        })
+    }
+
+    pub fn frame_delta(self: &Self) -> f32 {
+        1.0 / self.fps
+    }
+
+    pub fn frame_path(self: &Self, frame: u32) -> PathBuf {
+        self.directory.join(format!(
+            "{}_{:06}.{}",
+            self.prefix,
+            frame,
+            self.format.extension()
+        ))
+    }
+
+    pub fn capture(self: &mut Self, graphics: &Graphics) -> Result<(), Box<dyn Error>> {
+        let path = self.frame_path(self.frame);
+        self.capture.request(
+            graphics.default_framebuffer(),
+            graphics.screen_width,
+            graphics.screen_height,
+            &path,
+        )?;
+        self.capture.poll()?;
+        self.frame += 1;
+
+        Ok(())
+    }
+
+    pub fn finish(self: &mut Self) -> Result<(), Box<dyn Error>> {
+        self.capture.flush()
+    }
+
+    pub fn destroy(self: &mut Self) {
+        self.capture.destroy();
+    }
+}
+
+unsafe fn bind_read_buffer(framebuffer: u32) {
+    gl::BindFramebuffer(gl::READ_FRAMEBUFFER, framebuffer);
+    gl::ReadBuffer(if framebuffer == 0 {
+        gl::BACK
+    } else {
+        gl::COLOR_ATTACHMENT0
+    });
+    gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+}
+
+fn write_image(
+    pixels: Vec<u8>,
+    width: u32,
+    height: u32,
+    format: CaptureFormat,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    // GL rows start at the bottom of the image
+    match format {
+        CaptureFormat::Png => {
+            let mut image: RgbaImage = ImageBuffer::<Rgba<u8>, _>::from_raw(width, height, pixels)
+                .ok_or("Capture buffer does not match image size")?;
+            image::imageops::flip_vertical_in_place(&mut image);
+            image.save(path)?;
+        }
+        CaptureFormat::Exr => {
+            let floats: Vec<f32> = pixels
+                .chunks_exact(4)
+                .map(|bytes| f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .collect();
+            let mut image: Rgba32FImage = ImageBuffer::from_raw(width, height, floats)
+                .ok_or("Capture buffer does not match image size")?;
+            image::imageops::flip_vertical_in_place(&mut image);
+            image.save(path)?;
+        }
+    }
+
+    Ok(())
+}
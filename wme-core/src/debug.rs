@@ -0,0 +1,210 @@
+use std::{
+    error::Error,
+    ffi::{c_void, CStr},
+    fmt,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+};
+
+use log::{debug, error, info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DebugSeverity {
+    Notification,
+    Low,
+    Medium,
+    High,
+}
+
+impl DebugSeverity {
+    fn from_gl(severity: gl::types::GLenum) -> Self {
+        match severity {
+            gl::DEBUG_SEVERITY_HIGH => DebugSeverity::High,
+            gl::DEBUG_SEVERITY_MEDIUM => DebugSeverity::Medium,
+            gl::DEBUG_SEVERITY_LOW => DebugSeverity::Low,
+            _ => DebugSeverity::Notification,
+        }
+    }
+}
+
+static MIN_SEVERITY: AtomicU8 = AtomicU8::new(DebugSeverity::Low as u8);
+static CHECK_CALLS: AtomicBool = AtomicBool::new(false);
+
+/*
+ * Turns on KHR_debug output and forwards every message at or above
+ * min_severity to the log crate. Needs a context created with
+ * GraphicsConfig::debug_context for drivers to report much of anything.
+ * Returns false when the driver has no KHR_debug support.
+ */
+pub fn enable_debug_output(min_severity: DebugSeverity) -> bool {
+    if !gl::DebugMessageCallback::is_loaded() {
+        warn!("KHR_debug is not available, GL debug output disabled");
+        return false;
+    }
+
+    MIN_SEVERITY.store(min_severity as u8, Ordering::Relaxed);
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(Some(debug_message_callback), std::ptr::null());
+        gl::DebugMessageControl(
+            gl::DONT_CARE,
+            gl::DONT_CARE,
+            gl::DONT_CARE,
+            0,
+            std::ptr::null(),
+            gl::TRUE,
+        );
+    }
+
+    true
+}
+
+pub fn set_min_severity(min_severity: DebugSeverity) {
+    MIN_SEVERITY.store(min_severity as u8, Ordering::Relaxed);
+}
+
+// makes gl_check! and check_call panic on GL errors, only has an effect in debug builds
+pub fn set_call_checks(enabled: bool) {
+    CHECK_CALLS.store(enabled, Ordering::Relaxed);
+}
+
+pub fn call_checks_enabled() -> bool {
+    cfg!(debug_assertions) && CHECK_CALLS.load(Ordering::Relaxed)
+}
+
+// names a GL object in debugger captures and debug messages
+pub fn label_object(identifier: gl::types::GLenum, id: u32, name: &str) {
+    if !gl::ObjectLabel::is_loaded() || id == 0 {
+        return;
+    }
+    unsafe {
+        gl::ObjectLabel(identifier, id, name.len() as i32, name.as_ptr().cast());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlError {
+    pub code: gl::types::GLenum,
+    pub context: String,
+}
+
+impl fmt::Display for GlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} in {}", error_name(self.code), self.context)
+    }
+}
+
+impl Error for GlError {}
+
+// drains glGetError, reporting the first error with context attached
+pub fn check_error(context: &str) -> Result<(), GlError> {
+    let mut first: Option<GlError> = None;
+    loop {
+        let code = unsafe { gl::GetError() };
+        if code == gl::NO_ERROR {
+            break;
+        }
+        if first.is_none() {
+            first = Some(GlError {
+                code,
+                context: context.to_string(),
+            });
+        }
+    }
+
+    match first {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+// panics on a pending GL error when call checks are on, the context is only formatted on failure
+pub fn check_call(call: &str, context: impl fmt::Display) {
+    if !call_checks_enabled() {
+        return;
+    }
+    if let Err(error) = check_error(call) {
+        panic!("{} {}", error, context);
+    }
+}
+
+/*
+ * Wraps a GL call. With call checks on in a debug build, glGetError
+ * is checked right after the call and any error panics with the call
+ * and the optional context:
+ *     gl_check!(unsafe { gl::BindTexture(gl::TEXTURE_2D, id) }, path);
+ */
+#[macro_export]
+macro_rules! gl_check {
+    ($call:expr) => {
+        $crate::gl_check!($call, "")
+    };
+    ($call:expr, $context:expr) => {{
+        let result = $call;
+        $crate::debug::check_call(stringify!($call), $context);
+        result
+    }};
+}
+
+pub fn error_name(code: gl::types::GLenum) -> &'static str {
+    match code {
+        gl::INVALID_ENUM => "GL_INVALID_ENUM",
+        gl::INVALID_VALUE => "GL_INVALID_VALUE",
+        gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+        gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+        gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+        gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+        gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+        _ => "GL_UNKNOWN_ERROR",
+    }
+}
+
+fn source_name(source: gl::types::GLenum) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API => "api",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    }
+}
+
+fn type_name(gltype: gl::types::GLenum) -> &'static str {
+    match gltype {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        _ => "other",
+    }
+}
+
+extern "system" fn debug_message_callback(
+    source: gl::types::GLenum,
+    gltype: gl::types::GLenum,
+    id: gl::types::GLuint,
+    severity: gl::types::GLenum,
+    _length: gl::types::GLsizei,
+    message: *const gl::types::GLchar,
+    _user_param: *mut c_void,
+) {
+    let severity = DebugSeverity::from_gl(severity);
+    if (severity as u8) < MIN_SEVERITY.load(Ordering::Relaxed) || message.is_null() {
+        return;
+    }
+
+    let message = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+    let source = source_name(source);
+    let gltype = type_name(gltype);
+    match severity {
+        DebugSeverity::High => error!("GL {} {} ({}): {}", source, gltype, id, message),
+        DebugSeverity::Medium => warn!("GL {} {} ({}): {}", source, gltype, id, message),
+        DebugSeverity::Low => info!("GL {} {} ({}): {}", source, gltype, id, message),
+        DebugSeverity::Notification => {
+            debug!("GL {} {} ({}): {}", source, gltype, id, message)
+        }
+    }
+}
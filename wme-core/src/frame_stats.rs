@@ -0,0 +1,186 @@
+use std::{collections::VecDeque, fmt};
+
+/*
+ * Rolling window of the most recent frame times in seconds.
+ * All fps queries are derived from the frames in the window.
+ */
+pub struct FrameStats {
+    pub capacity: usize,
+    pub frame_count: u64,
+    frame_times: VecDeque<f32>,
+}
+
+impl FrameStats {
+    pub fn new(capacity: usize) -> Self {
+        FrameStats {
+            capacity,
+            frame_count: 0,
+            frame_times: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(self: &mut Self, frame_time: f32) {
+        if self.frame_times.len() == self.capacity {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+        self.frame_count += 1;
+    }
+
+    pub fn clear(self: &mut Self) {
+        self.frame_times.clear();
+    }
+
+    pub fn len(self: &Self) -> usize {
+        self.frame_times.len()
+    }
+
+    pub fn is_empty(self: &Self) -> bool {
+        self.frame_times.is_empty()
+    }
+
+    pub fn average_frame_time(self: &Self) -> f32 {
+        if self.frame_times.is_empty() {
+            return 0.0;
+        }
+        self.frame_times.iter().sum::<f32>() / self.frame_times.len() as f32
+    }
+
+    pub fn average_fps(self: &Self) -> f32 {
+        to_fps(self.average_frame_time())
+    }
+
+    // slowest frame in the window
+    pub fn min_fps(self: &Self) -> f32 {
+        to_fps(self.frame_times.iter().cloned().fold(0.0, f32::max))
+    }
+
+    // fastest frame in the window
+    pub fn max_fps(self: &Self) -> f32 {
+        match self.frame_times.iter().cloned().reduce(f32::min) {
+            Some(frame_time) => to_fps(frame_time),
+            None => 0.0,
+        }
+    }
+
+    /*
+     * fps that the given percentage of frames stays above,
+     * percentile_fps(1.0) is the usual "1% low".
+     */
+    pub fn percentile_fps(self: &Self, percent: f32) -> f32 {
+        to_fps(self.percentile_frame_time(100.0 - percent))
+    }
+
+    pub fn percentile_frame_time(self: &Self, percent: f32) -> f32 {
+        if self.frame_times.is_empty() {
+            return 0.0;
+        }
+
+        let mut sorted: Vec<f32> = self.frame_times.iter().cloned().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let rank = (percent.clamp(0.0, 100.0) / 100.0) * (sorted.len() - 1) as f32;
+        sorted[rank.round() as usize]
+    }
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        FrameStats::new(240)
+    }
+}
+
+fn to_fps(frame_time: f32) -> f32 {
+    if frame_time > 0.0 {
+        1.0 / frame_time
+    } else {
+        0.0
+    }
+}
+
+impl fmt::Display for FrameStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:.1} fps (min {:.1}, max {:.1}, 1% low {:.1}), {:.2} ms",
+            self.average_fps(),
+            self.min_fps(),
+            self.max_fps(),
+            self.percentile_fps(1.0),
+            self.average_frame_time() * 1000.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-3, "{}", actual);
+    }
+
+    fn stats_from(frame_times: &[f32]) -> FrameStats {
+        let mut stats = FrameStats::new(frame_times.len());
+        for frame_time in frame_times {
+            stats.push(*frame_time);
+        }
+        stats
+    }
+
+    #[test]
+    fn min_and_max_come_from_the_slowest_and_fastest_frames() {
+        let stats = stats_from(&[0.02, 0.01, 0.05, 0.04]);
+        assert_near(stats.min_fps(), 20.0);
+        assert_near(stats.max_fps(), 100.0);
+        assert_near(stats.average_frame_time(), 0.03);
+    }
+
+    #[test]
+    fn percentile_ranks_round_to_the_nearest_frame() {
+        let stats = stats_from(&[0.005, 0.001, 0.004, 0.002, 0.003]);
+        assert_eq!(stats.percentile_frame_time(0.0), 0.001);
+        assert_eq!(stats.percentile_frame_time(50.0), 0.003);
+        // rank 2.4 rounds down, 2.6 up
+        assert_eq!(stats.percentile_frame_time(60.0), 0.003);
+        assert_eq!(stats.percentile_frame_time(65.0), 0.004);
+        assert_eq!(stats.percentile_frame_time(100.0), 0.005);
+        assert_eq!(stats.percentile_frame_time(150.0), 0.005);
+    }
+
+    #[test]
+    fn one_percent_low_uses_the_99th_percentile_frame_time() {
+        let frame_times: Vec<f32> = (1..=101).map(|ms| ms as f32 / 1000.0).collect();
+        let stats = stats_from(&frame_times);
+        assert_eq!(stats.percentile_frame_time(99.0), 0.1);
+        assert_near(stats.percentile_fps(1.0), 10.0);
+    }
+
+    #[test]
+    fn an_empty_window_reports_zero() {
+        let mut stats = FrameStats::new(4);
+        assert!(stats.is_empty());
+        assert_eq!(stats.average_fps(), 0.0);
+        assert_eq!(stats.min_fps(), 0.0);
+        assert_eq!(stats.max_fps(), 0.0);
+        assert_eq!(stats.percentile_fps(1.0), 0.0);
+
+        stats.push(0.01);
+        stats.clear();
+        assert!(stats.is_empty());
+        assert_eq!(stats.frame_count, 1);
+        assert_eq!(stats.average_frame_time(), 0.0);
+    }
+
+    #[test]
+    fn the_oldest_frame_drops_out_at_capacity() {
+        let mut stats = stats_from(&[0.1, 0.01, 0.01]);
+        assert_near(stats.min_fps(), 10.0);
+
+        stats.push(0.02);
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats.frame_count, 4);
+        assert_near(stats.min_fps(), 50.0);
+        assert_near(stats.average_frame_time(), 0.04 / 3.0);
+    }
+}
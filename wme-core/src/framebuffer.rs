@@ -0,0 +1,159 @@
+use std::error::Error;
+
+use crate::debug;
+use crate::render_device::{RenderDevice, Viewport};
+
+/*
+ * Offscreen render target with an RGBA8 color texture and a
+ * combined depth/stencil renderbuffer. The color texture is bound and
+ * deleted through the device so its texture cache stays accurate.
+ */
+pub struct Framebuffer {
+    pub id: u32,
+    pub color_texture: u32,
+    pub depth_renderbuffer: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Framebuffer {
+    pub fn new(
+        device: &mut dyn RenderDevice,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, Box<dyn Error>> {
+        let mut framebuffer = Framebuffer {
+            id: 0,
+            color_texture: 0,
+            depth_renderbuffer: 0,
+            width,
+            height,
+        };
+        framebuffer.create_attachments(device)?;
+
+        Ok(framebuffer)
+    }
+
+    // the viewport is device state, set it with viewport() after binding
+    pub fn bind(self: &Self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+        }
+        debug::check_call(
+            "glBindFramebuffer",
+            format_args!("(framebuffer {})", self.id),
+        );
+    }
+
+    pub fn viewport(self: &Self) -> Viewport {
+        Viewport::new(0, 0, self.width as i32, self.height as i32)
+    }
+
+    pub fn unbind(self: &Self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        debug::check_call("glBindFramebuffer", "(default framebuffer)");
+    }
+
+    // leaves texture unit 0 unbound
+    pub fn resize(
+        self: &mut Self,
+        device: &mut dyn RenderDevice,
+        width: u32,
+        height: u32,
+    ) -> Result<(), Box<dyn Error>> {
+        if width == self.width && height == self.height {
+            return Ok(());
+        }
+
+        self.destroy(device);
+        self.width = width;
+        self.height = height;
+        self.create_attachments(device)
+    }
+
+    pub fn destroy(self: &mut Self, device: &mut dyn RenderDevice) {
+        device.delete_texture(self.color_texture);
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.id);
+            gl::DeleteRenderbuffers(1, &self.depth_renderbuffer);
+        }
+        debug::check_call(
+            "Framebuffer::destroy",
+            format_args!("(framebuffer {})", self.id),
+        );
+        self.id = 0;
+        self.color_texture = 0;
+        self.depth_renderbuffer = 0;
+    }
+
+    fn create_attachments(
+        self: &mut Self,
+        device: &mut dyn RenderDevice,
+    ) -> Result<(), Box<dyn Error>> {
+        unsafe {
+            gl::GenTextures(1, &mut self.color_texture);
+        }
+        device.bind_texture(0, self.color_texture);
+        unsafe {
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                gl::RGBA8 as i32,
+                self.width as i32,
+                self.height as i32,
+                0,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+        }
+        device.bind_texture(0, 0);
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut self.id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                self.color_texture,
+                0,
+            );
+
+            gl::GenRenderbuffers(1, &mut self.depth_renderbuffer);
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.depth_renderbuffer);
+            gl::RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::DEPTH24_STENCIL8,
+                self.width as i32,
+                self.height as i32,
+            );
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+            gl::FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_STENCIL_ATTACHMENT,
+                gl::RENDERBUFFER,
+                self.depth_renderbuffer,
+            );
+        }
+
+        let status = unsafe {
+            let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            status
+        };
+        debug::check_error(&format!(
+            "creating a {}x{} framebuffer",
+            self.width, self.height
+        ))?;
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(format!("Framebuffer incomplete: 0x{:X}", status).into());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,195 @@
+extern crate nalgebra_glm as glm;
+
+use crate::bounds::Aabb;
+
+// points with dot(normal, p) + distance >= 0 are on the inner side
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: glm::Vec3,
+    pub distance: f32,
+}
+
+impl Plane {
+    // from the coefficients of ax + by + cz + d, normalized when possible
+    pub fn from_coefficients(coefficients: &glm::Vec4) -> Self {
+        let normal = coefficients.xyz();
+        let length = glm::length(&normal);
+        if length <= f32::EPSILON {
+            return Plane {
+                normal,
+                distance: coefficients.w,
+            };
+        }
+        Plane {
+            normal: normal / length,
+            distance: coefficients.w / length,
+        }
+    }
+
+    pub fn signed_distance(self: &Self, point: &glm::Vec3) -> f32 {
+        glm::dot(&self.normal, point) + self.distance
+    }
+}
+
+/*
+ * The six planes bounding what a view-projection matrix can see, in
+ * world space, with normals pointing inwards. from_matrix expects GL's
+ * -1..1 clip depth, from_reversed_z_matrix the 0..1 depth reversed-Z
+ * projections use. Infinite projections get a far plane that never
+ * rejects anything. The tests are conservative: boxes near a frustum
+ * corner may pass while outside.
+ */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    // left, right, bottom, top, near, far
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    // -1 <= z / w <= 1, what glm::perspective and glm::ortho produce
+    pub fn from_matrix(view_projection: &glm::Mat4) -> Self {
+        let (w, z) = (row(view_projection, 3), row(view_projection, 2));
+        Self::from_depth_planes(view_projection, w + z, w - z)
+    }
+
+    /*
+     * 0 <= z / w <= 1 with 1 at the near plane. Here w + z is a plane
+     * behind the camera and w - z the real near plane, so from_matrix
+     * would label them the wrong way round.
+     */
+    pub fn from_reversed_z_matrix(view_projection: &glm::Mat4) -> Self {
+        let (w, z) = (row(view_projection, 3), row(view_projection, 2));
+        Self::from_depth_planes(view_projection, w - z, z)
+    }
+
+    fn from_depth_planes(view_projection: &glm::Mat4, near: glm::Vec4, far: glm::Vec4) -> Self {
+        let (x, y, w) = (
+            row(view_projection, 0),
+            row(view_projection, 1),
+            row(view_projection, 3),
+        );
+
+        Frustum {
+            planes: [
+                Plane::from_coefficients(&(w + x)),
+                Plane::from_coefficients(&(w - x)),
+                Plane::from_coefficients(&(w + y)),
+                Plane::from_coefficients(&(w - y)),
+                Plane::from_coefficients(&near),
+                Plane::from_coefficients(&far),
+            ],
+        }
+    }
+
+    pub fn contains_point(self: &Self, point: &glm::Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+
+    pub fn intersects_sphere(self: &Self, center: &glm::Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(center) >= -radius)
+    }
+
+    // empty boxes are never visible
+    pub fn intersects_aabb(self: &Self, aabb: &Aabb) -> bool {
+        if aabb.is_empty() {
+            return false;
+        }
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the normal is the last one to leave
+            let corner = aabb
+                .min
+                .zip_zip_map(&aabb.max, &plane.normal, |min, max, normal| {
+                    if normal >= 0.0 {
+                        max
+                    } else {
+                        min
+                    }
+                });
+            plane.signed_distance(&corner) >= 0.0
+        })
+    }
+}
+
+fn row(matrix: &glm::Mat4, index: usize) -> glm::Vec4 {
+    matrix.row(index).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::{Camera, Projection};
+
+    // at the origin looking down -z, 90 degrees vertically, near 0.1 and far 100
+    fn perspective() -> Frustum {
+        let projection = glm::perspective(1.0, 90.0_f32.to_radians(), 0.1, 100.0);
+        Frustum::from_matrix(&projection)
+    }
+
+    #[test]
+    fn planes_point_inwards() {
+        let frustum = perspective();
+        let [left, right, bottom, top, near, far] = frustum.planes;
+        assert!(glm::dot(&left.normal, &glm::Vec3::x()) > 0.0);
+        assert!(glm::dot(&right.normal, &glm::Vec3::x()) < 0.0);
+        assert!(glm::dot(&bottom.normal, &glm::Vec3::y()) > 0.0);
+        assert!(glm::dot(&top.normal, &glm::Vec3::y()) < 0.0);
+        assert!((near.signed_distance(&glm::vec3(0.0, 0.0, -0.1))).abs() < 1e-4);
+        assert!((far.signed_distance(&glm::vec3(0.0, 0.0, -100.0))).abs() < 1e-2);
+        assert!(glm::dot(&near.normal, &-glm::Vec3::z()) > 0.99);
+    }
+
+    #[test]
+    fn points_inside_and_outside() {
+        let frustum = perspective();
+        assert!(frustum.contains_point(&glm::vec3(0.0, 0.0, -5.0)));
+        assert!(frustum.contains_point(&glm::vec3(4.9, 0.0, -5.0)));
+        assert!(!frustum.contains_point(&glm::vec3(5.1, 0.0, -5.0)));
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, 5.0)));
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, -0.05)));
+        assert!(!frustum.contains_point(&glm::vec3(0.0, 0.0, -101.0)));
+    }
+
+    #[test]
+    fn spheres_touching_a_plane_intersect() {
+        let frustum = perspective();
+        assert!(frustum.intersects_sphere(&glm::vec3(0.0, 0.0, -5.0), 0.5));
+        assert!(frustum.intersects_sphere(&glm::vec3(0.0, 0.0, 0.5), 1.0));
+        assert!(!frustum.intersects_sphere(&glm::vec3(0.0, 0.0, 2.0), 1.0));
+        assert!(!frustum.intersects_sphere(&glm::vec3(20.0, 0.0, -5.0), 1.0));
+    }
+
+    #[test]
+    fn boxes_straddling_a_plane_intersect() {
+        let frustum = perspective();
+        let unit = |center: glm::Vec3| {
+            Aabb::new(
+                center - glm::Vec3::repeat(0.5),
+                center + glm::Vec3::repeat(0.5),
+            )
+        };
+        assert!(frustum.intersects_aabb(&unit(glm::vec3(0.0, 0.0, -5.0))));
+        assert!(frustum.intersects_aabb(&unit(glm::vec3(5.0, 0.0, -5.0))));
+        assert!(!frustum.intersects_aabb(&unit(glm::vec3(7.0, 0.0, -5.0))));
+        assert!(!frustum.intersects_aabb(&unit(glm::vec3(0.0, 0.0, 5.0))));
+        assert!(!frustum.intersects_aabb(&Aabb::empty()));
+    }
+
+    #[test]
+    fn reversed_z_near_plane_is_in_front_of_the_camera() {
+        let mut camera = Camera::new(glm::Vec3::zeros());
+        camera.projection = Projection::ReversedZInfinite;
+        camera.near = 0.1;
+        let frustum = camera.frustum();
+        let near = frustum.planes[4];
+
+        assert!((near.signed_distance(&(camera.front * 0.1))).abs() < 1e-4);
+        assert!(glm::dot(&near.normal, &camera.front) > 0.99);
+        assert!(frustum.contains_point(&(camera.front * 1.0e6)));
+        assert!(!frustum.contains_point(&(camera.front * 0.05)));
+        assert!(!frustum.contains_point(&(-camera.front)));
+    }
+}
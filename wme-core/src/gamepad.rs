@@ -0,0 +1,406 @@
+use std::{cell::RefCell, error::Error, path::Path, rc::Rc};
+
+use glfw::{Action, GamepadAxis, GamepadButton, Glfw, JoystickEvent, JoystickId};
+use log::info;
+
+pub const BUTTON_COUNT: usize = GamepadButton::ButtonDpadLeft as usize + 1;
+pub const AXIS_COUNT: usize = GamepadAxis::AxisRightTrigger as usize + 1;
+const JOYSTICK_COUNT: i32 = JoystickId::Joystick16 as i32 + 1;
+
+/*
+ * A joystick GLFW has a gamepad mapping for, read through the standard
+ * Xbox style layout. Sticks and triggers already have the dead zones
+ * applied, triggers go from 0 to 1 and stick Y is positive downwards.
+ */
+#[derive(Debug, Clone)]
+pub struct Gamepad {
+    pub id: JoystickId,
+    pub name: String,
+    buttons: [bool; BUTTON_COUNT],
+    previous: [bool; BUTTON_COUNT],
+    axes: [f32; AXIS_COUNT],
+}
+
+impl Gamepad {
+    pub fn is_down(self: &Self, button: GamepadButton) -> bool {
+        self.buttons[button as usize]
+    }
+
+    pub fn just_pressed(self: &Self, button: GamepadButton) -> bool {
+        self.buttons[button as usize] && !self.previous[button as usize]
+    }
+
+    pub fn just_released(self: &Self, button: GamepadButton) -> bool {
+        !self.buttons[button as usize] && self.previous[button as usize]
+    }
+
+    pub fn axis(self: &Self, axis: GamepadAxis) -> f32 {
+        self.axes[axis as usize]
+    }
+
+    pub fn snapshot(self: &Self) -> GamepadSnapshot {
+        let mut buttons: u16 = 0;
+        for (index, down) in self.buttons.iter().enumerate() {
+            if *down {
+                buttons |= 1 << index;
+            }
+        }
+        GamepadSnapshot {
+            id: self.id,
+            buttons,
+            axes: self.axes,
+        }
+    }
+}
+
+// one frame of a pad's state, buttons as a bit per GamepadButton
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GamepadSnapshot {
+    pub id: JoystickId,
+    pub buttons: u16,
+    pub axes: [f32; AXIS_COUNT],
+}
+
+/*
+ * Every connected gamepad, kept up to date by Graphics::check_events.
+ * Plugging and unplugging is picked up through the GLFW joystick
+ * callback, joysticks without a mapping are skipped until one is
+ * added with load_mappings. Headless Graphics has no GLFW and so no
+ * pads, apart from replayed ones.
+ */
+pub struct Gamepads {
+    pub dead_zone: f32,
+    pub trigger_dead_zone: f32,
+    pub connected: Vec<JoystickId>,
+    pub disconnected: Vec<JoystickId>,
+    // None when headless
+    glfw: Option<Glfw>,
+    pads: Vec<Gamepad>,
+    plug_events: Rc<RefCell<Vec<(JoystickId, JoystickEvent)>>>,
+}
+
+impl Gamepads {
+    // replaces any joystick callback set on glfw
+    pub fn new(glfw: &mut Glfw) -> Self {
+        let plug_events: Rc<RefCell<Vec<(JoystickId, JoystickEvent)>>> = Rc::default();
+        let events = Rc::clone(&plug_events);
+        glfw.set_joystick_callback(move |id, event| {
+            events.borrow_mut().push((id, event));
+        });
+
+        let mut gamepads = Gamepads {
+            dead_zone: 0.15,
+            trigger_dead_zone: 0.05,
+            connected: Vec::new(),
+            disconnected: Vec::new(),
+            glfw: Some(glfw.clone()),
+            pads: Vec::new(),
+            plug_events,
+        };
+        gamepads.scan();
+        gamepads
+    }
+
+    pub fn headless() -> Self {
+        Gamepads {
+            dead_zone: 0.15,
+            trigger_dead_zone: 0.05,
+            connected: Vec::new(),
+            disconnected: Vec::new(),
+            glfw: None,
+            pads: Vec::new(),
+            plug_events: Rc::default(),
+        }
+    }
+
+    // call once per frame after polling events
+    pub fn update(self: &mut Self) {
+        self.connected.clear();
+        self.disconnected.clear();
+
+        let plug_events: Vec<(JoystickId, JoystickEvent)> =
+            self.plug_events.borrow_mut().drain(..).collect();
+        for (id, event) in plug_events {
+            match event {
+                JoystickEvent::Connected => self.connect(id),
+                JoystickEvent::Disconnected => self.disconnect(id),
+            }
+        }
+
+        let glfw = match &self.glfw {
+            Some(glfw) => glfw,
+            None => return,
+        };
+        let (dead_zone, trigger_dead_zone) = (self.dead_zone, self.trigger_dead_zone);
+        for pad in self.pads.iter_mut() {
+            pad.previous = pad.buttons;
+            // None once the pad is gone, the disconnect event follows
+            let state = match glfw.get_joystick(pad.id).get_gamepad_state() {
+                Some(state) => state,
+                None => {
+                    pad.buttons = [false; BUTTON_COUNT];
+                    pad.axes = [0.0; AXIS_COUNT];
+                    continue;
+                }
+            };
+
+            for (index, button) in pad.buttons.iter_mut().enumerate() {
+                *button = GamepadButton::from_i32(index as i32)
+                    .is_some_and(|id| state.get_button_state(id) == Action::Press);
+            }
+
+            let (left_x, left_y) = radial_dead_zone(
+                state.get_axis(GamepadAxis::AxisLeftX),
+                state.get_axis(GamepadAxis::AxisLeftY),
+                dead_zone,
+            );
+            let (right_x, right_y) = radial_dead_zone(
+                state.get_axis(GamepadAxis::AxisRightX),
+                state.get_axis(GamepadAxis::AxisRightY),
+                dead_zone,
+            );
+            pad.axes[GamepadAxis::AxisLeftX as usize] = left_x;
+            pad.axes[GamepadAxis::AxisLeftY as usize] = left_y;
+            pad.axes[GamepadAxis::AxisRightX as usize] = right_x;
+            pad.axes[GamepadAxis::AxisRightY as usize] = right_y;
+            pad.axes[GamepadAxis::AxisLeftTrigger as usize] = trigger(
+                state.get_axis(GamepadAxis::AxisLeftTrigger),
+                trigger_dead_zone,
+            );
+            pad.axes[GamepadAxis::AxisRightTrigger as usize] = trigger(
+                state.get_axis(GamepadAxis::AxisRightTrigger),
+                trigger_dead_zone,
+            );
+        }
+    }
+
+    /*
+     * Replaces the polled state with recorded snapshots, use instead of
+     * update() while replaying. Pads missing from the snapshots count as
+     * unplugged.
+     */
+    pub fn replay(self: &mut Self, snapshots: &[GamepadSnapshot]) {
+        self.connected.clear();
+        self.disconnected.clear();
+
+        let mut pads: Vec<Gamepad> = Vec::with_capacity(snapshots.len());
+        for snapshot in snapshots {
+            let mut pad = match self.pads.iter().position(|pad| pad.id == snapshot.id) {
+                Some(index) => self.pads.swap_remove(index),
+                None => {
+                    self.connected.push(snapshot.id);
+                    Gamepad {
+                        id: snapshot.id,
+                        name: String::from("Replayed gamepad"),
+                        buttons: [false; BUTTON_COUNT],
+                        previous: [false; BUTTON_COUNT],
+                        axes: [0.0; AXIS_COUNT],
+                    }
+                }
+            };
+            pad.previous = pad.buttons;
+            for (index, button) in pad.buttons.iter_mut().enumerate() {
+                *button = snapshot.buttons & (1 << index) != 0;
+            }
+            pad.axes = snapshot.axes;
+            pads.push(pad);
+        }
+
+        self.disconnected.extend(self.pads.iter().map(|pad| pad.id));
+        self.pads = pads;
+    }
+
+    // SDL_GameControllerDB format, one mapping per line
+    pub fn update_mappings(self: &mut Self, mappings: &str) -> Result<(), Box<dyn Error>> {
+        let glfw = self.glfw.as_mut().ok_or("No gamepads without a window")?;
+        if !glfw.update_gamepad_mappings(mappings) {
+            return Err("Invalid gamepad mappings".into());
+        }
+        // joysticks that were skipped may be gamepads now
+        self.scan();
+        Ok(())
+    }
+
+    pub fn load_mappings(self: &mut Self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mappings = std::fs::read_to_string(path)?;
+        self.update_mappings(&mappings)
+            .map_err(|error| format!("{}: {}", path.display(), error).into())
+    }
+
+    pub fn pads(self: &Self) -> &[Gamepad] {
+        &self.pads
+    }
+
+    pub fn get(self: &Self, id: JoystickId) -> Option<&Gamepad> {
+        self.pads.iter().find(|pad| pad.id == id)
+    }
+
+    // true if the button is held on any pad
+    pub fn is_down(self: &Self, button: GamepadButton) -> bool {
+        self.pads.iter().any(|pad| pad.is_down(button))
+    }
+
+    // the value furthest from rest over all pads
+    pub fn axis(self: &Self, axis: GamepadAxis) -> f32 {
+        self.pads
+            .iter()
+            .map(|pad| pad.axis(axis))
+            .fold(0.0, |value: f32, pad_value| {
+                if pad_value.abs() > value.abs() {
+                    pad_value
+                } else {
+                    value
+                }
+            })
+    }
+
+    fn scan(self: &mut Self) {
+        let glfw = match &self.glfw {
+            Some(glfw) => glfw.clone(),
+            None => return,
+        };
+        for index in 0..JOYSTICK_COUNT {
+            if let Some(id) = JoystickId::from_i32(index) {
+                if self.get(id).is_none() && glfw.get_joystick(id).is_present() {
+                    self.connect(id);
+                }
+            }
+        }
+    }
+
+    fn connect(self: &mut Self, id: JoystickId) {
+        let joystick = match &self.glfw {
+            Some(glfw) => glfw.get_joystick(id),
+            None => return,
+        };
+        if !joystick.is_gamepad() {
+            // the guid is what a mapping line for this device starts with
+            info!(
+                "Joystick {} ({}) has no gamepad mapping",
+                joystick.get_name().unwrap_or_default(),
+                joystick.get_guid().unwrap_or_default()
+            );
+            return;
+        }
+        if self.get(id).is_some() {
+            return;
+        }
+
+        let name = joystick.get_gamepad_name().unwrap_or_default();
+        info!("Gamepad connected: {}", name);
+        self.pads.push(Gamepad {
+            id,
+            name,
+            buttons: [false; BUTTON_COUNT],
+            previous: [false; BUTTON_COUNT],
+            axes: [0.0; AXIS_COUNT],
+        });
+        self.connected.push(id);
+    }
+
+    fn disconnect(self: &mut Self, id: JoystickId) {
+        if let Some(index) = self.pads.iter().position(|pad| pad.id == id) {
+            let pad = self.pads.remove(index);
+            info!("Gamepad disconnected: {}", pad.name);
+            self.disconnected.push(id);
+        }
+    }
+}
+
+// keeps the stick direction and rescales the rest of the range to 0..1
+fn radial_dead_zone(x: f32, y: f32, dead_zone: f32) -> (f32, f32) {
+    let magnitude = (x * x + y * y).sqrt();
+    if magnitude <= dead_zone {
+        return (0.0, 0.0);
+    }
+    let scale = ((magnitude - dead_zone) / (1.0 - dead_zone)).min(1.0) / magnitude;
+    (x * scale, y * scale)
+}
+
+// GLFW reports triggers from -1 at rest to 1 fully pulled
+fn trigger(value: f32, dead_zone: f32) -> f32 {
+    let value = (value + 1.0) * 0.5;
+    if value <= dead_zone {
+        0.0
+    } else {
+        ((value - dead_zone) / (1.0 - dead_zone)).min(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{}", actual);
+    }
+
+    fn snapshot(id: JoystickId, buttons: u16, left_x: f32) -> GamepadSnapshot {
+        let mut axes = [0.0; AXIS_COUNT];
+        axes[GamepadAxis::AxisLeftX as usize] = left_x;
+        GamepadSnapshot { id, buttons, axes }
+    }
+
+    #[test]
+    fn sticks_inside_the_dead_zone_rest() {
+        assert_eq!(radial_dead_zone(0.1, -0.1, 0.15), (0.0, 0.0));
+        assert_eq!(radial_dead_zone(0.0, 0.15, 0.15), (0.0, 0.0));
+    }
+
+    #[test]
+    fn sticks_are_rescaled_to_reach_one() {
+        let (x, y) = radial_dead_zone(1.0, 0.0, 0.2);
+        assert_near(x, 1.0);
+        assert_near(y, 0.0);
+        // halfway between the dead zone and the edge
+        let (x, y) = radial_dead_zone(0.0, -0.6, 0.2);
+        assert_near(x, 0.0);
+        assert_near(y, -0.5);
+    }
+
+    #[test]
+    fn diagonals_clamp_to_one_and_keep_their_direction() {
+        let (x, y) = radial_dead_zone(1.0, 1.0, 0.15);
+        assert_near((x * x + y * y).sqrt(), 1.0);
+        assert_near(x, y);
+    }
+
+    #[test]
+    fn triggers_at_rest_read_zero() {
+        assert_eq!(trigger(-1.0, 0.05), 0.0);
+        assert_eq!(trigger(-0.95, 0.05), 0.0);
+        assert_near(trigger(1.0, 0.05), 1.0);
+        assert_near(trigger(0.0, 0.0), 0.5);
+    }
+
+    #[test]
+    fn replay_reports_connected_and_disconnected_pads() {
+        let mut gamepads = Gamepads::headless();
+        let a = GamepadButton::ButtonA as usize;
+        gamepads.replay(&[
+            snapshot(JoystickId::Joystick1, 1 << a, 0.5),
+            snapshot(JoystickId::Joystick2, 0, -1.0),
+        ]);
+        assert_eq!(
+            gamepads.connected,
+            vec![JoystickId::Joystick1, JoystickId::Joystick2]
+        );
+        assert!(gamepads.disconnected.is_empty());
+        assert!(gamepads.is_down(GamepadButton::ButtonA));
+        assert_eq!(gamepads.axis(GamepadAxis::AxisLeftX), -1.0);
+        let pad = gamepads.get(JoystickId::Joystick1).unwrap();
+        assert!(pad.just_pressed(GamepadButton::ButtonA));
+        assert_eq!(pad.snapshot(), snapshot(JoystickId::Joystick1, 1 << a, 0.5));
+
+        gamepads.replay(&[snapshot(JoystickId::Joystick1, 0, 0.0)]);
+        assert!(gamepads.connected.is_empty());
+        assert_eq!(gamepads.disconnected, vec![JoystickId::Joystick2]);
+        assert_eq!(gamepads.pads().len(), 1);
+        let pad = gamepads.get(JoystickId::Joystick1).unwrap();
+        assert!(pad.just_released(GamepadButton::ButtonA));
+
+        gamepads.replay(&[]);
+        assert_eq!(gamepads.disconnected, vec![JoystickId::Joystick1]);
+        assert!(gamepads.pads().is_empty());
+    }
+}
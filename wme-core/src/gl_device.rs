@@ -0,0 +1,768 @@
+use std::{
+    error::Error,
+    ffi::{CStr, CString},
+};
+
+use crate::debug;
+use crate::render_device::{
+    ActiveAttribute, ActiveUniform, BlendMode, BufferTarget, ClearState, ClipDepth, CullMode,
+    DepthFunc, DepthState, ObjectKind, PrimitiveType, RenderDevice, ShaderStage, TextureFormat,
+    UniformType, UniformValue, VertexAttribute, Viewport,
+};
+use crate::state_cache::StateCache;
+
+// RenderDevice backed by the current OpenGL context
+#[derive(Default)]
+pub struct GlDevice {
+    pub state: StateCache,
+}
+
+impl GlDevice {
+    pub fn new() -> Self {
+        GlDevice {
+            state: StateCache::new(),
+        }
+    }
+}
+
+fn buffer_target(target: BufferTarget) -> gl::types::GLenum {
+    match target {
+        BufferTarget::Array => gl::ARRAY_BUFFER,
+        BufferTarget::ElementArray => gl::ELEMENT_ARRAY_BUFFER,
+    }
+}
+
+fn primitive_mode(primitive: PrimitiveType) -> gl::types::GLenum {
+    match primitive {
+        PrimitiveType::Triangles => gl::TRIANGLES,
+        PrimitiveType::Lines => gl::LINES,
+        PrimitiveType::Points => gl::POINTS,
+    }
+}
+
+fn texture_format(format: TextureFormat) -> gl::types::GLenum {
+    match format {
+        TextureFormat::R8 => gl::RED,
+        TextureFormat::Rg8 => gl::RG,
+        TextureFormat::Rgb8 => gl::RGB,
+        TextureFormat::Rgba8 => gl::RGBA,
+    }
+}
+
+fn depth_func(func: DepthFunc) -> gl::types::GLenum {
+    match func {
+        DepthFunc::Never => gl::NEVER,
+        DepthFunc::Less => gl::LESS,
+        DepthFunc::LessEqual => gl::LEQUAL,
+        DepthFunc::Equal => gl::EQUAL,
+        DepthFunc::Greater => gl::GREATER,
+        DepthFunc::GreaterEqual => gl::GEQUAL,
+        DepthFunc::Always => gl::ALWAYS,
+    }
+}
+
+fn object_identifier(kind: ObjectKind) -> gl::types::GLenum {
+    match kind {
+        ObjectKind::Buffer => gl::BUFFER,
+        ObjectKind::VertexArray => gl::VERTEX_ARRAY,
+        ObjectKind::Texture => gl::TEXTURE,
+        ObjectKind::Shader => gl::SHADER,
+        ObjectKind::Program => gl::PROGRAM,
+    }
+}
+
+fn uniform_type(kind: gl::types::GLenum) -> UniformType {
+    match kind {
+        gl::BOOL => UniformType::Bool,
+        gl::BOOL_VEC2 => UniformType::BVec2,
+        gl::BOOL_VEC3 => UniformType::BVec3,
+        gl::BOOL_VEC4 => UniformType::BVec4,
+        gl::INT => UniformType::Int,
+        gl::INT_VEC2 => UniformType::IVec2,
+        gl::INT_VEC3 => UniformType::IVec3,
+        gl::INT_VEC4 => UniformType::IVec4,
+        gl::UNSIGNED_INT => UniformType::UInt,
+        gl::UNSIGNED_INT_VEC2 => UniformType::UVec2,
+        gl::UNSIGNED_INT_VEC3 => UniformType::UVec3,
+        gl::UNSIGNED_INT_VEC4 => UniformType::UVec4,
+        gl::FLOAT => UniformType::Float,
+        gl::FLOAT_VEC2 => UniformType::Vec2,
+        gl::FLOAT_VEC3 => UniformType::Vec3,
+        gl::FLOAT_VEC4 => UniformType::Vec4,
+        gl::FLOAT_MAT3 => UniformType::Mat3,
+        gl::FLOAT_MAT4 => UniformType::Mat4,
+        gl::SAMPLER_2D => UniformType::Sampler2D,
+        gl::SAMPLER_CUBE => UniformType::SamplerCube,
+        sampler if is_sampler(sampler) => UniformType::Sampler(sampler),
+        other => UniformType::Other(other),
+    }
+}
+
+// samplers and images of every dimension and data type, all set with glUniform1i
+fn is_sampler(kind: gl::types::GLenum) -> bool {
+    matches!(
+        kind,
+        gl::SAMPLER_1D
+            | gl::SAMPLER_3D
+            | gl::SAMPLER_1D_SHADOW
+            | gl::SAMPLER_2D_SHADOW
+            | gl::SAMPLER_1D_ARRAY
+            | gl::SAMPLER_2D_ARRAY
+            | gl::SAMPLER_1D_ARRAY_SHADOW
+            | gl::SAMPLER_2D_ARRAY_SHADOW
+            | gl::SAMPLER_2D_MULTISAMPLE
+            | gl::SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::SAMPLER_CUBE_SHADOW
+            | gl::SAMPLER_CUBE_MAP_ARRAY
+            | gl::SAMPLER_CUBE_MAP_ARRAY_SHADOW
+            | gl::SAMPLER_BUFFER
+            | gl::SAMPLER_2D_RECT
+            | gl::SAMPLER_2D_RECT_SHADOW
+            | gl::INT_SAMPLER_1D
+            | gl::INT_SAMPLER_2D
+            | gl::INT_SAMPLER_3D
+            | gl::INT_SAMPLER_CUBE
+            | gl::INT_SAMPLER_1D_ARRAY
+            | gl::INT_SAMPLER_2D_ARRAY
+            | gl::INT_SAMPLER_2D_MULTISAMPLE
+            | gl::INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::INT_SAMPLER_CUBE_MAP_ARRAY
+            | gl::INT_SAMPLER_BUFFER
+            | gl::INT_SAMPLER_2D_RECT
+            | gl::UNSIGNED_INT_SAMPLER_1D
+            | gl::UNSIGNED_INT_SAMPLER_2D
+            | gl::UNSIGNED_INT_SAMPLER_3D
+            | gl::UNSIGNED_INT_SAMPLER_CUBE
+            | gl::UNSIGNED_INT_SAMPLER_1D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE
+            | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_CUBE_MAP_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_BUFFER
+            | gl::UNSIGNED_INT_SAMPLER_2D_RECT
+            | gl::IMAGE_1D
+            | gl::IMAGE_2D
+            | gl::IMAGE_3D
+            | gl::IMAGE_2D_RECT
+            | gl::IMAGE_CUBE
+            | gl::IMAGE_BUFFER
+            | gl::IMAGE_1D_ARRAY
+            | gl::IMAGE_2D_ARRAY
+            | gl::IMAGE_CUBE_MAP_ARRAY
+            | gl::IMAGE_2D_MULTISAMPLE
+            | gl::IMAGE_2D_MULTISAMPLE_ARRAY
+            | gl::INT_IMAGE_1D
+            | gl::INT_IMAGE_2D
+            | gl::INT_IMAGE_3D
+            | gl::INT_IMAGE_2D_RECT
+            | gl::INT_IMAGE_CUBE
+            | gl::INT_IMAGE_BUFFER
+            | gl::INT_IMAGE_1D_ARRAY
+            | gl::INT_IMAGE_2D_ARRAY
+            | gl::INT_IMAGE_CUBE_MAP_ARRAY
+            | gl::INT_IMAGE_2D_MULTISAMPLE
+            | gl::INT_IMAGE_2D_MULTISAMPLE_ARRAY
+            | gl::UNSIGNED_INT_IMAGE_1D
+            | gl::UNSIGNED_INT_IMAGE_2D
+            | gl::UNSIGNED_INT_IMAGE_3D
+            | gl::UNSIGNED_INT_IMAGE_2D_RECT
+            | gl::UNSIGNED_INT_IMAGE_CUBE
+            | gl::UNSIGNED_INT_IMAGE_BUFFER
+            | gl::UNSIGNED_INT_IMAGE_1D_ARRAY
+            | gl::UNSIGNED_INT_IMAGE_2D_ARRAY
+            | gl::UNSIGNED_INT_IMAGE_CUBE_MAP_ARRAY
+            | gl::UNSIGNED_INT_IMAGE_2D_MULTISAMPLE
+            | gl::UNSIGNED_INT_IMAGE_2D_MULTISAMPLE_ARRAY
+    )
+}
+
+enum Variables {
+    Uniforms,
+    Attributes,
+}
+
+// name, type and array size of each active uniform or attribute of a linked program
+fn active_variables(program: u32, variables: Variables) -> Vec<(String, UniformType, i32)> {
+    let (count_param, length_param) = match variables {
+        Variables::Uniforms => (gl::ACTIVE_UNIFORMS, gl::ACTIVE_UNIFORM_MAX_LENGTH),
+        Variables::Attributes => (gl::ACTIVE_ATTRIBUTES, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH),
+    };
+    let mut count = 0;
+    let mut max_length = 0;
+    unsafe {
+        gl::GetProgramiv(program, count_param, &mut count);
+        gl::GetProgramiv(program, length_param, &mut max_length);
+    }
+
+    (0..count.max(0) as u32)
+        .map(|index| {
+            let mut length = 0;
+            let mut size = 0;
+            let mut kind = 0;
+            let mut name: Vec<u8> = vec![0; max_length.max(1) as usize];
+            unsafe {
+                let get_active = match variables {
+                    Variables::Uniforms => gl::GetActiveUniform,
+                    Variables::Attributes => gl::GetActiveAttrib,
+                };
+                get_active(
+                    program,
+                    index,
+                    name.len() as i32,
+                    &mut length,
+                    &mut size,
+                    &mut kind,
+                    name.as_mut_ptr().cast(),
+                );
+            }
+            name.truncate(length.max(0) as usize);
+            (
+                String::from_utf8_lossy(&name).to_string(),
+                uniform_type(kind),
+                size,
+            )
+        })
+        .collect()
+}
+
+impl RenderDevice for GlDevice {
+    fn create_buffer(self: &mut Self, target: BufferTarget, data: &[u8]) -> u32 {
+        let mut buffer: u32 = 0;
+        unsafe {
+            // binding an element buffer would attach it to the bound vertex array
+            gl::BindVertexArray(0);
+            gl::GenBuffers(1, &mut buffer);
+            gl::BindBuffer(buffer_target(target), buffer);
+            gl::BufferData(
+                buffer_target(target),
+                data.len() as gl::types::GLsizeiptr,
+                data.as_ptr().cast(),
+                gl::STATIC_DRAW,
+            );
+            gl::BindBuffer(buffer_target(target), 0);
+        }
+        debug::check_call("create_buffer", format_args!("(buffer {})", buffer));
+        self.state.vertex_array_changed(0);
+        buffer
+    }
+
+    fn delete_buffer(self: &mut Self, buffer: u32) {
+        unsafe {
+            gl::DeleteBuffers(1, &buffer);
+        }
+        debug::check_call("glDeleteBuffers", format_args!("(buffer {})", buffer));
+    }
+
+    fn create_vertex_array(
+        self: &mut Self,
+        vertex_buffer: u32,
+        index_buffer: Option<u32>,
+        attributes: &[VertexAttribute],
+    ) -> u32 {
+        let mut vertex_array: u32 = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vertex_array);
+            gl::BindVertexArray(vertex_array);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vertex_buffer);
+            if let Some(index_buffer) = index_buffer {
+                gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, index_buffer);
+            }
+            for attribute in attributes {
+                gl::VertexAttribPointer(
+                    attribute.index,
+                    attribute.components,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    attribute.stride as gl::types::GLsizei,
+                    attribute.offset as *const gl::types::GLvoid,
+                );
+                gl::EnableVertexAttribArray(attribute.index);
+            }
+            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+        debug::check_call(
+            "create_vertex_array",
+            format_args!("(vertex array {})", vertex_array),
+        );
+        self.state.vertex_array_changed(0);
+        vertex_array
+    }
+
+    fn delete_vertex_array(self: &mut Self, vertex_array: u32) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &vertex_array);
+        }
+        debug::check_call(
+            "glDeleteVertexArrays",
+            format_args!("(vertex array {})", vertex_array),
+        );
+        self.state.forget_vertex_array(vertex_array);
+    }
+
+    fn create_texture(
+        self: &mut Self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        pixels: &[u8],
+        label: &str,
+    ) -> Result<u32, Box<dyn Error>> {
+        let filter = texture_format(format);
+        let mut id: u32 = 0;
+
+        // errors left over from earlier calls would otherwise fail this upload
+        if let Err(error) = debug::check_error(&format!("calls before creating {}", label)) {
+            log::warn!("Unchecked GL error: {}", error);
+        }
+
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+        }
+        // the new texture replaces whatever the active unit had bound
+        self.state.forget_texture_units();
+
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                gl::LINEAR_MIPMAP_LINEAR as i32,
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+
+            // rows of RGB and single channel images are not 4 byte aligned
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                filter as i32,
+                width as i32,
+                height as i32,
+                0,
+                filter,
+                gl::UNSIGNED_BYTE,
+                pixels.as_ptr().cast(),
+            );
+        }
+
+        if let Err(error) = debug::check_error(&format!("TexImage2D ({})", label)) {
+            unsafe {
+                gl::DeleteTextures(1, &id);
+            }
+            return Err(Box::new(error));
+        }
+
+        unsafe {
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+        }
+        debug::check_call("glGenerateMipmap", format_args!("({})", label));
+        debug::label_object(gl::TEXTURE, id, label);
+
+        Ok(id)
+    }
+
+    fn delete_texture(self: &mut Self, texture: u32) {
+        unsafe {
+            gl::DeleteTextures(1, &texture);
+        }
+        debug::check_call("glDeleteTextures", format_args!("(texture {})", texture));
+        self.state.forget_texture(texture);
+    }
+
+    fn create_shader(self: &mut Self, stage: ShaderStage, source: &str) -> Result<u32, String> {
+        let shader_type = match stage {
+            ShaderStage::Vertex => gl::VERTEX_SHADER,
+            ShaderStage::Fragment => gl::FRAGMENT_SHADER,
+        };
+
+        let shader_id = unsafe { gl::CreateShader(shader_type) };
+        unsafe {
+            gl::ShaderSource(
+                shader_id,
+                1,
+                &source.as_bytes().as_ptr().cast(),
+                &source.len().try_into().unwrap(),
+            );
+            gl::CompileShader(shader_id);
+
+            let mut success = 0;
+            gl::GetShaderiv(shader_id, gl::COMPILE_STATUS, &mut success);
+            if success == 0 {
+                let mut log_len = 0_i32;
+                let mut v: Vec<u8> = Vec::with_capacity(1024);
+                gl::GetShaderInfoLog(shader_id, 1024, &mut log_len, v.as_mut_ptr().cast());
+                v.set_len(log_len.try_into().unwrap());
+                gl::DeleteShader(shader_id);
+                return Err(String::from_utf8_lossy(&v).to_string());
+            }
+        }
+        debug::check_call("create_shader", format_args!("(shader {})", shader_id));
+
+        Ok(shader_id)
+    }
+
+    fn delete_shader(self: &mut Self, shader: u32) {
+        unsafe {
+            gl::DeleteShader(shader);
+        }
+        debug::check_call("glDeleteShader", format_args!("(shader {})", shader));
+    }
+
+    fn create_program(self: &mut Self, shaders: &[u32]) -> Result<u32, String> {
+        let id = unsafe { gl::CreateProgram() };
+        unsafe {
+            for shader in shaders {
+                gl::AttachShader(id, *shader);
+            }
+            gl::LinkProgram(id);
+
+            let mut success = 0;
+            gl::GetProgramiv(id, gl::LINK_STATUS, &mut success);
+            for shader in shaders {
+                gl::DetachShader(id, *shader);
+                gl::DeleteShader(*shader);
+            }
+            if success == 0 {
+                let mut log_len = 0_i32;
+                let mut v: Vec<u8> = Vec::with_capacity(1024);
+                gl::GetProgramInfoLog(id, 1024, &mut log_len, v.as_mut_ptr().cast());
+                v.set_len(log_len.try_into().unwrap());
+                gl::DeleteProgram(id);
+                return Err(String::from_utf8_lossy(&v).to_string());
+            }
+        }
+        debug::check_call("create_program", format_args!("(program {})", id));
+
+        Ok(id)
+    }
+
+    fn delete_program(self: &mut Self, program: u32) {
+        unsafe {
+            gl::DeleteProgram(program);
+        }
+        debug::check_call("glDeleteProgram", format_args!("(program {})", program));
+        self.state.forget_program(program);
+    }
+
+    fn use_program(self: &mut Self, program: u32) {
+        if !self.state.use_program(program) {
+            return;
+        }
+        unsafe {
+            gl::UseProgram(program);
+        }
+        debug::check_call("glUseProgram", format_args!("(program {})", program));
+    }
+
+    fn uniform_location(self: &mut Self, program: u32, name: &CStr) -> i32 {
+        if let Some(location) = self.state.uniform_location(program, name) {
+            return location;
+        }
+        let location = unsafe { gl::GetUniformLocation(program, name.as_ptr()) };
+        debug::check_call(
+            "glGetUniformLocation",
+            format_args!("({:?} in program {})", name, program),
+        );
+        self.state.store_uniform_location(program, name, location);
+        location
+    }
+
+    fn active_uniforms(self: &mut Self, program: u32) -> Option<Vec<ActiveUniform>> {
+        let mut uniforms = Vec::new();
+        for (name, kind, size) in active_variables(program, Variables::Uniforms) {
+            let locations: Vec<i32> = if size > 1 {
+                let base = name.trim_end_matches("[0]").to_string();
+                (0..size)
+                    .map(|index| {
+                        let element = CString::new(format!("{}[{}]", base, index)).unwrap();
+                        self.uniform_location(program, &element)
+                    })
+                    .collect()
+            } else {
+                let name = CString::new(name.as_str()).unwrap();
+                vec![self.uniform_location(program, &name)]
+            };
+            // members of uniform blocks have no location and can't be set one by one
+            if locations[0] == -1 {
+                continue;
+            }
+            uniforms.push(ActiveUniform {
+                name: name.trim_end_matches("[0]").to_string(),
+                kind,
+                locations,
+            });
+        }
+        Some(uniforms)
+    }
+
+    fn active_attributes(self: &mut Self, program: u32) -> Option<Vec<ActiveAttribute>> {
+        let attributes = active_variables(program, Variables::Attributes)
+            .into_iter()
+            .map(|(name, kind, _)| {
+                let c_name = CString::new(name.as_str()).unwrap();
+                let location = unsafe { gl::GetAttribLocation(program, c_name.as_ptr()) };
+                debug::check_call(
+                    "glGetAttribLocation",
+                    format_args!("({} in program {})", name, program),
+                );
+                ActiveAttribute {
+                    name,
+                    kind,
+                    location,
+                }
+            })
+            .collect();
+        Some(attributes)
+    }
+
+    fn set_uniform(self: &mut Self, location: i32, value: UniformValue) {
+        if location == -1 || !self.state.set_uniform(location, value) {
+            return;
+        }
+        unsafe {
+            match value {
+                UniformValue::Int(value) => gl::Uniform1i(location, value),
+                UniformValue::IVec2(vector) => gl::Uniform2i(location, vector.x, vector.y),
+                UniformValue::IVec3(vector) => {
+                    gl::Uniform3i(location, vector.x, vector.y, vector.z)
+                }
+                UniformValue::IVec4(vector) => {
+                    gl::Uniform4i(location, vector.x, vector.y, vector.z, vector.w)
+                }
+                UniformValue::UInt(value) => gl::Uniform1ui(location, value),
+                UniformValue::UVec2(vector) => gl::Uniform2ui(location, vector.x, vector.y),
+                UniformValue::UVec3(vector) => {
+                    gl::Uniform3ui(location, vector.x, vector.y, vector.z)
+                }
+                UniformValue::UVec4(vector) => {
+                    gl::Uniform4ui(location, vector.x, vector.y, vector.z, vector.w)
+                }
+                UniformValue::Float(value) => gl::Uniform1f(location, value),
+                UniformValue::Vec2(vector) => gl::Uniform2f(location, vector.x, vector.y),
+                UniformValue::Vec3(vector) => gl::Uniform3f(location, vector.x, vector.y, vector.z),
+                UniformValue::Vec4(vector) => {
+                    gl::Uniform4f(location, vector.x, vector.y, vector.z, vector.w)
+                }
+                UniformValue::Mat3(matrix) => {
+                    gl::UniformMatrix3fv(location, 1, gl::FALSE, matrix.as_ptr())
+                }
+                UniformValue::Mat4(matrix) => {
+                    gl::UniformMatrix4fv(location, 1, gl::FALSE, matrix.as_ptr())
+                }
+            }
+        }
+        debug::check_call(
+            "set_uniform",
+            format_args!("(location {}, {:?})", location, value),
+        );
+    }
+
+    fn bind_texture(self: &mut Self, unit: u32, texture: u32) {
+        if !self.state.bind_texture(unit, texture) {
+            return;
+        }
+        unsafe {
+            if self.state.active_texture(unit) {
+                gl::ActiveTexture(gl::TEXTURE0 + unit);
+            }
+            gl::BindTexture(gl::TEXTURE_2D, texture);
+        }
+        debug::check_call(
+            "bind_texture",
+            format_args!("(texture {} on unit {})", texture, unit),
+        );
+    }
+
+    fn bind_vertex_array(self: &mut Self, vertex_array: u32) {
+        if !self.state.bind_vertex_array(vertex_array) {
+            return;
+        }
+        unsafe {
+            gl::BindVertexArray(vertex_array);
+        }
+        debug::check_call(
+            "glBindVertexArray",
+            format_args!("(vertex array {})", vertex_array),
+        );
+    }
+
+    fn set_blend(self: &mut Self, blend: BlendMode) {
+        if !self.state.set_blend(blend) {
+            return;
+        }
+        unsafe {
+            match blend {
+                BlendMode::Disabled => gl::Disable(gl::BLEND),
+                BlendMode::Alpha => {
+                    gl::Enable(gl::BLEND);
+                    gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+                }
+                BlendMode::Premultiplied => {
+                    gl::Enable(gl::BLEND);
+                    gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+                }
+                BlendMode::Additive => {
+                    gl::Enable(gl::BLEND);
+                    gl::BlendFunc(gl::ONE, gl::ONE);
+                }
+            }
+        }
+        debug::check_call("set_blend", format_args!("({:?})", blend));
+    }
+
+    fn set_depth(self: &mut Self, depth: DepthState) {
+        if !self.state.set_depth(depth) {
+            return;
+        }
+        unsafe {
+            match depth.test {
+                Some(func) => {
+                    gl::Enable(gl::DEPTH_TEST);
+                    gl::DepthFunc(depth_func(func));
+                }
+                None => gl::Disable(gl::DEPTH_TEST),
+            }
+            gl::DepthMask(if depth.write { gl::TRUE } else { gl::FALSE });
+        }
+        debug::check_call("set_depth", format_args!("({:?})", depth));
+    }
+
+    fn set_cull(self: &mut Self, cull: CullMode) {
+        if !self.state.set_cull(cull) {
+            return;
+        }
+        unsafe {
+            match cull {
+                CullMode::Disabled => gl::Disable(gl::CULL_FACE),
+                CullMode::Back => {
+                    gl::Enable(gl::CULL_FACE);
+                    gl::CullFace(gl::BACK);
+                }
+                CullMode::Front => {
+                    gl::Enable(gl::CULL_FACE);
+                    gl::CullFace(gl::FRONT);
+                }
+            }
+        }
+        debug::check_call("set_cull", format_args!("({:?})", cull));
+    }
+
+    // glClipControl needs GL 4.5 or ARB_clip_control
+    fn set_clip_depth(self: &mut Self, clip_depth: ClipDepth) -> bool {
+        if !gl::ClipControl::is_loaded() {
+            return clip_depth == ClipDepth::NegativeOneToOne;
+        }
+        if self.state.set_clip_depth(clip_depth) {
+            let depth = match clip_depth {
+                ClipDepth::NegativeOneToOne => gl::NEGATIVE_ONE_TO_ONE,
+                ClipDepth::ZeroToOne => gl::ZERO_TO_ONE,
+            };
+            unsafe { gl::ClipControl(gl::LOWER_LEFT, depth) };
+            debug::check_call("glClipControl", format_args!("({:?})", clip_depth));
+        }
+        true
+    }
+
+    fn set_viewport(self: &mut Self, viewport: Viewport) {
+        if !self.state.set_viewport(viewport) {
+            return;
+        }
+        unsafe {
+            gl::Viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+        }
+        debug::check_call("glViewport", format_args!("({:?})", viewport));
+    }
+
+    fn set_scissor(self: &mut Self, scissor: Option<Viewport>) {
+        if !self.state.set_scissor(scissor) {
+            return;
+        }
+        unsafe {
+            match scissor {
+                Some(rect) => {
+                    gl::Enable(gl::SCISSOR_TEST);
+                    gl::Scissor(rect.x, rect.y, rect.width, rect.height);
+                }
+                None => gl::Disable(gl::SCISSOR_TEST),
+            }
+        }
+        debug::check_call("set_scissor", format_args!("({:?})", scissor));
+    }
+
+    fn clear(self: &mut Self, clear: ClearState) {
+        let mut mask: gl::types::GLbitfield = 0;
+        unsafe {
+            if let Some(color) = clear.color {
+                gl::ClearColor(color.x, color.y, color.z, color.w);
+                mask |= gl::COLOR_BUFFER_BIT;
+            }
+            if let Some(depth) = clear.depth {
+                gl::ClearDepth(depth as f64);
+                mask |= gl::DEPTH_BUFFER_BIT;
+            }
+            gl::Clear(mask);
+        }
+        debug::check_call("glClear", format_args!("({:?})", clear));
+    }
+
+    fn draw_arrays(self: &mut Self, primitive: PrimitiveType, first: i32, count: i32) {
+        crate::gl_check!(
+            unsafe { gl::DrawArrays(primitive_mode(primitive), first, count) },
+            format_args!("({:?}, first {}, count {})", primitive, first, count)
+        );
+    }
+
+    fn draw_elements(self: &mut Self, primitive: PrimitiveType, count: i32) {
+        crate::gl_check!(
+            unsafe {
+                gl::DrawElements(
+                    primitive_mode(primitive),
+                    count,
+                    gl::UNSIGNED_INT,
+                    std::ptr::null(),
+                )
+            },
+            format_args!("({:?}, count {})", primitive, count)
+        );
+    }
+
+    fn label(self: &mut Self, kind: ObjectKind, id: u32, name: &str) {
+        debug::label_object(object_identifier(kind), id, name);
+    }
+
+    fn reset_state(self: &mut Self) {
+        self.state.invalidate();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nalgebra_glm as glm;
+
+    use super::*;
+
+    #[test]
+    fn samplers_and_integer_uniforms_accept_ints() {
+        for kind in [
+            gl::SAMPLER_2D_SHADOW,
+            gl::SAMPLER_3D,
+            gl::SAMPLER_2D_ARRAY,
+            gl::INT_SAMPLER_2D,
+            gl::UNSIGNED_INT_SAMPLER_CUBE,
+            gl::IMAGE_2D,
+        ] {
+            assert!(
+                uniform_type(kind).accepts(&UniformValue::Int(0)),
+                "{:#x}",
+                kind
+            );
+        }
+        assert!(uniform_type(gl::INT_VEC3).accepts(&UniformValue::IVec3(glm::IVec3::zeros())));
+        assert!(uniform_type(gl::BOOL_VEC2).accepts(&UniformValue::IVec2(glm::IVec2::zeros())));
+        assert!(uniform_type(gl::UNSIGNED_INT).accepts(&UniformValue::UInt(1)));
+        assert!(!uniform_type(gl::UNSIGNED_INT).accepts(&UniformValue::Float(1.0)));
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::debug;
+
+// queries a pass may have waiting on the GPU, frames beyond that go untimed
+const MAX_IN_FLIGHT: usize = 4;
+
+/*
+ * GL_TIME_ELAPSED timings per named pass.
+ * Each pass keeps a few queries in flight, a query is only read back
+ * once GL reports its result available so timing never stalls the
+ * pipeline, and is only reused after that. Results therefore lag a
+ * frame or two behind. If the GPU falls MAX_IN_FLIGHT frames behind,
+ * the pass is not timed until a query frees up.
+ * TIME_ELAPSED queries cannot nest, so passes must not overlap.
+ */
+pub struct GpuTimers {
+    passes: HashMap<String, GpuPass>,
+    active: Option<String>,
+}
+
+struct GpuPass {
+    // oldest first, results become available in this order
+    pending: VecDeque<u32>,
+    free: Vec<u32>,
+    elapsed_ms: Option<f32>,
+}
+
+impl GpuTimers {
+    pub fn new() -> Self {
+        GpuTimers {
+            passes: HashMap::new(),
+            active: None,
+        }
+    }
+
+    pub fn begin(self: &mut Self, name: &str) {
+        if self.active.is_some() {
+            self.end();
+        }
+
+        let pass = self
+            .passes
+            .entry(name.to_string())
+            .or_insert_with(GpuPass::new);
+        pass.collect();
+
+        // every query still waits for its result, reusing one would lose it
+        let Some(query) = pass.acquire() else {
+            return;
+        };
+        unsafe {
+            gl::BeginQuery(gl::TIME_ELAPSED, query);
+        }
+        debug::check_call("glBeginQuery", format_args!("(pass {})", name));
+        pass.pending.push_back(query);
+        self.active = Some(name.to_string());
+    }
+
+    pub fn end(self: &mut Self) {
+        if let Some(name) = self.active.take() {
+            unsafe {
+                gl::EndQuery(gl::TIME_ELAPSED);
+            }
+            debug::check_call("glEndQuery", format_args!("(pass {})", name));
+        }
+    }
+
+    // call once per frame after the last pass, Graphics::swap_buffers does this
+    pub fn end_frame(self: &mut Self) {
+        self.end();
+        for pass in self.passes.values_mut() {
+            pass.collect();
+        }
+    }
+
+    pub fn elapsed_ms(self: &Self, name: &str) -> Option<f32> {
+        self.passes.get(name).and_then(|pass| pass.elapsed_ms)
+    }
+
+    pub fn results(self: &Self) -> Vec<(String, f32)> {
+        let mut results: Vec<(String, f32)> = self
+            .passes
+            .iter()
+            .filter_map(|(name, pass)| pass.elapsed_ms.map(|ms| (name.clone(), ms)))
+            .collect();
+        results.sort_by(|a, b| a.0.cmp(&b.0));
+        results
+    }
+
+    pub fn destroy(self: &mut Self) {
+        self.end();
+        for pass in self.passes.values() {
+            for query in pass.pending.iter().chain(pass.free.iter()) {
+                unsafe {
+                    gl::DeleteQueries(1, query);
+                }
+            }
+        }
+        debug::check_call("glDeleteQueries", "(gpu timers)");
+        self.passes.clear();
+    }
+}
+
+impl Default for GpuTimers {
+    fn default() -> Self {
+        GpuTimers::new()
+    }
+}
+
+impl GpuPass {
+    fn new() -> Self {
+        GpuPass {
+            pending: VecDeque::new(),
+            free: Vec::new(),
+            elapsed_ms: None,
+        }
+    }
+
+    // a query that holds no unread result, None when MAX_IN_FLIGHT are pending
+    fn acquire(self: &mut Self) -> Option<u32> {
+        if let Some(query) = self.free.pop() {
+            return Some(query);
+        }
+        if self.pending.len() >= MAX_IN_FLIGHT {
+            return None;
+        }
+        let mut query: u32 = 0;
+        unsafe {
+            gl::GenQueries(1, &mut query);
+        }
+        debug::check_call("glGenQueries", "");
+        Some(query)
+    }
+
+    // reads back every finished query, the newest result wins
+    fn collect(self: &mut Self) {
+        while let Some(&query) = self.pending.front() {
+            let mut available: i32 = 0;
+            unsafe {
+                gl::GetQueryObjectiv(query, gl::QUERY_RESULT_AVAILABLE, &mut available);
+            }
+            if available == 0 {
+                return;
+            }
+
+            let mut elapsed_ns: u64 = 0;
+            unsafe {
+                gl::GetQueryObjectui64v(query, gl::QUERY_RESULT, &mut elapsed_ns);
+            }
+            debug::check_call("glGetQueryObjectui64v", format_args!("(query {})", query));
+            self.elapsed_ms = Some(elapsed_ns as f32 / 1_000_000.0);
+            self.pending.pop_front();
+            self.free.push(query);
+        }
+    }
+}
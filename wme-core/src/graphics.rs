@@ -1,83 +1,509 @@
-use glfw::{Context, Glfw, GlfwReceiver, InitError, PWindow, WindowEvent, WindowHint};
+use std::{cell::RefCell, error::Error, fmt, rc::Rc, time::Instant};
 
+use glfw::{
+    Context, Glfw, GlfwReceiver, InitError, Monitor, PWindow, WindowEvent, WindowHint, WindowMode,
+};
+
+use crate::debug::{self, DebugSeverity};
+use crate::frame_stats::FrameStats;
+use crate::framebuffer::Framebuffer;
+use crate::gamepad::Gamepads;
+use crate::gl_device::GlDevice;
+use crate::gpu_timer::GpuTimers;
+use crate::graphics_config::{DisplayMode, GraphicsConfig};
+use crate::headless::HeadlessContext;
+use crate::input_recording::InputFrame;
+use crate::input_state::InputState;
+use crate::render_device::{DepthState, RenderDevice, Viewport};
+
+type WindowAndEvents = (PWindow, GlfwReceiver<(f64, WindowEvent)>);
+
+#[derive(Debug)]
+pub enum GraphicsError {
+    Init(InitError),
+    NoMonitor,
+    MonitorNotFound(usize),
+    NoVideoMode,
+    WindowCreation(String),
+    Headless(String),
+    Offscreen(String),
+}
+
+impl fmt::Display for GraphicsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphicsError::Init(error) => write!(f, "Failed to init glfw: {}", error),
+            GraphicsError::NoMonitor => write!(f, "No monitor connected"),
+            GraphicsError::MonitorNotFound(index) => write!(f, "Monitor {} not found", index),
+            GraphicsError::NoVideoMode => write!(f, "Monitor has no video mode"),
+            GraphicsError::WindowCreation(title) => {
+                write!(f, "Failed to create GLFW window \"{}\"", title)
+            }
+            GraphicsError::Headless(reason) => {
+                write!(f, "Failed to create headless context: {}", reason)
+            }
+            GraphicsError::Offscreen(reason) => {
+                write!(f, "Failed to create offscreen framebuffer: {}", reason)
+            }
+        }
+    }
+}
+
+impl Error for GraphicsError {}
+
+impl From<InitError> for GraphicsError {
+    fn from(error: InitError) -> Self {
+        GraphicsError::Init(error)
+    }
+}
+
+/*
+ * Implemented by anything sized after the window, Graphics::notify_resize
+ * forwards framebuffer size and content scale changes to them.
+ */
+pub trait Resizable {
+    fn resize(self: &mut Self, width: u32, height: u32);
+
+    fn set_content_scale(self: &mut Self, _x_scale: f32, _y_scale: f32) {}
+}
+
+// written by the GLFW window callbacks, applied in Graphics::check_events
+#[derive(Default)]
+struct WindowChanges {
+    framebuffer_size: Option<(i32, i32)>,
+    content_scale: Option<(f32, f32)>,
+    iconified: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CursorMode {
+    Normal,
+    Hidden,
+    // hidden and locked to the window, with raw motion where supported
+    Captured,
+}
+
+// the GLFW side of a windowed Graphics
+pub struct Window {
+    pub glfw: Glfw,
+    pub handle: PWindow,
+    pub events: GlfwReceiver<(f64, WindowEvent)>,
+}
+
+/*
+ * The window, GL context and per-frame state everything draws through.
+ * Headless instances have no GLFW at all: window is None, the context
+ * comes from headless and rendering goes to offscreen. Use the methods
+ * here rather than window where both kinds should work.
+ */
 pub struct Graphics {
     pub screen_width: u32,
     pub screen_height: u32,
-    pub glfw: Glfw,
-    pub window: PWindow,
-    pub events: GlfwReceiver<(f64, WindowEvent)>,
+    pub window: Option<Window>,
+    pub headless: Option<HeadlessContext>,
+    pub input: InputState,
+    pub gamepads: Gamepads,
+    pub device: GlDevice,
     pub delta_time: f32,
     pub current_time: f32,
     pub previous_time: f32,
+    pub offscreen: Option<Framebuffer>,
+    pub frame_stats: FrameStats,
+    pub gpu_timers: GpuTimers,
+    pub content_scale: (f32, f32),
+    pub minimized: bool,
+    pub resized: bool,
+    pub scale_changed: bool,
+    cursor_mode: CursorMode,
+    window_changes: Rc<RefCell<WindowChanges>>,
+    should_close: bool,
+    start_time: Instant,
 }
 
 impl Graphics {
-    pub fn new(width: u32, height: u32) -> Result<Graphics, InitError> {
-        let mut glfw = glfw::init(glfw::fail_on_errors).unwrap();
-        glfw.window_hint(WindowHint::ContextVersion(3, 3));
-        glfw.window_hint(WindowHint::OpenGlProfile(glfw::OpenGlProfileHint::Core));
+    pub fn new(width: u32, height: u32) -> Result<Graphics, GraphicsError> {
+        GraphicsConfig::new().size(width, height).build()
+    }
+
+    pub fn with_config(config: GraphicsConfig) -> Result<Graphics, GraphicsError> {
+        if let Some(backend) = config.headless {
+            let context =
+                HeadlessContext::new(backend, &config).map_err(GraphicsError::Headless)?;
+            gl::load_with(|symbol| context.get_proc_address(symbol));
+            return Self::with_context(&config, None, Some(context), Gamepads::headless());
+        }
+
+        let mut glfw = glfw::init(glfw::log_errors)?;
+        glfw.window_hint(WindowHint::ContextVersion(
+            config.gl_version.0,
+            config.gl_version.1,
+        ));
+        glfw.window_hint(WindowHint::OpenGlProfile(config.gl_profile.to_glfw()));
+        glfw.window_hint(WindowHint::OpenGlForwardCompat(cfg!(target_os = "macos")));
+        glfw.window_hint(WindowHint::OpenGlDebugContext(config.debug_context));
+        glfw.window_hint(WindowHint::Resizable(config.resizable));
+        glfw.window_hint(WindowHint::Samples(config.samples));
+        glfw.window_hint(WindowHint::SRgbCapable(config.srgb));
+        glfw.window_hint(WindowHint::Decorated(true));
 
-        const SCR_WIDTH: u32 = 1280;
-        const SCR_HEIGHT: u32 = 720;
+        let created = match config.display_mode {
+            DisplayMode::Windowed => glfw.create_window(
+                config.width,
+                config.height,
+                &config.title,
+                WindowMode::Windowed,
+            ),
+            _ => glfw.with_connected_monitors(|glfw, monitors| {
+                create_monitor_window(glfw, monitors, &config)
+            })?,
+        };
 
-        let (mut window, events) = glfw
-            .create_window(
-                SCR_WIDTH,
-                SCR_HEIGHT,
-                "Wave Motion Engine",
-                glfw::WindowMode::Windowed,
-            )
-            .expect("Failed to create GLFW window");
+        let (mut handle, events) =
+            created.ok_or_else(|| GraphicsError::WindowCreation(config.title.clone()))?;
 
-        window.set_key_polling(true);
-        window.make_current();
+        handle.set_key_polling(true);
+        handle.make_current();
+        glfw.set_swap_interval(config.swap_interval);
 
-        gl::load_with(|symbol| window.get_proc_address(symbol));
+        gl::load_with(|symbol| handle.get_proc_address(symbol));
+
+        let gamepads = Gamepads::new(&mut glfw);
+        let window = Window {
+            glfw,
+            handle,
+            events,
+        };
+        Self::with_context(&config, Some(window), None, gamepads)
+    }
+
+    // the rest of with_config, once a context is current and GL is loaded
+    fn with_context(
+        config: &GraphicsConfig,
+        mut window: Option<Window>,
+        headless: Option<HeadlessContext>,
+        gamepads: Gamepads,
+    ) -> Result<Graphics, GraphicsError> {
+        if config.debug_context {
+            debug::enable_debug_output(DebugSeverity::Low);
+            debug::set_call_checks(true);
+        }
 
+        let mut device = GlDevice::new();
+        device.set_depth(DepthState::default());
         unsafe {
-            gl::Enable(gl::DEPTH_TEST);
+            if config.samples.is_some() {
+                gl::Enable(gl::MULTISAMPLE);
+            }
+            if config.srgb {
+                gl::Enable(gl::FRAMEBUFFER_SRGB);
+            }
         }
 
-        window.set_framebuffer_size_callback(frame_buffer_size_callback);
-        window.set_cursor_pos_polling(true);
-        window.set_scroll_polling(true);
+        let window_changes: Rc<RefCell<WindowChanges>> = Rc::default();
+        if let Some(window) = &mut window {
+            let handle = &mut window.handle;
+            let changes = Rc::clone(&window_changes);
+            handle.set_framebuffer_size_callback(move |_, width, height| {
+                changes.borrow_mut().framebuffer_size = Some((width, height));
+            });
+            let changes = Rc::clone(&window_changes);
+            handle.set_content_scale_callback(move |_, x_scale, y_scale| {
+                changes.borrow_mut().content_scale = Some((x_scale, y_scale));
+            });
+            let changes = Rc::clone(&window_changes);
+            handle.set_iconify_callback(move |_, iconified| {
+                changes.borrow_mut().iconified = Some(iconified);
+            });
+            handle.set_mouse_button_polling(true);
+            handle.set_cursor_pos_polling(true);
+            handle.set_scroll_polling(true);
+            handle.set_focus_polling(true);
+        }
+
+        // headless contexts have no default framebuffer worth drawing into
+        let offscreen = match &headless {
+            Some(_) => {
+                let framebuffer = Framebuffer::new(&mut device, config.width, config.height)
+                    .map_err(|error| GraphicsError::Offscreen(error.to_string()))?;
+                framebuffer.bind();
+                device.set_viewport(framebuffer.viewport());
+                Some(framebuffer)
+            }
+            None => None,
+        };
+
+        let ((width, height), content_scale) = match (&offscreen, &window) {
+            (Some(framebuffer), _) => ((framebuffer.width, framebuffer.height), (1.0, 1.0)),
+            (None, Some(window)) => {
+                let (width, height) = window.handle.get_framebuffer_size();
+                (
+                    (width as u32, height as u32),
+                    window.handle.get_content_scale(),
+                )
+            }
+            (None, None) => ((config.width, config.height), (1.0, 1.0)),
+        };
         Ok(Graphics {
             screen_width: width,
             screen_height: height,
-            glfw,
             window,
-            events,
+            headless,
+            input: InputState::new(),
+            gamepads,
+            device,
             delta_time: 0.0,
             current_time: 0.0,
             previous_time: 0.0,
+            offscreen,
+            frame_stats: FrameStats::default(),
+            gpu_timers: GpuTimers::new(),
+            content_scale,
+            minimized: false,
+            resized: false,
+            scale_changed: false,
+            cursor_mode: CursorMode::Normal,
+            window_changes,
+            should_close: false,
+            start_time: Instant::now(),
         })
     }
 
     pub fn window_should_close(self: &Self) -> bool {
-        self.window.should_close()
+        self.should_close
+            || self
+                .window
+                .as_ref()
+                .is_some_and(|window| window.handle.should_close())
+    }
+
+    // the only way a headless run ends, short of a frame limit on Runner
+    pub fn set_should_close(self: &mut Self, should_close: bool) {
+        self.should_close = should_close;
+        if let Some(window) = &mut self.window {
+            window.handle.set_should_close(should_close);
+        }
+    }
+
+    pub fn set_title(self: &mut Self, title: &str) {
+        if let Some(window) = &mut self.window {
+            window.handle.set_title(title);
+        }
     }
 
+    // in screen coordinates like cursor positions, the framebuffer size when headless
+    pub fn window_size(self: &Self) -> (i32, i32) {
+        match &self.window {
+            Some(window) => window.handle.get_size(),
+            None => (self.screen_width as i32, self.screen_height as i32),
+        }
+    }
+
+    // seconds since Graphics was created
     pub fn get_time(self: &Self) -> f32 {
-        self.glfw.get_time() as f32
+        self.start_time.elapsed().as_secs_f32()
     }
 
     pub fn update_time(self: &mut Self) {
         self.current_time = self.get_time();
         self.delta_time = self.current_time - self.previous_time;
         self.previous_time = self.current_time;
+        self.frame_stats.push(self.delta_time);
     }
 
+    // drains every window event into input, once per frame
     pub fn check_events(self: &mut Self) {
-        self.glfw.poll_events();
+        self.input.begin_frame();
+        if let Some(window) = &mut self.window {
+            window.glfw.poll_events();
+            for (_, event) in glfw::flush_messages(&window.events) {
+                self.input.handle_event(&event);
+            }
+        }
+        self.gamepads.update();
+        self.apply_window_changes();
+    }
+
+    // check_events for replays, window input is dropped in favour of the recorded frame
+    pub fn replay_events(self: &mut Self, frame: &InputFrame) {
+        if let Some(window) = &mut self.window {
+            window.glfw.poll_events();
+            for _ in glfw::flush_messages(&window.events) {}
+        }
+        self.input.begin_frame();
+        for event in &frame.events {
+            self.input.handle_event(event);
+        }
+        self.gamepads.replay(&frame.gamepads);
+        self.apply_window_changes();
+    }
+
+    // blocks until an event arrives, used while minimized
+    // input events stay queued for the next check_events
+    pub fn wait_events(self: &mut Self) {
+        if let Some(window) = &mut self.window {
+            window.glfw.wait_events();
+        }
+        self.apply_window_changes();
+    }
+
+    pub fn notify_resize(self: &Self, subscribers: &mut [&mut dyn Resizable]) {
+        for subscriber in subscribers.iter_mut() {
+            if self.resized {
+                subscriber.resize(self.screen_width, self.screen_height);
+            }
+            if self.scale_changed {
+                subscriber.set_content_scale(self.content_scale.0, self.content_scale.1);
+            }
+        }
+    }
+
+    pub fn aspect_ratio(self: &Self) -> f32 {
+        self.screen_width as f32 / self.screen_height.max(1) as f32
+    }
+
+    fn apply_window_changes(self: &mut Self) {
+        let changes = std::mem::take(&mut *self.window_changes.borrow_mut());
+        self.resized = false;
+        self.scale_changed = false;
+
+        if let Some(iconified) = changes.iconified {
+            self.minimized = iconified;
+        }
+
+        // minimizing reports a 0x0 framebuffer, keep the last usable size
+        if let Some((width, height)) = changes.framebuffer_size {
+            let (width, height) = (width as u32, height as u32);
+            let changed = width != self.screen_width || height != self.screen_height;
+            if width > 0 && height > 0 && changed {
+                self.screen_width = width;
+                self.screen_height = height;
+                self.resized = true;
+                if self.offscreen.is_none() {
+                    self.device
+                        .set_viewport(Viewport::new(0, 0, width as i32, height as i32));
+                }
+            }
+        }
+
+        if let Some(content_scale) = changes.content_scale {
+            self.scale_changed = content_scale != self.content_scale;
+            self.content_scale = content_scale;
+        }
     }
 
     pub fn swap_buffers(self: &mut Self) {
-        self.window.swap_buffers();
+        self.gpu_timers.end_frame();
+        match &mut self.window {
+            Some(window) if self.offscreen.is_none() => window.handle.swap_buffers(),
+            _ => unsafe {
+                gl::Finish();
+            },
+        }
+    }
+
+    pub fn cursor_mode(self: &Self) -> CursorMode {
+        self.cursor_mode
+    }
+
+    pub fn set_cursor_mode(self: &mut Self, mode: CursorMode) {
+        if mode == self.cursor_mode {
+            return;
+        }
+
+        if let Some(window) = &mut self.window {
+            let handle = &mut window.handle;
+            match mode {
+                CursorMode::Normal => handle.set_cursor_mode(glfw::CursorMode::Normal),
+                CursorMode::Hidden => handle.set_cursor_mode(glfw::CursorMode::Hidden),
+                CursorMode::Captured => handle.set_cursor_mode(glfw::CursorMode::Disabled),
+            }
+            let raw_motion = mode == CursorMode::Captured && window.glfw.supports_raw_motion();
+            handle.set_raw_mouse_motion(raw_motion);
+
+            // a released cursor shows up where the view was aimed, not where it left off
+            if self.cursor_mode == CursorMode::Captured {
+                let (width, height) = handle.get_size();
+                handle.set_cursor_pos(width as f64 / 2.0, height as f64 / 2.0);
+            }
+        }
+
+        self.cursor_mode = mode;
+        self.input.set_cursor_captured(mode == CursorMode::Captured);
+    }
+
+    pub fn toggle_cursor_capture(self: &mut Self) {
+        match self.cursor_mode {
+            CursorMode::Captured => self.set_cursor_mode(CursorMode::Normal),
+            _ => self.set_cursor_mode(CursorMode::Captured),
+        }
+    }
+
+    pub fn is_headless(self: &Self) -> bool {
+        self.headless.is_some()
+    }
+
+    // id of the framebuffer that stands in for the window, 0 unless headless
+    pub fn default_framebuffer(self: &Self) -> u32 {
+        self.offscreen
+            .as_ref()
+            .map_or(0, |framebuffer| framebuffer.id)
+    }
+
+    pub fn bind_default_framebuffer(self: &mut Self) {
+        match &self.offscreen {
+            Some(framebuffer) => {
+                framebuffer.bind();
+                self.device.set_viewport(framebuffer.viewport());
+            }
+            None => {
+                unsafe {
+                    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                }
+                self.device.set_viewport(Viewport::new(
+                    0,
+                    0,
+                    self.screen_width as i32,
+                    self.screen_height as i32,
+                ));
+            }
+        }
     }
 }
 
-fn frame_buffer_size_callback(_window: &mut glfw::Window, width: i32, height: i32) {
-    unsafe {
-        gl::Viewport(0, 0, width, height);
+fn create_monitor_window(
+    glfw: &mut Glfw,
+    monitors: &[&mut Monitor],
+    config: &GraphicsConfig,
+) -> Result<Option<WindowAndEvents>, GraphicsError> {
+    // glfwGetMonitors always lists the primary monitor first
+    let index = config.monitor.unwrap_or(0);
+    let monitor: &Monitor = match monitors.get(index) {
+        Some(monitor) => monitor,
+        None if config.monitor.is_none() => return Err(GraphicsError::NoMonitor),
+        None => return Err(GraphicsError::MonitorNotFound(index)),
+    };
+
+    match config.display_mode {
+        DisplayMode::Borderless => {
+            // a fullscreen window matching the desktop mode keeps the monitor from switching modes
+            let mode = monitor.get_video_mode().ok_or(GraphicsError::NoVideoMode)?;
+            glfw.window_hint(WindowHint::RedBits(Some(mode.red_bits)));
+            glfw.window_hint(WindowHint::GreenBits(Some(mode.green_bits)));
+            glfw.window_hint(WindowHint::BlueBits(Some(mode.blue_bits)));
+            glfw.window_hint(WindowHint::RefreshRate(Some(mode.refresh_rate)));
+            glfw.window_hint(WindowHint::Decorated(false));
+            Ok(glfw.create_window(
+                mode.width,
+                mode.height,
+                &config.title,
+                WindowMode::FullScreen(monitor),
+            ))
+        }
+        _ => Ok(glfw.create_window(
+            config.width,
+            config.height,
+            &config.title,
+            WindowMode::FullScreen(monitor),
+        )),
     }
 }
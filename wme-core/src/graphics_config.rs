@@ -0,0 +1,170 @@
+use glfw::{OpenGlProfileHint, SwapInterval};
+
+use crate::graphics::{Graphics, GraphicsError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisplayMode {
+    Windowed,
+    Fullscreen,
+    Borderless,
+}
+
+/*
+ * Context APIs that can run without a display server.
+ * Egl expects a surfaceless capable driver (Mesa, NVIDIA), OsMesa
+ * renders on the CPU through llvmpipe.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeadlessBackend {
+    Egl,
+    OsMesa,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlProfile {
+    Core,
+    Compat,
+    Any,
+}
+
+impl GlProfile {
+    pub fn to_glfw(self: &Self) -> OpenGlProfileHint {
+        match self {
+            GlProfile::Core => OpenGlProfileHint::Core,
+            GlProfile::Compat => OpenGlProfileHint::Compat,
+            GlProfile::Any => OpenGlProfileHint::Any,
+        }
+    }
+}
+
+/*
+ * Describes the window and GL context that Graphics creates.
+ * Every setter consumes and returns the config so calls can be chained:
+ *     GraphicsConfig::new().title("Viewer").size(800, 600).samples(4).build()?
+ */
+#[derive(Debug, Clone)]
+pub struct GraphicsConfig {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    pub resizable: bool,
+    pub display_mode: DisplayMode,
+    pub monitor: Option<usize>,
+    pub swap_interval: SwapInterval,
+    pub samples: Option<u32>,
+    pub srgb: bool,
+    pub gl_version: (u32, u32),
+    pub gl_profile: GlProfile,
+    pub debug_context: bool,
+    pub headless: Option<HeadlessBackend>,
+}
+
+impl GraphicsConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn title(mut self: Self, title: &str) -> Self {
+        self.title = title.to_string();
+        self
+    }
+
+    pub fn size(mut self: Self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn resizable(mut self: Self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn windowed(mut self: Self) -> Self {
+        self.display_mode = DisplayMode::Windowed;
+        self.monitor = None;
+        self
+    }
+
+    // monitor indexes follow glfwGetMonitors, None picks the primary monitor
+    pub fn fullscreen(mut self: Self, monitor: Option<usize>) -> Self {
+        self.display_mode = DisplayMode::Fullscreen;
+        self.monitor = monitor;
+        self
+    }
+
+    pub fn borderless(mut self: Self, monitor: Option<usize>) -> Self {
+        self.display_mode = DisplayMode::Borderless;
+        self.monitor = monitor;
+        self
+    }
+
+    pub fn vsync(mut self: Self, enabled: bool) -> Self {
+        self.swap_interval = if enabled {
+            SwapInterval::Sync(1)
+        } else {
+            SwapInterval::None
+        };
+        self
+    }
+
+    pub fn swap_interval(mut self: Self, swap_interval: SwapInterval) -> Self {
+        self.swap_interval = swap_interval;
+        self
+    }
+
+    pub fn samples(mut self: Self, samples: Option<u32>) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn srgb(mut self: Self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    pub fn gl_version(mut self: Self, major: u32, minor: u32) -> Self {
+        self.gl_version = (major, minor);
+        self
+    }
+
+    pub fn gl_profile(mut self: Self, gl_profile: GlProfile) -> Self {
+        self.gl_profile = gl_profile;
+        self
+    }
+
+    pub fn debug_context(mut self: Self, debug_context: bool) -> Self {
+        self.debug_context = debug_context;
+        self
+    }
+
+    // renders into Graphics::offscreen instead of a visible window
+    pub fn headless(mut self: Self, backend: HeadlessBackend) -> Self {
+        self.headless = Some(backend);
+        self
+    }
+
+    pub fn build(self: Self) -> Result<Graphics, GraphicsError> {
+        Graphics::with_config(self)
+    }
+}
+
+impl Default for GraphicsConfig {
+    fn default() -> Self {
+        GraphicsConfig {
+            title: String::from("Wave Motion Engine"),
+            width: 1280,
+            height: 720,
+            resizable: true,
+            display_mode: DisplayMode::Windowed,
+            monitor: None,
+            swap_interval: SwapInterval::Sync(1),
+            samples: None,
+            srgb: false,
+            gl_version: (3, 3),
+            gl_profile: GlProfile::Core,
+            debug_context: false,
+            headless: None,
+        }
+    }
+}
@@ -0,0 +1,361 @@
+use std::{
+    ffi::{c_char, c_int, c_void, CString},
+    ptr,
+};
+
+use crate::graphics_config::{GlProfile, GraphicsConfig, HeadlessBackend};
+
+/*
+ * A GL context with no window and no display server, for CI and render
+ * farms. GLFW 3.3 can't create one (its null platform only arrived in
+ * 3.4), so this talks to EGL or OSMesa directly. Both libraries are
+ * opened at runtime: builds and windowed runs don't need them installed.
+ * The context has no default framebuffer, Graphics renders into an
+ * offscreen Framebuffer instead.
+ */
+pub struct HeadlessContext {
+    library: *mut c_void,
+    api: ContextApi,
+}
+
+enum ContextApi {
+    Egl {
+        display: *mut c_void,
+        context: *mut c_void,
+        get_proc_address: EglGetProcAddress,
+    },
+    OsMesa {
+        context: *mut c_void,
+        get_proc_address: OsMesaGetProcAddress,
+        // OSMesa always draws into client memory, even when a framebuffer is bound
+        _buffer: Vec<u8>,
+    },
+}
+
+type EglGetProcAddress = unsafe extern "C" fn(*const c_char) -> *const c_void;
+type OsMesaGetProcAddress = unsafe extern "C" fn(*const c_char) -> *const c_void;
+
+const RTLD_NOW: c_int = 2;
+
+// not in the standard library, but libc always provides them on unix
+#[cfg(unix)]
+extern "C" {
+    fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
+    fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    fn dlclose(handle: *mut c_void) -> c_int;
+}
+
+// elsewhere nothing loads and new() reports the library as missing
+#[cfg(not(unix))]
+unsafe fn dlopen(_filename: *const c_char, _flag: c_int) -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[cfg(not(unix))]
+unsafe fn dlsym(_handle: *mut c_void, _symbol: *const c_char) -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[cfg(not(unix))]
+unsafe fn dlclose(_handle: *mut c_void) -> c_int {
+    0
+}
+
+const EGL_NONE: i32 = 0x3038;
+const EGL_SURFACE_TYPE: i32 = 0x3033;
+const EGL_PBUFFER_BIT: i32 = 0x0001;
+const EGL_RENDERABLE_TYPE: i32 = 0x3040;
+const EGL_OPENGL_BIT: i32 = 0x0008;
+const EGL_OPENGL_API: u32 = 0x30A2;
+const EGL_CONTEXT_MAJOR_VERSION: i32 = 0x3098;
+const EGL_CONTEXT_MINOR_VERSION: i32 = 0x30FB;
+const EGL_CONTEXT_OPENGL_PROFILE_MASK: i32 = 0x30FD;
+const EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT: i32 = 0x0001;
+const EGL_CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT: i32 = 0x0002;
+const EGL_CONTEXT_OPENGL_DEBUG: i32 = 0x31B0;
+const EGL_PLATFORM_SURFACELESS_MESA: u32 = 0x31DD;
+
+const OSMESA_FORMAT: c_int = 0x22;
+const OSMESA_RGBA: c_int = 0x1908;
+const OSMESA_DEPTH_BITS: c_int = 0x30;
+const OSMESA_STENCIL_BITS: c_int = 0x31;
+const OSMESA_PROFILE: c_int = 0x33;
+const OSMESA_CORE_PROFILE: c_int = 0x34;
+const OSMESA_COMPAT_PROFILE: c_int = 0x35;
+const OSMESA_CONTEXT_MAJOR_VERSION: c_int = 0x36;
+const OSMESA_CONTEXT_MINOR_VERSION: c_int = 0x37;
+const GL_UNSIGNED_BYTE: u32 = 0x1401;
+
+impl HeadlessContext {
+    // creates the context and makes it current on the calling thread
+    pub fn new(backend: HeadlessBackend, config: &GraphicsConfig) -> Result<Self, String> {
+        match backend {
+            HeadlessBackend::Egl => Self::create_egl(config),
+            HeadlessBackend::OsMesa => Self::create_osmesa(config),
+        }
+    }
+
+    pub fn get_proc_address(self: &Self, symbol: &str) -> *const c_void {
+        let symbol = match CString::new(symbol) {
+            Ok(symbol) => symbol,
+            Err(_) => return ptr::null(),
+        };
+        unsafe {
+            match &self.api {
+                ContextApi::Egl {
+                    get_proc_address, ..
+                } => get_proc_address(symbol.as_ptr()),
+                ContextApi::OsMesa {
+                    get_proc_address, ..
+                } => get_proc_address(symbol.as_ptr()),
+            }
+        }
+    }
+
+    fn create_egl(config: &GraphicsConfig) -> Result<Self, String> {
+        type GetPlatformDisplay = unsafe extern "C" fn(u32, *mut c_void, *const i32) -> *mut c_void;
+        type GetDisplay = unsafe extern "C" fn(*mut c_void) -> *mut c_void;
+        type Initialize = unsafe extern "C" fn(*mut c_void, *mut i32, *mut i32) -> u32;
+        type BindApi = unsafe extern "C" fn(u32) -> u32;
+        type ChooseConfig =
+            unsafe extern "C" fn(*mut c_void, *const i32, *mut *mut c_void, i32, *mut i32) -> u32;
+        type CreateContext =
+            unsafe extern "C" fn(*mut c_void, *mut c_void, *mut c_void, *const i32) -> *mut c_void;
+        type MakeCurrent =
+            unsafe extern "C" fn(*mut c_void, *mut c_void, *mut c_void, *mut c_void) -> u32;
+        type Terminate = unsafe extern "C" fn(*mut c_void) -> u32;
+        type GetError = unsafe extern "C" fn() -> i32;
+
+        let library = open_library(&["libEGL.so.1", "libEGL.so"])?;
+        unsafe {
+            let get_proc_address: EglGetProcAddress = load(library, "eglGetProcAddress")?;
+            let get_display: GetDisplay = load(library, "eglGetDisplay")?;
+            let initialize: Initialize = load(library, "eglInitialize")?;
+            let bind_api: BindApi = load(library, "eglBindAPI")?;
+            let choose_config: ChooseConfig = load(library, "eglChooseConfig")?;
+            let create_context: CreateContext = load(library, "eglCreateContext")?;
+            let make_current: MakeCurrent = load(library, "eglMakeCurrent")?;
+            let terminate: Terminate = load(library, "eglTerminate")?;
+            let get_error: GetError = load(library, "eglGetError")?;
+            let fail = |step: &str| -> String {
+                format!("{} failed with EGL error {:#x}", step, get_error())
+            };
+
+            // Mesa's surfaceless platform needs no display server or GPU, other drivers pick their own
+            let name = CString::new("eglGetPlatformDisplayEXT").unwrap();
+            let get_platform_display = get_proc_address(name.as_ptr());
+            let mut display = ptr::null_mut();
+            if !get_platform_display.is_null() {
+                let get_platform_display: GetPlatformDisplay =
+                    std::mem::transmute(get_platform_display);
+                display = get_platform_display(
+                    EGL_PLATFORM_SURFACELESS_MESA,
+                    ptr::null_mut(),
+                    ptr::null(),
+                );
+            }
+            if display.is_null() {
+                display = get_display(ptr::null_mut());
+            }
+            let (mut major, mut minor) = (0, 0);
+            if display.is_null() || initialize(display, &mut major, &mut minor) == 0 {
+                let error = fail("eglInitialize");
+                dlclose(library);
+                return Err(error);
+            }
+
+            let mut egl_config = ptr::null_mut();
+            let mut count = 0;
+            let config_attributes = [
+                EGL_SURFACE_TYPE,
+                EGL_PBUFFER_BIT,
+                EGL_RENDERABLE_TYPE,
+                EGL_OPENGL_BIT,
+                EGL_NONE,
+            ];
+            let profile = match config.gl_profile {
+                GlProfile::Compat => EGL_CONTEXT_OPENGL_COMPATIBILITY_PROFILE_BIT,
+                _ => EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            };
+            let context_attributes = [
+                EGL_CONTEXT_MAJOR_VERSION,
+                config.gl_version.0 as i32,
+                EGL_CONTEXT_MINOR_VERSION,
+                config.gl_version.1 as i32,
+                EGL_CONTEXT_OPENGL_PROFILE_MASK,
+                profile,
+                EGL_CONTEXT_OPENGL_DEBUG,
+                config.debug_context as i32,
+                EGL_NONE,
+            ];
+
+            let mut context = ptr::null_mut();
+            let result = if bind_api(EGL_OPENGL_API) == 0 {
+                Err(fail("eglBindAPI"))
+            } else if choose_config(
+                display,
+                config_attributes.as_ptr(),
+                &mut egl_config,
+                1,
+                &mut count,
+            ) == 0
+                || count == 0
+            {
+                Err(fail("eglChooseConfig"))
+            } else {
+                context = create_context(
+                    display,
+                    egl_config,
+                    ptr::null_mut(),
+                    context_attributes.as_ptr(),
+                );
+                if context.is_null() {
+                    Err(fail("eglCreateContext"))
+                } else if make_current(display, ptr::null_mut(), ptr::null_mut(), context) == 0 {
+                    Err(fail("eglMakeCurrent"))
+                } else {
+                    Ok(())
+                }
+            };
+            if let Err(error) = result {
+                // terminating the display also frees a context that failed to become current
+                terminate(display);
+                dlclose(library);
+                return Err(error);
+            }
+
+            Ok(HeadlessContext {
+                library,
+                api: ContextApi::Egl {
+                    display,
+                    context,
+                    get_proc_address,
+                },
+            })
+        }
+    }
+
+    fn create_osmesa(config: &GraphicsConfig) -> Result<Self, String> {
+        type CreateContextAttribs = unsafe extern "C" fn(*const c_int, *mut c_void) -> *mut c_void;
+        type MakeCurrent = unsafe extern "C" fn(*mut c_void, *mut c_void, u32, c_int, c_int) -> u8;
+        type DestroyContext = unsafe extern "C" fn(*mut c_void);
+
+        let library = open_library(&["libOSMesa.so.8", "libOSMesa.so.6", "libOSMesa.so"])?;
+        unsafe {
+            let create_context: CreateContextAttribs = load(library, "OSMesaCreateContextAttribs")?;
+            let make_current: MakeCurrent = load(library, "OSMesaMakeCurrent")?;
+            let destroy_context: DestroyContext = load(library, "OSMesaDestroyContext")?;
+            let get_proc_address: OsMesaGetProcAddress = load(library, "OSMesaGetProcAddress")?;
+
+            let profile = match config.gl_profile {
+                GlProfile::Compat => OSMESA_COMPAT_PROFILE,
+                _ => OSMESA_CORE_PROFILE,
+            };
+            let attributes = [
+                OSMESA_FORMAT,
+                OSMESA_RGBA,
+                OSMESA_DEPTH_BITS,
+                24,
+                OSMESA_STENCIL_BITS,
+                8,
+                OSMESA_PROFILE,
+                profile,
+                OSMESA_CONTEXT_MAJOR_VERSION,
+                config.gl_version.0 as c_int,
+                OSMESA_CONTEXT_MINOR_VERSION,
+                config.gl_version.1 as c_int,
+                0,
+            ];
+            let context = create_context(attributes.as_ptr(), ptr::null_mut());
+            if context.is_null() {
+                dlclose(library);
+                return Err("OSMesaCreateContextAttribs failed".to_string());
+            }
+
+            let (width, height) = (config.width.max(1), config.height.max(1));
+            let mut buffer = vec![0_u8; width as usize * height as usize * 4];
+            if make_current(
+                context,
+                buffer.as_mut_ptr().cast(),
+                GL_UNSIGNED_BYTE,
+                width as c_int,
+                height as c_int,
+            ) == 0
+            {
+                destroy_context(context);
+                dlclose(library);
+                return Err("OSMesaMakeCurrent failed".to_string());
+            }
+
+            Ok(HeadlessContext {
+                library,
+                api: ContextApi::OsMesa {
+                    context,
+                    get_proc_address,
+                    _buffer: buffer,
+                },
+            })
+        }
+    }
+}
+
+impl Drop for HeadlessContext {
+    fn drop(self: &mut Self) {
+        unsafe {
+            match &self.api {
+                ContextApi::Egl {
+                    display, context, ..
+                } => {
+                    type MakeCurrent = unsafe extern "C" fn(
+                        *mut c_void,
+                        *mut c_void,
+                        *mut c_void,
+                        *mut c_void,
+                    ) -> u32;
+                    type DestroyContext = unsafe extern "C" fn(*mut c_void, *mut c_void) -> u32;
+                    type Terminate = unsafe extern "C" fn(*mut c_void) -> u32;
+                    if let (Ok(make_current), Ok(destroy_context), Ok(terminate)) = (
+                        load::<MakeCurrent>(self.library, "eglMakeCurrent"),
+                        load::<DestroyContext>(self.library, "eglDestroyContext"),
+                        load::<Terminate>(self.library, "eglTerminate"),
+                    ) {
+                        make_current(*display, ptr::null_mut(), ptr::null_mut(), ptr::null_mut());
+                        destroy_context(*display, *context);
+                        terminate(*display);
+                    }
+                }
+                ContextApi::OsMesa { context, .. } => {
+                    type DestroyContext = unsafe extern "C" fn(*mut c_void);
+                    if let Ok(destroy_context) =
+                        load::<DestroyContext>(self.library, "OSMesaDestroyContext")
+                    {
+                        destroy_context(*context);
+                    }
+                }
+            }
+            dlclose(self.library);
+        }
+    }
+}
+
+// the first of names that loads
+fn open_library(names: &[&str]) -> Result<*mut c_void, String> {
+    for name in names {
+        let c_name = CString::new(*name).unwrap();
+        let library = unsafe { dlopen(c_name.as_ptr(), RTLD_NOW) };
+        if !library.is_null() {
+            return Ok(library);
+        }
+    }
+    Err(format!("Could not load any of {}", names.join(", ")))
+}
+
+// T must be the extern "C" fn type of the symbol
+unsafe fn load<T: Copy>(library: *mut c_void, name: &str) -> Result<T, String> {
+    let c_name = CString::new(name).unwrap();
+    let symbol = dlsym(library, c_name.as_ptr());
+    if symbol.is_null() {
+        return Err(format!("Missing symbol {}", name));
+    }
+    Ok(std::mem::transmute_copy(&symbol))
+}
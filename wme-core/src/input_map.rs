@@ -0,0 +1,760 @@
+use std::{collections::HashMap, error::Error, fmt, path::Path};
+
+use glfw::{GamepadAxis, GamepadButton, Key, MouseButton};
+
+use crate::gamepad::Gamepads;
+use crate::input_state::{Button, InputState};
+
+// look axes are summed mouse movement in pixels, a full stick deflection maps to this many
+const GAMEPAD_LOOK_SCALE: f32 = 15.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseAxis {
+    X,
+    Y,
+}
+
+/*
+ * Something an action or axis can be bound to. Keys are GLFW key
+ * codes, which name physical positions on a US layout, so WASD stays
+ * under the same fingers on AZERTY or QWERTZ keyboards.
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(Key),
+    MouseButton(MouseButton),
+    MouseAxis(MouseAxis),
+    Scroll(MouseAxis),
+    GamepadButton(GamepadButton),
+    GamepadAxis(GamepadAxis),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisBinding {
+    pub binding: Binding,
+    pub scale: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputMapError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for InputMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for InputMapError {}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct ActionState {
+    down: bool,
+    previous: bool,
+    // a key or mouse button went down or up this frame, even if it's back where it was
+    pressed: bool,
+    released: bool,
+}
+
+/*
+ * Named actions and axes resolved from their bindings once per frame
+ * by update(). Actions are down while any binding is, axes sum the
+ * value of every binding times its scale: 1 for held keys and buttons,
+ * the frame's movement for mouse and scroll axes. Mouse axes only move
+ * while the cursor is captured, leaving a free cursor to the UI.
+ */
+#[derive(Debug, Clone)]
+pub struct InputMap {
+    actions: HashMap<String, Vec<Binding>>,
+    axes: HashMap<String, Vec<AxisBinding>>,
+    action_states: HashMap<String, ActionState>,
+    axis_values: HashMap<String, f32>,
+}
+
+impl InputMap {
+    // an empty map, Default has the engine's standard bindings
+    pub fn new() -> Self {
+        InputMap {
+            actions: HashMap::new(),
+            axes: HashMap::new(),
+            action_states: HashMap::new(),
+            axis_values: HashMap::new(),
+        }
+    }
+
+    /*
+     * One binding list per line, # starts a comment:
+     *     action quit = key:Escape
+     *     axis move_forward = key:W, key:S -1, key:Up, key:Down -1
+     *     axis look_x = mouse:x 0.5
+     *     action fire = button:left, gamepad:right_bumper
+     *     axis zoom = scroll:y
+     *     axis look_y = gamepad_axis:right_y 15
+     * A name that appears again adds to its bindings.
+     */
+    pub fn parse(source: &str) -> Result<Self, InputMapError> {
+        let mut input_map = InputMap::new();
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let error = |message: String| InputMapError {
+                line: line_number,
+                message,
+            };
+
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (declaration, bindings) = line
+                .split_once('=')
+                .ok_or_else(|| error(String::from("expected '='")))?;
+            let mut declaration = declaration.split_whitespace();
+            let (kind, name) = match (declaration.next(), declaration.next(), declaration.next()) {
+                (Some(kind), Some(name), None) => (kind, name),
+                _ => {
+                    return Err(error(String::from(
+                        "expected 'action <name>' or 'axis <name>'",
+                    )))
+                }
+            };
+
+            for binding in bindings.split(',') {
+                let mut parts = binding.split_whitespace();
+                let binding = match parts.next() {
+                    Some(binding) => parse_binding(binding).map_err(error)?,
+                    None => return Err(error(String::from("empty binding"))),
+                };
+                let scale = match parts.next() {
+                    Some(scale) => scale
+                        .parse::<f32>()
+                        .map_err(|_| error(format!("invalid scale '{}'", scale)))?,
+                    None => 1.0,
+                };
+                if let Some(extra) = parts.next() {
+                    return Err(error(format!("unexpected '{}'", extra)));
+                }
+
+                match kind {
+                    "action" if scale == 1.0 => input_map.bind_action(name, binding),
+                    "action" => return Err(error(String::from("actions take no scale"))),
+                    "axis" => input_map.bind_axis(name, binding, scale),
+                    _ => return Err(error(format!("unknown declaration '{}'", kind))),
+                }
+            }
+        }
+
+        Ok(input_map)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let source = std::fs::read_to_string(path)?;
+        InputMap::parse(&source).map_err(|error| format!("{}: {}", path.display(), error).into())
+    }
+
+    pub fn bind_action(self: &mut Self, name: &str, binding: Binding) {
+        self.actions
+            .entry(name.to_string())
+            .or_default()
+            .push(binding);
+    }
+
+    pub fn bind_axis(self: &mut Self, name: &str, binding: Binding, scale: f32) {
+        self.axes
+            .entry(name.to_string())
+            .or_default()
+            .push(AxisBinding { binding, scale });
+    }
+
+    // drops every binding of the action or axis so it can be rebound
+    pub fn unbind(self: &mut Self, name: &str) {
+        self.actions.remove(name);
+        self.axes.remove(name);
+        self.action_states.remove(name);
+        self.axis_values.remove(name);
+    }
+
+    pub fn action_bindings(self: &Self, name: &str) -> &[Binding] {
+        self.actions.get(name).map_or(&[], |bindings| bindings)
+    }
+
+    pub fn axis_bindings(self: &Self, name: &str) -> &[AxisBinding] {
+        self.axes.get(name).map_or(&[], |bindings| bindings)
+    }
+
+    // call once per frame after Graphics::check_events
+    pub fn update(self: &mut Self, input: &InputState, gamepads: &Gamepads) {
+        for (name, bindings) in &self.actions {
+            let down = bindings
+                .iter()
+                .any(|binding| binding_value(binding, input, gamepads) != 0.0);
+            let pressed = bindings
+                .iter()
+                .any(|binding| binding_edge(binding, input, InputState::just_pressed));
+            let released = bindings
+                .iter()
+                .any(|binding| binding_edge(binding, input, InputState::just_released));
+            let state = self.action_states.entry(name.clone()).or_default();
+            state.previous = state.down;
+            state.down = down;
+            state.pressed = pressed;
+            state.released = released;
+        }
+
+        for (name, bindings) in &self.axes {
+            let value: f32 = bindings
+                .iter()
+                .map(|axis| binding_value(&axis.binding, input, gamepads) * axis.scale)
+                .sum();
+            self.axis_values.insert(name.clone(), value);
+        }
+    }
+
+    pub fn action_down(self: &Self, name: &str) -> bool {
+        self.action_state(name).down
+    }
+
+    // true only on the frame the action went down, also when it went back up in the same frame
+    pub fn action_pressed(self: &Self, name: &str) -> bool {
+        let state = self.action_state(name);
+        (state.down && !state.previous) || state.pressed
+    }
+
+    pub fn action_released(self: &Self, name: &str) -> bool {
+        let state = self.action_state(name);
+        (!state.down && state.previous) || state.released
+    }
+
+    pub fn axis(self: &Self, name: &str) -> f32 {
+        self.axis_values.get(name).cloned().unwrap_or(0.0)
+    }
+
+    fn action_state(self: &Self, name: &str) -> ActionState {
+        self.action_states.get(name).cloned().unwrap_or_default()
+    }
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        let mut input_map = InputMap::new();
+        input_map.bind_action("quit", Binding::Key(Key::Escape));
+        input_map.bind_axis("move_forward", Binding::Key(Key::W), 1.0);
+        input_map.bind_axis("move_forward", Binding::Key(Key::Up), 1.0);
+        input_map.bind_axis("move_forward", Binding::Key(Key::S), -1.0);
+        input_map.bind_axis("move_forward", Binding::Key(Key::Down), -1.0);
+        input_map.bind_axis("move_right", Binding::Key(Key::D), 1.0);
+        input_map.bind_axis("move_right", Binding::Key(Key::Right), 1.0);
+        input_map.bind_axis("move_right", Binding::Key(Key::A), -1.0);
+        input_map.bind_axis("move_right", Binding::Key(Key::Left), -1.0);
+        input_map.bind_axis("move_up", Binding::Key(Key::E), 1.0);
+        input_map.bind_axis("move_up", Binding::Key(Key::Space), 1.0);
+        input_map.bind_axis("move_up", Binding::Key(Key::Q), -1.0);
+        input_map.bind_action("sprint", Binding::Key(Key::LeftShift));
+        input_map.bind_action("crouch", Binding::Key(Key::LeftControl));
+        input_map.bind_axis("look_x", Binding::MouseAxis(MouseAxis::X), 1.0);
+        input_map.bind_axis("look_y", Binding::MouseAxis(MouseAxis::Y), 1.0);
+        // stick Y is positive when pushed down
+        input_map.bind_axis(
+            "move_forward",
+            Binding::GamepadAxis(GamepadAxis::AxisLeftY),
+            -1.0,
+        );
+        input_map.bind_axis(
+            "move_right",
+            Binding::GamepadAxis(GamepadAxis::AxisLeftX),
+            1.0,
+        );
+        input_map.bind_axis(
+            "look_x",
+            Binding::GamepadAxis(GamepadAxis::AxisRightX),
+            GAMEPAD_LOOK_SCALE,
+        );
+        input_map.bind_axis(
+            "look_y",
+            Binding::GamepadAxis(GamepadAxis::AxisRightY),
+            GAMEPAD_LOOK_SCALE,
+        );
+        input_map.bind_axis(
+            "move_up",
+            Binding::GamepadButton(GamepadButton::ButtonRightBumper),
+            1.0,
+        );
+        input_map.bind_axis(
+            "move_up",
+            Binding::GamepadButton(GamepadButton::ButtonLeftBumper),
+            -1.0,
+        );
+        input_map.bind_action(
+            "sprint",
+            Binding::GamepadButton(GamepadButton::ButtonLeftThumb),
+        );
+        input_map.bind_action("crouch", Binding::GamepadButton(GamepadButton::ButtonB));
+        input_map.bind_action("quit", Binding::GamepadButton(GamepadButton::ButtonBack));
+        input_map
+    }
+}
+
+// gamepad bindings read whichever connected pad is furthest from rest
+fn binding_value(binding: &Binding, input: &InputState, gamepads: &Gamepads) -> f32 {
+    match binding {
+        Binding::Key(key) => pressed_value(input.is_down(*key)),
+        Binding::MouseButton(button) => pressed_value(input.is_down(*button)),
+        Binding::MouseAxis(_) if !input.cursor_captured() => 0.0,
+        Binding::MouseAxis(MouseAxis::X) => input.mouse_delta().0,
+        Binding::MouseAxis(MouseAxis::Y) => input.mouse_delta().1,
+        Binding::Scroll(MouseAxis::X) => input.scroll_delta().0,
+        Binding::Scroll(MouseAxis::Y) => input.scroll_delta().1,
+        Binding::GamepadButton(button) => pressed_value(gamepads.is_down(*button)),
+        Binding::GamepadAxis(axis) => gamepads.axis(*axis),
+    }
+}
+
+// just_pressed or just_released for keys and mouse buttons, gamepads are only polled
+fn binding_edge(
+    binding: &Binding,
+    input: &InputState,
+    edge: fn(&InputState, Button) -> bool,
+) -> bool {
+    match binding {
+        Binding::Key(key) => edge(input, (*key).into()),
+        Binding::MouseButton(button) => edge(input, (*button).into()),
+        _ => false,
+    }
+}
+
+fn pressed_value(down: bool) -> f32 {
+    if down {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+fn parse_binding(binding: &str) -> Result<Binding, String> {
+    let (device, name) = binding
+        .split_once(':')
+        .ok_or_else(|| format!("expected '<device>:<name>' in '{}'", binding))?;
+    let name = name.to_lowercase();
+
+    let parsed = match device {
+        "key" => key_from_name(&name).map(Binding::Key),
+        "button" => mouse_button_from_name(&name).map(Binding::MouseButton),
+        "mouse" => mouse_axis_from_name(&name).map(Binding::MouseAxis),
+        "scroll" => mouse_axis_from_name(&name).map(Binding::Scroll),
+        "gamepad" => gamepad_button_from_name(&name).map(Binding::GamepadButton),
+        "gamepad_axis" => gamepad_axis_from_name(&name).map(Binding::GamepadAxis),
+        _ => return Err(format!("unknown input device '{}'", device)),
+    };
+
+    parsed.ok_or_else(|| format!("unknown {} '{}'", device, name))
+}
+
+fn mouse_axis_from_name(name: &str) -> Option<MouseAxis> {
+    match name {
+        "x" => Some(MouseAxis::X),
+        "y" => Some(MouseAxis::Y),
+        _ => None,
+    }
+}
+
+fn mouse_button_from_name(name: &str) -> Option<MouseButton> {
+    match name {
+        "left" => Some(MouseButton::Button1),
+        "right" => Some(MouseButton::Button2),
+        "middle" => Some(MouseButton::Button3),
+        _ => name
+            .parse::<i32>()
+            .ok()
+            .and_then(|number| MouseButton::from_i32(number - 1)),
+    }
+}
+
+fn gamepad_button_from_name(name: &str) -> Option<GamepadButton> {
+    let button = match name {
+        "a" => GamepadButton::ButtonA,
+        "b" => GamepadButton::ButtonB,
+        "x" => GamepadButton::ButtonX,
+        "y" => GamepadButton::ButtonY,
+        "left_bumper" => GamepadButton::ButtonLeftBumper,
+        "right_bumper" => GamepadButton::ButtonRightBumper,
+        "back" => GamepadButton::ButtonBack,
+        "start" => GamepadButton::ButtonStart,
+        "guide" => GamepadButton::ButtonGuide,
+        "left_thumb" => GamepadButton::ButtonLeftThumb,
+        "right_thumb" => GamepadButton::ButtonRightThumb,
+        "dpad_up" => GamepadButton::ButtonDpadUp,
+        "dpad_right" => GamepadButton::ButtonDpadRight,
+        "dpad_down" => GamepadButton::ButtonDpadDown,
+        "dpad_left" => GamepadButton::ButtonDpadLeft,
+        _ => return None,
+    };
+    Some(button)
+}
+
+fn gamepad_axis_from_name(name: &str) -> Option<GamepadAxis> {
+    let axis = match name {
+        "left_x" => GamepadAxis::AxisLeftX,
+        "left_y" => GamepadAxis::AxisLeftY,
+        "right_x" => GamepadAxis::AxisRightX,
+        "right_y" => GamepadAxis::AxisRightY,
+        "left_trigger" => GamepadAxis::AxisLeftTrigger,
+        "right_trigger" => GamepadAxis::AxisRightTrigger,
+        _ => return None,
+    };
+    Some(axis)
+}
+
+// names match glfw::Key variants, case insensitive
+pub fn key_from_name(name: &str) -> Option<Key> {
+    let name = name.to_lowercase();
+    let alias = match name.as_str() {
+        "0" | "1" | "2" | "3" | "4" | "5" | "6" | "7" | "8" | "9" => {
+            return key_from_name(&format!("num{}", name))
+        }
+        "esc" => Some(Key::Escape),
+        "return" => Some(Key::Enter),
+        "shift" => Some(Key::LeftShift),
+        "control" | "ctrl" => Some(Key::LeftControl),
+        "alt" => Some(Key::LeftAlt),
+        _ => None,
+    };
+
+    alias.or_else(|| {
+        KEY_NAMES
+            .iter()
+            .find(|(key_name, _)| *key_name == name)
+            .map(|(_, key)| *key)
+    })
+}
+
+// inverse of `key as i32`, Key::Unknown for codes GLFW does not name
+pub fn key_from_code(code: i32) -> Key {
+    KEY_NAMES
+        .iter()
+        .find(|(_, key)| *key as i32 == code)
+        .map_or(Key::Unknown, |(_, key)| *key)
+}
+
+const KEY_NAMES: [(&str, Key); 120] = [
+    ("space", Key::Space),
+    ("apostrophe", Key::Apostrophe),
+    ("comma", Key::Comma),
+    ("minus", Key::Minus),
+    ("period", Key::Period),
+    ("slash", Key::Slash),
+    ("num0", Key::Num0),
+    ("num1", Key::Num1),
+    ("num2", Key::Num2),
+    ("num3", Key::Num3),
+    ("num4", Key::Num4),
+    ("num5", Key::Num5),
+    ("num6", Key::Num6),
+    ("num7", Key::Num7),
+    ("num8", Key::Num8),
+    ("num9", Key::Num9),
+    ("semicolon", Key::Semicolon),
+    ("equal", Key::Equal),
+    ("a", Key::A),
+    ("b", Key::B),
+    ("c", Key::C),
+    ("d", Key::D),
+    ("e", Key::E),
+    ("f", Key::F),
+    ("g", Key::G),
+    ("h", Key::H),
+    ("i", Key::I),
+    ("j", Key::J),
+    ("k", Key::K),
+    ("l", Key::L),
+    ("m", Key::M),
+    ("n", Key::N),
+    ("o", Key::O),
+    ("p", Key::P),
+    ("q", Key::Q),
+    ("r", Key::R),
+    ("s", Key::S),
+    ("t", Key::T),
+    ("u", Key::U),
+    ("v", Key::V),
+    ("w", Key::W),
+    ("x", Key::X),
+    ("y", Key::Y),
+    ("z", Key::Z),
+    ("leftbracket", Key::LeftBracket),
+    ("backslash", Key::Backslash),
+    ("rightbracket", Key::RightBracket),
+    ("graveaccent", Key::GraveAccent),
+    ("world1", Key::World1),
+    ("world2", Key::World2),
+    ("escape", Key::Escape),
+    ("enter", Key::Enter),
+    ("tab", Key::Tab),
+    ("backspace", Key::Backspace),
+    ("insert", Key::Insert),
+    ("delete", Key::Delete),
+    ("right", Key::Right),
+    ("left", Key::Left),
+    ("down", Key::Down),
+    ("up", Key::Up),
+    ("pageup", Key::PageUp),
+    ("pagedown", Key::PageDown),
+    ("home", Key::Home),
+    ("end", Key::End),
+    ("capslock", Key::CapsLock),
+    ("scrolllock", Key::ScrollLock),
+    ("numlock", Key::NumLock),
+    ("printscreen", Key::PrintScreen),
+    ("pause", Key::Pause),
+    ("f1", Key::F1),
+    ("f2", Key::F2),
+    ("f3", Key::F3),
+    ("f4", Key::F4),
+    ("f5", Key::F5),
+    ("f6", Key::F6),
+    ("f7", Key::F7),
+    ("f8", Key::F8),
+    ("f9", Key::F9),
+    ("f10", Key::F10),
+    ("f11", Key::F11),
+    ("f12", Key::F12),
+    ("f13", Key::F13),
+    ("f14", Key::F14),
+    ("f15", Key::F15),
+    ("f16", Key::F16),
+    ("f17", Key::F17),
+    ("f18", Key::F18),
+    ("f19", Key::F19),
+    ("f20", Key::F20),
+    ("f21", Key::F21),
+    ("f22", Key::F22),
+    ("f23", Key::F23),
+    ("f24", Key::F24),
+    ("f25", Key::F25),
+    ("kp0", Key::Kp0),
+    ("kp1", Key::Kp1),
+    ("kp2", Key::Kp2),
+    ("kp3", Key::Kp3),
+    ("kp4", Key::Kp4),
+    ("kp5", Key::Kp5),
+    ("kp6", Key::Kp6),
+    ("kp7", Key::Kp7),
+    ("kp8", Key::Kp8),
+    ("kp9", Key::Kp9),
+    ("kpdecimal", Key::KpDecimal),
+    ("kpdivide", Key::KpDivide),
+    ("kpmultiply", Key::KpMultiply),
+    ("kpsubtract", Key::KpSubtract),
+    ("kpadd", Key::KpAdd),
+    ("kpenter", Key::KpEnter),
+    ("kpequal", Key::KpEqual),
+    ("leftshift", Key::LeftShift),
+    ("leftcontrol", Key::LeftControl),
+    ("leftalt", Key::LeftAlt),
+    ("leftsuper", Key::LeftSuper),
+    ("rightshift", Key::RightShift),
+    ("rightcontrol", Key::RightControl),
+    ("rightalt", Key::RightAlt),
+    ("rightsuper", Key::RightSuper),
+    ("menu", Key::Menu),
+];
+
+#[cfg(test)]
+mod tests {
+    use glfw::{Action, Modifiers, WindowEvent};
+
+    use super::*;
+
+    fn parse_error(source: &str) -> InputMapError {
+        InputMap::parse(source).unwrap_err()
+    }
+
+    fn press(input: &mut InputState, key: Key) {
+        input.handle_event(&WindowEvent::Key(key, 0, Action::Press, Modifiers::empty()));
+    }
+
+    fn release(input: &mut InputState, key: Key) {
+        input.handle_event(&WindowEvent::Key(
+            key,
+            0,
+            Action::Release,
+            Modifiers::empty(),
+        ));
+    }
+
+    #[test]
+    fn parses_actions_and_axes_with_scales() {
+        let input_map = InputMap::parse(
+            "action quit = key:Escape, gamepad:back\n\
+             axis move_forward = key:W, key:S -1, gamepad_axis:left_y -1\n\
+             axis look_x = mouse:x 0.5\n\
+             axis zoom = scroll:y\n\
+             action fire = button:left, button:4\n\
+             action quit = key:q\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            input_map.action_bindings("quit"),
+            &[
+                Binding::Key(Key::Escape),
+                Binding::GamepadButton(GamepadButton::ButtonBack),
+                Binding::Key(Key::Q),
+            ]
+        );
+        assert_eq!(
+            input_map.axis_bindings("move_forward"),
+            &[
+                AxisBinding {
+                    binding: Binding::Key(Key::W),
+                    scale: 1.0
+                },
+                AxisBinding {
+                    binding: Binding::Key(Key::S),
+                    scale: -1.0
+                },
+                AxisBinding {
+                    binding: Binding::GamepadAxis(GamepadAxis::AxisLeftY),
+                    scale: -1.0
+                },
+            ]
+        );
+        assert_eq!(input_map.axis_bindings("look_x")[0].scale, 0.5);
+        assert_eq!(
+            input_map.axis_bindings("zoom")[0].binding,
+            Binding::Scroll(MouseAxis::Y)
+        );
+        assert_eq!(
+            input_map.action_bindings("fire"),
+            &[
+                Binding::MouseButton(MouseButton::Button1),
+                Binding::MouseButton(MouseButton::Button4),
+            ]
+        );
+        assert!(input_map.action_bindings("missing").is_empty());
+    }
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        let input_map = InputMap::parse(
+            "# a comment\n\
+             \n   \n\
+             action quit = key:Escape # trailing comment\n",
+        )
+        .unwrap();
+        assert_eq!(
+            input_map.action_bindings("quit"),
+            &[Binding::Key(Key::Escape)]
+        );
+    }
+
+    #[test]
+    fn reports_unknown_names_with_their_line() {
+        let error = parse_error("action quit = key:Escape\naction jump = key:Nope\n");
+        assert_eq!(error.line, 2);
+        assert_eq!(error.message, "unknown key 'nope'");
+
+        assert_eq!(
+            parse_error("action jump = joystick:1").message,
+            "unknown input device 'joystick'"
+        );
+        assert_eq!(
+            parse_error("action jump = gamepad:z").message,
+            "unknown gamepad 'z'"
+        );
+        assert_eq!(
+            parse_error("binding jump = key:Space").message,
+            "unknown declaration 'binding'"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        for (source, message) in [
+            ("action quit key:Escape", "expected '='"),
+            (
+                "action = key:Escape",
+                "expected 'action <name>' or 'axis <name>'",
+            ),
+            (
+                "action quit now = key:Escape",
+                "expected 'action <name>' or 'axis <name>'",
+            ),
+            ("action quit = key:Escape,", "empty binding"),
+            (
+                "action quit = Escape",
+                "expected '<device>:<name>' in 'Escape'",
+            ),
+            ("axis zoom = scroll:y fast", "invalid scale 'fast'"),
+            ("axis zoom = scroll:y 1 2", "unexpected '2'"),
+            ("action quit = key:Escape 2", "actions take no scale"),
+        ] {
+            let error = parse_error(source);
+            assert_eq!(error.line, 1, "{}", source);
+            assert_eq!(error.message, message, "{}", source);
+        }
+    }
+
+    #[test]
+    fn the_demo_bindings_parse() {
+        let input_map = InputMap::load(Path::new("../resources/input.cfg")).unwrap();
+        assert!(!input_map.action_bindings("quit").is_empty());
+        assert!(!input_map.axis_bindings("move_forward").is_empty());
+    }
+
+    #[test]
+    fn update_sums_axes_and_tracks_presses() {
+        let mut input_map =
+            InputMap::parse("axis move_forward = key:W, key:S -1\naction jump = key:Space")
+                .unwrap();
+        let gamepads = Gamepads::headless();
+        let mut input = InputState::new();
+
+        press(&mut input, Key::W);
+        press(&mut input, Key::Space);
+        input_map.update(&input, &gamepads);
+        assert_eq!(input_map.axis("move_forward"), 1.0);
+        assert!(input_map.action_pressed("jump"));
+
+        input.begin_frame();
+        press(&mut input, Key::S);
+        input_map.update(&input, &gamepads);
+        assert_eq!(input_map.axis("move_forward"), 0.0);
+        assert!(input_map.action_down("jump"));
+        assert!(!input_map.action_pressed("jump"));
+    }
+
+    #[test]
+    fn a_press_and_release_in_one_frame_is_seen() {
+        let mut input_map =
+            InputMap::parse("action jump = key:Space\naction fire = button:left").unwrap();
+        let gamepads = Gamepads::headless();
+        let mut input = InputState::new();
+
+        press(&mut input, Key::Space);
+        release(&mut input, Key::Space);
+        input.handle_event(&WindowEvent::MouseButton(
+            MouseButton::Button1,
+            Action::Press,
+            Modifiers::empty(),
+        ));
+        input.handle_event(&WindowEvent::MouseButton(
+            MouseButton::Button1,
+            Action::Release,
+            Modifiers::empty(),
+        ));
+        input_map.update(&input, &gamepads);
+        for action in ["jump", "fire"] {
+            assert!(!input_map.action_down(action), "{}", action);
+            assert!(input_map.action_pressed(action), "{}", action);
+            assert!(input_map.action_released(action), "{}", action);
+        }
+
+        input.begin_frame();
+        input_map.update(&input, &gamepads);
+        assert!(!input_map.action_pressed("jump"));
+        assert!(!input_map.action_released("jump"));
+    }
+}
@@ -0,0 +1,398 @@
+use std::{
+    collections::VecDeque,
+    error::Error,
+    fs::File,
+    io::{BufWriter, Read, Write},
+    path::Path,
+};
+
+use glfw::{Action, JoystickId, Modifiers, MouseButton, WindowEvent};
+
+use crate::gamepad::{GamepadSnapshot, Gamepads, AXIS_COUNT};
+use crate::input_map::key_from_code;
+use crate::input_state::InputState;
+
+const MAGIC: &[u8; 4] = b"WMEI";
+const VERSION: u16 = 1;
+
+const EVENT_KEY: u8 = 1;
+const EVENT_MOUSE_BUTTON: u8 = 2;
+const EVENT_CURSOR_POS: u8 = 3;
+const EVENT_SCROLL: u8 = 4;
+const EVENT_FOCUS: u8 = 5;
+
+// everything the app saw as input during one frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputFrame {
+    pub delta_time: f32,
+    pub events: Vec<WindowEvent>,
+    pub gamepads: Vec<GamepadSnapshot>,
+}
+
+/*
+ * Writes each frame's delta time, input events and gamepad state to a
+ * compact little endian file for InputReplay. Only the events InputState
+ * reacts to are kept: keys, mouse buttons, cursor, scroll and focus.
+ * Cursor and scroll positions are stored as the f32 InputState uses.
+ */
+pub struct InputRecorder {
+    pub frames: u32,
+    writer: BufWriter<File>,
+}
+
+impl InputRecorder {
+    pub fn new(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+
+        Ok(InputRecorder { frames: 0, writer })
+    }
+
+    // call after Graphics::check_events with the delta the frame was simulated with
+    pub fn record_frame(
+        self: &mut Self,
+        delta_time: f32,
+        input: &InputState,
+        gamepads: &Gamepads,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut bytes: Vec<u8> = Vec::new();
+        bytes.extend_from_slice(&delta_time.to_le_bytes());
+
+        let events: Vec<&WindowEvent> = input
+            .events()
+            .iter()
+            .filter(|event| is_recorded(event))
+            .collect();
+        bytes.extend_from_slice(&(events.len() as u16).to_le_bytes());
+        for event in events {
+            encode_event(&mut bytes, event);
+        }
+
+        bytes.push(gamepads.pads().len() as u8);
+        for pad in gamepads.pads() {
+            let snapshot = pad.snapshot();
+            bytes.push(snapshot.id as u8);
+            bytes.extend_from_slice(&snapshot.buttons.to_le_bytes());
+            for axis in snapshot.axes {
+                bytes.extend_from_slice(&axis.to_le_bytes());
+            }
+        }
+
+        self.writer.write_all(&bytes)?;
+        self.frames += 1;
+        Ok(())
+    }
+
+    pub fn finish(self: &mut Self) -> Result<(), Box<dyn Error>> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/*
+ * Frames read back from an InputRecorder file. Runner takes one per
+ * frame, overriding the delta time and feeding the events through
+ * Graphics::replay_events in place of GLFW.
+ */
+pub struct InputReplay {
+    pub frame: u32,
+    frames: VecDeque<InputFrame>,
+}
+
+impl InputReplay {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let bytes = std::fs::read(path)?;
+        InputReplay::from_bytes(&bytes)
+            .map_err(|error| format!("{}: {}", path.display(), error).into())
+    }
+
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let reader = &mut bytes;
+        let mut magic = [0_u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err("Not an input recording".into());
+        }
+        let version = read_u16(reader)?;
+        if version != VERSION {
+            return Err(format!("Unsupported input recording version {}", version).into());
+        }
+
+        let mut frames: VecDeque<InputFrame> = VecDeque::new();
+        while !reader.is_empty() {
+            frames.push_back(decode_frame(reader)?);
+        }
+
+        Ok(InputReplay { frame: 0, frames })
+    }
+
+    // None once every recorded frame has been replayed
+    pub fn next_frame(self: &mut Self) -> Option<InputFrame> {
+        let frame = self.frames.pop_front()?;
+        self.frame += 1;
+        Some(frame)
+    }
+
+    pub fn remaining(self: &Self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_finished(self: &Self) -> bool {
+        self.frames.is_empty()
+    }
+}
+
+fn is_recorded(event: &WindowEvent) -> bool {
+    matches!(
+        event,
+        WindowEvent::Key(..)
+            | WindowEvent::MouseButton(..)
+            | WindowEvent::CursorPos(..)
+            | WindowEvent::Scroll(..)
+            | WindowEvent::Focus(..)
+    )
+}
+
+fn encode_event(bytes: &mut Vec<u8>, event: &WindowEvent) {
+    match event {
+        WindowEvent::Key(key, scancode, action, modifiers) => {
+            bytes.push(EVENT_KEY);
+            bytes.extend_from_slice(&(*key as i16).to_le_bytes());
+            bytes.extend_from_slice(&scancode.to_le_bytes());
+            bytes.push(*action as u8);
+            bytes.push(modifiers.bits() as u8);
+        }
+        WindowEvent::MouseButton(button, action, modifiers) => {
+            bytes.push(EVENT_MOUSE_BUTTON);
+            bytes.push(*button as u8);
+            bytes.push(*action as u8);
+            bytes.push(modifiers.bits() as u8);
+        }
+        WindowEvent::CursorPos(x, y) => {
+            bytes.push(EVENT_CURSOR_POS);
+            bytes.extend_from_slice(&(*x as f32).to_le_bytes());
+            bytes.extend_from_slice(&(*y as f32).to_le_bytes());
+        }
+        WindowEvent::Scroll(x, y) => {
+            bytes.push(EVENT_SCROLL);
+            bytes.extend_from_slice(&(*x as f32).to_le_bytes());
+            bytes.extend_from_slice(&(*y as f32).to_le_bytes());
+        }
+        WindowEvent::Focus(focused) => {
+            bytes.push(EVENT_FOCUS);
+            bytes.push(*focused as u8);
+        }
+        _ => (),
+    }
+}
+
+fn decode_frame(reader: &mut &[u8]) -> Result<InputFrame, Box<dyn Error>> {
+    let delta_time = read_f32(reader)?;
+
+    let event_count = read_u16(reader)?;
+    let mut events: Vec<WindowEvent> = Vec::with_capacity(event_count as usize);
+    for _ in 0..event_count {
+        events.push(decode_event(reader)?);
+    }
+
+    let pad_count = read_u8(reader)?;
+    let mut gamepads: Vec<GamepadSnapshot> = Vec::with_capacity(pad_count as usize);
+    for _ in 0..pad_count {
+        let id = read_u8(reader)?;
+        let id =
+            JoystickId::from_i32(id as i32).ok_or_else(|| format!("Invalid joystick id {}", id))?;
+        let buttons = read_u16(reader)?;
+        let mut axes = [0.0; AXIS_COUNT];
+        for axis in axes.iter_mut() {
+            *axis = read_f32(reader)?;
+        }
+        gamepads.push(GamepadSnapshot { id, buttons, axes });
+    }
+
+    Ok(InputFrame {
+        delta_time,
+        events,
+        gamepads,
+    })
+}
+
+fn decode_event(reader: &mut &[u8]) -> Result<WindowEvent, Box<dyn Error>> {
+    let event = match read_u8(reader)? {
+        EVENT_KEY => {
+            let key = key_from_code(read_i16(reader)? as i32);
+            let scancode = read_i32(reader)?;
+            let action = decode_action(read_u8(reader)?)?;
+            let modifiers = Modifiers::from_bits_truncate(read_u8(reader)? as i32);
+            WindowEvent::Key(key, scancode, action, modifiers)
+        }
+        EVENT_MOUSE_BUTTON => {
+            let button = read_u8(reader)?;
+            let button = MouseButton::from_i32(button as i32)
+                .ok_or_else(|| format!("Invalid mouse button {}", button))?;
+            let action = decode_action(read_u8(reader)?)?;
+            let modifiers = Modifiers::from_bits_truncate(read_u8(reader)? as i32);
+            WindowEvent::MouseButton(button, action, modifiers)
+        }
+        EVENT_CURSOR_POS => {
+            WindowEvent::CursorPos(read_f32(reader)? as f64, read_f32(reader)? as f64)
+        }
+        EVENT_SCROLL => WindowEvent::Scroll(read_f32(reader)? as f64, read_f32(reader)? as f64),
+        EVENT_FOCUS => WindowEvent::Focus(read_u8(reader)? != 0),
+        tag => return Err(format!("Unknown input event tag {}", tag).into()),
+    };
+
+    Ok(event)
+}
+
+fn decode_action(action: u8) -> Result<Action, Box<dyn Error>> {
+    match action as i32 {
+        glfw::ffi::RELEASE => Ok(Action::Release),
+        glfw::ffi::PRESS => Ok(Action::Press),
+        glfw::ffi::REPEAT => Ok(Action::Repeat),
+        _ => Err(format!("Invalid key action {}", action).into()),
+    }
+}
+
+fn read_u8(reader: &mut &[u8]) -> Result<u8, Box<dyn Error>> {
+    let mut bytes = [0_u8; 1];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes[0])
+}
+
+fn read_u16(reader: &mut &[u8]) -> Result<u16, Box<dyn Error>> {
+    let mut bytes = [0_u8; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_i16(reader: &mut &[u8]) -> Result<i16, Box<dyn Error>> {
+    let mut bytes = [0_u8; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(i16::from_le_bytes(bytes))
+}
+
+fn read_i32(reader: &mut &[u8]) -> Result<i32, Box<dyn Error>> {
+    let mut bytes = [0_u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(i32::from_le_bytes(bytes))
+}
+
+fn read_f32(reader: &mut &[u8]) -> Result<f32, Box<dyn Error>> {
+    let mut bytes = [0_u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(f32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use glfw::{GamepadButton, Key};
+
+    use super::*;
+    use crate::input_map::key_from_name;
+
+    fn frame_events() -> Vec<WindowEvent> {
+        vec![
+            WindowEvent::Key(Key::W, 17, Action::Press, Modifiers::Shift),
+            WindowEvent::Key(Key::Kp5, 0, Action::Repeat, Modifiers::empty()),
+            WindowEvent::MouseButton(MouseButton::Button1, Action::Press, Modifiers::Control),
+            WindowEvent::CursorPos(10.0, 20.0),
+            WindowEvent::CursorPos(12.5, 16.0),
+            WindowEvent::Scroll(0.0, -1.0),
+            WindowEvent::Focus(true),
+        ]
+    }
+
+    #[test]
+    fn recordings_replay_the_same_input() {
+        let path = std::env::temp_dir().join(format!("wme-input-{}.rec", std::process::id()));
+
+        let mut input = InputState::new();
+        let mut gamepads = Gamepads::headless();
+        let pad = GamepadSnapshot {
+            id: JoystickId::Joystick2,
+            buttons: 1 << GamepadButton::ButtonA as u16,
+            axes: [0.5, -0.25, 0.0, 1.0, -1.0, 0.0],
+        };
+        gamepads.replay(&[pad]);
+
+        let mut recorder = InputRecorder::new(&path).unwrap();
+        for event in frame_events() {
+            input.handle_event(&event);
+        }
+        // events InputState ignores are not recorded
+        input.handle_event(&WindowEvent::Close);
+        recorder
+            .record_frame(1.0 / 60.0, &input, &gamepads)
+            .unwrap();
+        input.begin_frame();
+        recorder
+            .record_frame(1.0 / 30.0, &input, &gamepads)
+            .unwrap();
+        recorder.finish().unwrap();
+        assert_eq!(recorder.frames, 2);
+
+        let mut replay = InputReplay::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(replay.remaining(), 2);
+
+        let first = replay.next_frame().unwrap();
+        assert_eq!(
+            first,
+            InputFrame {
+                delta_time: 1.0 / 60.0,
+                events: frame_events(),
+                gamepads: vec![pad],
+            }
+        );
+        let second = replay.next_frame().unwrap();
+        assert_eq!(second.delta_time, 1.0 / 30.0);
+        assert!(second.events.is_empty());
+        assert!(replay.is_finished());
+        assert_eq!(replay.next_frame(), None);
+
+        // feeding the frame back gives the state the recording saw
+        let mut replayed = InputState::new();
+        for event in &first.events {
+            replayed.handle_event(event);
+        }
+        let mut replayed_pads = Gamepads::headless();
+        replayed_pads.replay(&first.gamepads);
+        assert!(replayed.is_down(Key::W));
+        assert!(replayed.is_down(MouseButton::Button1));
+        assert_eq!(replayed.mouse_delta(), (2.5, -4.0));
+        assert_eq!(replayed.scroll_delta(), (0.0, -1.0));
+        assert!(replayed_pads.is_down(GamepadButton::ButtonA));
+        assert_eq!(replayed_pads.axis(glfw::GamepadAxis::AxisLeftX), 0.5);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(InputReplay::from_bytes(b"PNG\x00\x01\x00").is_err());
+        assert!(InputReplay::from_bytes(b"WMEI\x02\x00").is_err());
+        // a frame cut off in the middle
+        assert!(InputReplay::from_bytes(b"WMEI\x01\x00\x00\x00").is_err());
+        assert_eq!(
+            InputReplay::from_bytes(b"WMEI\x01\x00")
+                .unwrap()
+                .remaining(),
+            0
+        );
+    }
+
+    #[test]
+    fn key_codes_and_names_round_trip() {
+        for name in ["w", "escape", "num1", "f12", "kp5", "leftshift", "menu"] {
+            let key = key_from_name(name).unwrap();
+            assert_eq!(key_from_code(key as i32), key, "{}", name);
+        }
+        assert_eq!(key_from_name("1"), Some(Key::Num1));
+        assert_eq!(key_from_name("Esc"), Some(Key::Escape));
+        assert_eq!(key_from_name("ctrl"), Some(Key::LeftControl));
+        assert_eq!(key_from_name("F12"), Some(Key::F12));
+        assert_eq!(key_from_name("kp5"), Some(Key::Kp5));
+        assert_eq!(key_from_name("nope"), None);
+        assert_eq!(key_from_code(-1), Key::Unknown);
+        assert_eq!(key_from_code(Key::Menu as i32), Key::Menu);
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashSet;
+
+use glfw::{Action, Key, MouseButton, WindowEvent};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Button {
+    Key(Key),
+    Mouse(MouseButton),
+}
+
+impl From<Key> for Button {
+    fn from(key: Key) -> Self {
+        Button::Key(key)
+    }
+}
+
+impl From<MouseButton> for Button {
+    fn from(button: MouseButton) -> Self {
+        Button::Mouse(button)
+    }
+}
+
+/*
+ * Keyboard and mouse state for the current frame, built from every
+ * WindowEvent Graphics::check_events drains. Read it from as many
+ * places as needed, nothing else should flush graphics.events.
+ * Key repeats do not count as presses.
+ */
+#[derive(Debug, Clone)]
+pub struct InputState {
+    down: HashSet<Button>,
+    pressed: HashSet<Button>,
+    released: HashSet<Button>,
+    events: Vec<WindowEvent>,
+    first_cursor: bool,
+    cursor_captured: bool,
+    cursor: (f32, f32),
+    mouse_delta: (f32, f32),
+    scroll_delta: (f32, f32),
+    focused: bool,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        InputState {
+            down: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+            events: Vec::new(),
+            first_cursor: true,
+            cursor_captured: false,
+            cursor: (0.0, 0.0),
+            mouse_delta: (0.0, 0.0),
+            scroll_delta: (0.0, 0.0),
+            focused: true,
+        }
+    }
+
+    // clears the per-frame transitions and deltas, held buttons stay down
+    pub fn begin_frame(self: &mut Self) {
+        self.pressed.clear();
+        self.released.clear();
+        self.events.clear();
+        self.mouse_delta = (0.0, 0.0);
+        self.scroll_delta = (0.0, 0.0);
+    }
+
+    pub fn handle_event(self: &mut Self, event: &WindowEvent) {
+        match event {
+            WindowEvent::Key(key, _, action, _) => self.set_button(Button::Key(*key), *action),
+            WindowEvent::MouseButton(button, action, _) => {
+                self.set_button(Button::Mouse(*button), *action)
+            }
+            WindowEvent::CursorPos(x, y) => {
+                let (x, y) = (*x as f32, *y as f32);
+                // the first position has nothing to be relative to
+                if !self.first_cursor {
+                    self.mouse_delta.0 += x - self.cursor.0;
+                    self.mouse_delta.1 += y - self.cursor.1;
+                }
+                self.first_cursor = false;
+                self.cursor = (x, y);
+            }
+            WindowEvent::Scroll(x, y) => {
+                self.scroll_delta.0 += *x as f32;
+                self.scroll_delta.1 += *y as f32;
+            }
+            WindowEvent::Focus(focused) => {
+                self.focused = *focused;
+                // the cursor moved outside the window in the meantime
+                self.first_cursor = true;
+                // releases go to the other window, don't leave buttons stuck down
+                if !focused {
+                    self.released.extend(self.down.drain());
+                }
+            }
+            _ => (),
+        }
+        self.events.push(event.clone());
+    }
+
+    pub fn is_down<B: Into<Button>>(self: &Self, button: B) -> bool {
+        self.down.contains(&button.into())
+    }
+
+    pub fn just_pressed<B: Into<Button>>(self: &Self, button: B) -> bool {
+        self.pressed.contains(&button.into())
+    }
+
+    pub fn just_released<B: Into<Button>>(self: &Self, button: B) -> bool {
+        self.released.contains(&button.into())
+    }
+
+    // cursor movement in screen coordinates summed over the frame
+    pub fn mouse_delta(self: &Self) -> (f32, f32) {
+        self.mouse_delta
+    }
+
+    pub fn scroll_delta(self: &Self) -> (f32, f32) {
+        self.scroll_delta
+    }
+
+    pub fn cursor_position(self: &Self) -> (f32, f32) {
+        self.cursor
+    }
+
+    // the next cursor position starts over instead of producing a jump in mouse_delta
+    pub fn reset_cursor(self: &mut Self) {
+        self.first_cursor = true;
+    }
+
+    // kept in sync by Graphics::set_cursor_mode
+    pub fn cursor_captured(self: &Self) -> bool {
+        self.cursor_captured
+    }
+
+    pub fn set_cursor_captured(self: &mut Self, captured: bool) {
+        self.cursor_captured = captured;
+        self.first_cursor = true;
+    }
+
+    pub fn is_focused(self: &Self) -> bool {
+        self.focused
+    }
+
+    // every event received this frame, in order
+    pub fn events(self: &Self) -> &[WindowEvent] {
+        &self.events
+    }
+
+    fn set_button(self: &mut Self, button: Button, action: Action) {
+        match action {
+            Action::Press => {
+                if self.down.insert(button) {
+                    self.pressed.insert(button);
+                }
+            }
+            Action::Release => {
+                if self.down.remove(&button) {
+                    self.released.insert(button);
+                }
+            }
+            Action::Repeat => (),
+        }
+    }
+}
+
+impl Default for InputState {
+    fn default() -> Self {
+        InputState::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glfw::Modifiers;
+
+    use super::*;
+
+    fn key(input: &mut InputState, key: Key, action: Action) {
+        input.handle_event(&WindowEvent::Key(key, 0, action, Modifiers::empty()));
+    }
+
+    fn mouse(input: &mut InputState, button: MouseButton, action: Action) {
+        input.handle_event(&WindowEvent::MouseButton(
+            button,
+            action,
+            Modifiers::empty(),
+        ));
+    }
+
+    #[test]
+    fn presses_and_releases_last_one_frame() {
+        let mut input = InputState::new();
+        key(&mut input, Key::W, Action::Press);
+        assert!(input.is_down(Key::W));
+        assert!(input.just_pressed(Key::W));
+        assert!(!input.just_released(Key::W));
+
+        input.begin_frame();
+        assert!(input.is_down(Key::W));
+        assert!(!input.just_pressed(Key::W));
+
+        key(&mut input, Key::W, Action::Release);
+        assert!(!input.is_down(Key::W));
+        assert!(input.just_released(Key::W));
+
+        input.begin_frame();
+        assert!(!input.just_released(Key::W));
+        assert!(input.events().is_empty());
+    }
+
+    #[test]
+    fn key_repeats_are_not_presses() {
+        let mut input = InputState::new();
+        key(&mut input, Key::A, Action::Press);
+        input.begin_frame();
+        key(&mut input, Key::A, Action::Repeat);
+        assert!(input.is_down(Key::A));
+        assert!(!input.just_pressed(Key::A));
+        // the repeat is still in the event list for text input and the like
+        assert_eq!(input.events().len(), 1);
+    }
+
+    #[test]
+    fn the_first_cursor_position_has_no_delta() {
+        let mut input = InputState::new();
+        input.handle_event(&WindowEvent::CursorPos(100.0, 50.0));
+        assert_eq!(input.mouse_delta(), (0.0, 0.0));
+        assert_eq!(input.cursor_position(), (100.0, 50.0));
+
+        input.handle_event(&WindowEvent::CursorPos(110.0, 45.0));
+        input.handle_event(&WindowEvent::CursorPos(115.0, 40.0));
+        assert_eq!(input.mouse_delta(), (15.0, -10.0));
+
+        input.begin_frame();
+        assert_eq!(input.mouse_delta(), (0.0, 0.0));
+        input.reset_cursor();
+        input.handle_event(&WindowEvent::CursorPos(0.0, 0.0));
+        assert_eq!(input.mouse_delta(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn losing_focus_releases_held_buttons() {
+        let mut input = InputState::new();
+        key(&mut input, Key::LeftShift, Action::Press);
+        mouse(&mut input, MouseButton::Button1, Action::Press);
+        input.begin_frame();
+
+        input.handle_event(&WindowEvent::Focus(false));
+        assert!(!input.is_focused());
+        assert!(!input.is_down(Key::LeftShift));
+        assert!(!input.is_down(MouseButton::Button1));
+        assert!(input.just_released(Key::LeftShift));
+        assert!(input.just_released(MouseButton::Button1));
+
+        // the release arrives after focus is already gone
+        key(&mut input, Key::LeftShift, Action::Release);
+        input.handle_event(&WindowEvent::Focus(true));
+        assert!(input.is_focused());
+        assert!(!input.is_down(Key::LeftShift));
+    }
+}
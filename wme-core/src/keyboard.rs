@@ -1,23 +1,48 @@
 extern crate glfw;
+extern crate nalgebra_glm as glm;
 
-use glfw::{Action, Key};
+use crate::camera::Camera;
+use crate::graphics::Graphics;
+use crate::input_map::InputMap;
 
-use crate::camera::{Camera, MovementType};
+// movement speed multipliers while "sprint" or "crouch" is held
+pub const SPRINT_SCALE: f32 = 2.0;
+pub const CROUCH_SCALE: f32 = 0.5;
 
-pub fn process_inputs(window: &mut glfw::Window, camera: &mut Camera, delta: f32) {
-    if window.get_key(Key::Escape) == Action::Press {
-        window.set_should_close(true);
+/*
+ * Drives the camera from the "quit", "move_forward", "move_right",
+ * "move_up", "sprint", "crouch", "look_x" and "look_y" actions.
+ * InputMap::update must have run this frame. Movement is smoothed by
+ * Camera::move_camera and follows the camera's movement_mode.
+ */
+pub fn process_inputs(
+    graphics: &mut Graphics,
+    input_map: &InputMap,
+    camera: &mut Camera,
+    delta: f32,
+) {
+    if input_map.action_pressed("quit") {
+        graphics.set_should_close(true);
     }
-    if window.get_key(Key::Up) == Action::Press {
-        camera.dolly_camera(MovementType::FORWARD, delta);
-    }
-    if window.get_key(Key::Down) == Action::Press {
-        camera.dolly_camera(MovementType::BACKWARD, delta);
+
+    let movement = glm::Vec3::new(
+        input_map.axis("move_right"),
+        input_map.axis("move_up"),
+        input_map.axis("move_forward"),
+    )
+    .map(|value| value.clamp(-1.0, 1.0));
+
+    let mut speed_scale: f32 = 1.0;
+    if input_map.action_down("sprint") {
+        speed_scale *= SPRINT_SCALE;
     }
-    if window.get_key(Key::Left) == Action::Press {
-        camera.pan_camera(MovementType::LEFT, delta);
+    if input_map.action_down("crouch") {
+        speed_scale *= CROUCH_SCALE;
     }
-    if window.get_key(Key::Right) == Action::Press {
-        camera.pan_camera(MovementType::RIGHT, delta);
+    camera.move_camera(movement, speed_scale, delta);
+
+    let (look_x, look_y) = (input_map.axis("look_x"), input_map.axis("look_y"));
+    if look_x != 0.0 || look_y != 0.0 {
+        camera.fps_rotate_camera(look_x, look_y, delta);
     }
 }
@@ -1,8 +1,31 @@
+pub mod app;
+pub mod bounds;
 pub mod camera;
+pub mod camera_path;
+pub mod camera_view;
+pub mod capture;
 pub mod constants;
+pub mod debug;
+pub mod frame_stats;
+pub mod framebuffer;
+pub mod frustum;
+pub mod gamepad;
+pub mod gl_device;
+pub mod gpu_timer;
 pub mod graphics;
+pub mod graphics_config;
+pub mod headless;
+pub mod input_map;
+pub mod input_recording;
+pub mod input_state;
 pub mod keyboard;
 pub mod mouse;
+pub mod orbit_controller;
+pub mod preprocessor;
+pub mod ray;
+pub mod recording_device;
+pub mod render_device;
 pub mod shader;
+pub mod state_cache;
 pub mod texture;
 pub mod vertex;
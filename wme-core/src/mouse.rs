@@ -1,46 +1,63 @@
-use crate::camera::Camera;
+use glfw::WindowEvent;
 
+use crate::input_state::InputState;
+
+/*
+ * The mouse half of InputState, kept for code written before it.
+ * process_mouse flushes the window's events itself, so it can't be
+ * combined with Graphics::check_events, which drains the same queue.
+ */
+#[deprecated(note = "read mouse_delta and scroll_delta from Graphics::input instead")]
 pub struct Mouse {
     pub first_mouse: bool,
     pub last_x: f32,
     pub last_y: f32,
+    pub delta_x: f32,
+    pub delta_y: f32,
+    pub scroll_x: f32,
+    pub scroll_y: f32,
+    input: InputState,
 }
 
+#[allow(deprecated)]
 impl Mouse {
     pub fn new(x: f32, y: f32) -> Self {
         Mouse {
             first_mouse: true,
             last_x: x,
             last_y: y,
+            delta_x: 0.0,
+            delta_y: 0.0,
+            scroll_x: 0.0,
+            scroll_y: 0.0,
+            input: InputState::new(),
         }
     }
 
-    pub fn process_mouse(
-        self: &mut Self,
-        events: &glfw::GlfwReceiver<(f64, glfw::WindowEvent)>,
-        camera: &mut Camera,
-        delta: f32,
-    ) {
-        for (_, event) in glfw::flush_messages(&events) {
-            match event {
-                glfw::WindowEvent::CursorPos(xpos, ypos) => {
-                    let (xpos, ypos) = (xpos as f32, ypos as f32);
-                    if self.first_mouse {
-                        self.first_mouse = false;
-                        self.last_x = xpos;
-                        self.last_y = ypos;
-                    }
-
-                    let x_offset: f32 = xpos - self.last_x;
-                    let y_offset: f32 = ypos - self.last_y;
-
-                    self.last_x = xpos;
-                    self.last_y = ypos;
+    // sums this frame's cursor movement and scrolling
+    pub fn process_mouse(self: &mut Self, events: &glfw::GlfwReceiver<(f64, WindowEvent)>) {
+        // start from the public fields, callers may have moved the cursor themselves
+        self.input.handle_event(&WindowEvent::CursorPos(
+            self.last_x as f64,
+            self.last_y as f64,
+        ));
+        self.input.begin_frame();
+        if self.first_mouse {
+            self.input.reset_cursor();
+        }
 
-                    camera.fps_rotate_camera(x_offset, y_offset, delta);
-                }
-                _ => (),
-            }
+        for (_, event) in glfw::flush_messages(events) {
+            self.input.handle_event(&event);
         }
+
+        let moved = self
+            .input
+            .events()
+            .iter()
+            .any(|event| matches!(event, WindowEvent::CursorPos(..)));
+        self.first_mouse = self.first_mouse && !moved;
+        (self.delta_x, self.delta_y) = self.input.mouse_delta();
+        (self.scroll_x, self.scroll_y) = self.input.scroll_delta();
+        (self.last_x, self.last_y) = self.input.cursor_position();
     }
 }
@@ -0,0 +1,221 @@
+extern crate nalgebra_glm as glm;
+
+use glfw::{GamepadAxis, MouseButton};
+
+use crate::bounds::Aabb;
+use crate::camera::Camera;
+use crate::gamepad::Gamepads;
+use crate::input_state::InputState;
+
+/*
+ * Keeps a camera on a sphere around target, for inspecting models.
+ * Left drag orbits, middle drag pans the target in the view plane and
+ * scroll zooms. On a gamepad the right stick orbits, the left stick
+ * pans and the triggers zoom. Angles are in degrees, pitch is the
+ * elevation above the target.
+ */
+pub struct OrbitController {
+    pub target: glm::Vec3,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    pub min_pitch: f32,
+    pub max_pitch: f32,
+    // yaw wraps around while both bounds are infinite
+    pub min_yaw: f32,
+    pub max_yaw: f32,
+    // degrees per pixel dragged
+    pub rotate_speed: f32,
+    // fraction of the distance per scroll step
+    pub zoom_speed: f32,
+    // stick speeds per second at full deflection
+    pub gamepad_rotate_speed: f32,
+    pub gamepad_zoom_speed: f32,
+}
+
+impl OrbitController {
+    pub fn new(target: glm::Vec3, distance: f32) -> Self {
+        OrbitController {
+            target,
+            distance,
+            ..Default::default()
+        }
+    }
+
+    pub fn rotate(self: &mut Self, yaw: f32, pitch: f32) {
+        self.yaw = (self.yaw + yaw).clamp(self.min_yaw, self.max_yaw);
+        if self.min_yaw.is_infinite() && self.max_yaw.is_infinite() {
+            self.yaw %= 360.0;
+        }
+        self.pitch = (self.pitch + pitch).clamp(self.min_pitch, self.max_pitch);
+    }
+
+    // positive steps move closer, each one scales the distance by zoom_speed
+    pub fn zoom(self: &mut Self, steps: f32) {
+        self.distance *= (1.0 - self.zoom_speed).powf(steps);
+        self.distance = self.distance.clamp(self.min_distance, self.max_distance);
+    }
+
+    // moves the target along the camera's right and up, scaled so the target tracks the cursor
+    pub fn pan(self: &mut Self, camera: &Camera, x: f32, y: f32, viewport_height: f32) {
+        let units_per_pixel = camera.view_height(self.distance) / viewport_height.max(1.0);
+        self.target += (camera.up * y - camera.right * x) * units_per_pixel;
+    }
+
+    // centers on the box and backs off until its bounding sphere fits the view
+    pub fn frame(self: &mut Self, camera: &Camera, aabb: &Aabb) {
+        if aabb.is_empty() {
+            return;
+        }
+        let radius = glm::length(&aabb.extents()).max(f32::EPSILON);
+        let vertical = camera.fov.to_radians() * 0.5;
+        let horizontal = (vertical.tan() * camera.aspect).atan();
+        self.target = aabb.center();
+        self.distance =
+            (radius / vertical.min(horizontal).sin()).clamp(self.min_distance, self.max_distance);
+    }
+
+    pub fn update(
+        self: &mut Self,
+        input: &InputState,
+        gamepads: &Gamepads,
+        camera: &mut Camera,
+        viewport_height: f32,
+        delta: f32,
+    ) {
+        let (mouse_x, mouse_y) = input.mouse_delta();
+        if input.is_down(MouseButton::Button1) {
+            self.rotate(mouse_x * self.rotate_speed, mouse_y * self.rotate_speed);
+        }
+        if input.is_down(MouseButton::Button3) {
+            self.pan(camera, mouse_x, mouse_y, viewport_height);
+        }
+        self.zoom(input.scroll_delta().1);
+
+        let stick_x = gamepads.axis(GamepadAxis::AxisRightX);
+        let stick_y = gamepads.axis(GamepadAxis::AxisRightY);
+        let rotate = self.gamepad_rotate_speed * delta;
+        self.rotate(stick_x * rotate, stick_y * rotate);
+
+        // a full stick deflection pans half the view height per second, the
+        // stick moves the view where mouse drags move the scene
+        let pan_x = gamepads.axis(GamepadAxis::AxisLeftX);
+        let pan_y = gamepads.axis(GamepadAxis::AxisLeftY);
+        self.pan(camera, -pan_x * delta * 0.5, -pan_y * delta * 0.5, 1.0);
+
+        let zoom = gamepads.axis(GamepadAxis::AxisRightTrigger)
+            - gamepads.axis(GamepadAxis::AxisLeftTrigger);
+        self.zoom(zoom * self.gamepad_zoom_speed * delta);
+
+        self.apply(camera);
+    }
+
+    // places the camera on the orbit looking at the target
+    pub fn apply(self: &Self, camera: &mut Camera) {
+        let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+        let offset = glm::Vec3::new(
+            pitch.cos() * yaw.cos(),
+            pitch.sin(),
+            pitch.cos() * yaw.sin(),
+        );
+        camera.position = self.target + offset * self.distance;
+        camera.look_at(&self.target);
+    }
+}
+
+impl Default for OrbitController {
+    fn default() -> Self {
+        OrbitController {
+            target: glm::Vec3::zeros(),
+            distance: 5.0,
+            yaw: 90.0,
+            pitch: 20.0,
+            min_distance: 0.1,
+            max_distance: 1000.0,
+            min_pitch: -89.0,
+            max_pitch: 89.0,
+            min_yaw: f32::NEG_INFINITY,
+            max_yaw: f32::INFINITY,
+            rotate_speed: 0.3,
+            zoom_speed: 0.1,
+            gamepad_rotate_speed: 120.0,
+            gamepad_zoom_speed: 10.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotate_wraps_yaw_unless_bounded_and_clamps_pitch() {
+        let mut orbit = OrbitController::new(glm::Vec3::zeros(), 5.0);
+        orbit.rotate(300.0, 200.0);
+        assert_eq!(orbit.yaw, 30.0);
+        assert_eq!(orbit.pitch, orbit.max_pitch);
+
+        orbit.min_yaw = -45.0;
+        orbit.max_yaw = 45.0;
+        orbit.rotate(100.0, -400.0);
+        assert_eq!(orbit.yaw, 45.0);
+        assert_eq!(orbit.pitch, orbit.min_pitch);
+        orbit.rotate(-200.0, 0.0);
+        assert_eq!(orbit.yaw, -45.0);
+    }
+
+    #[test]
+    fn zoom_scales_the_distance_within_its_bounds() {
+        let mut orbit = OrbitController::new(glm::Vec3::zeros(), 10.0);
+        orbit.zoom(1.0);
+        assert!((orbit.distance - 9.0).abs() < 1e-4);
+        orbit.zoom(-1.0);
+        assert!((orbit.distance - 10.0).abs() < 1e-4);
+
+        orbit.zoom(1000.0);
+        assert_eq!(orbit.distance, orbit.min_distance);
+        orbit.zoom(-1000.0);
+        assert_eq!(orbit.distance, orbit.max_distance);
+    }
+
+    #[test]
+    fn frame_fits_the_bounding_sphere_in_the_narrower_fov() {
+        let aabb = Aabb::new(glm::vec3(1.0, 1.0, 1.0), glm::vec3(3.0, 5.0, 7.0));
+        let radius = glm::length(&aabb.extents());
+        let mut orbit = OrbitController::default();
+        let mut camera = Camera::new(glm::Vec3::zeros());
+        let vertical = camera.fov.to_radians() * 0.5;
+
+        // wide views are limited vertically
+        camera.aspect = 2.0;
+        orbit.frame(&camera, &aabb);
+        assert_eq!(orbit.target, glm::vec3(2.0, 3.0, 4.0));
+        assert!((orbit.distance * vertical.sin() - radius).abs() < 1e-4);
+
+        // tall views horizontally, backing off further
+        camera.aspect = 0.5;
+        orbit.frame(&camera, &aabb);
+        let horizontal = (vertical.tan() * 0.5).atan();
+        assert!((orbit.distance * horizontal.sin() - radius).abs() < 1e-4);
+        assert!(orbit.distance * vertical.sin() > radius);
+    }
+
+    #[test]
+    fn apply_puts_the_camera_on_the_orbit_looking_at_the_target() {
+        let target = glm::vec3(1.0, -2.0, 3.0);
+        let mut orbit = OrbitController::new(target, 4.0);
+        let mut camera = Camera::new(glm::Vec3::zeros());
+        for (yaw, pitch) in [(0.0, 0.0), (90.0, 20.0), (-135.0, -60.0)] {
+            orbit.yaw = yaw;
+            orbit.pitch = pitch;
+            orbit.apply(&mut camera);
+
+            assert!((glm::distance(&camera.position, &target) - 4.0).abs() < 1e-4);
+            let to_target = glm::normalize(&(target - camera.position));
+            assert!(glm::distance(&camera.front, &to_target) < 1e-4);
+            assert!((camera.position.y - target.y - 4.0 * pitch.to_radians().sin()).abs() < 1e-4);
+        }
+    }
+}
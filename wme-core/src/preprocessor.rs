@@ -0,0 +1,436 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+// the defines one variant of a shader is compiled with
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ShaderVariant {
+    // sorted, so the same defines always make the same variant
+    pub defines: BTreeMap<String, String>,
+}
+
+impl ShaderVariant {
+    pub fn new() -> Self {
+        ShaderVariant::default()
+    }
+
+    // a flag like HAS_NORMAL_MAP, defined without a value
+    pub fn define(mut self: Self, name: &str) -> Self {
+        self.defines.insert(name.to_string(), String::new());
+        self
+    }
+
+    pub fn define_value(mut self: Self, name: &str, value: impl ToString) -> Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    // e.g. "HAS_NORMAL_MAP NUM_POINT_LIGHTS=4", empty for no defines
+    pub fn key(self: &Self) -> String {
+        self.defines
+            .iter()
+            .map(|(name, value)| {
+                if value.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}={}", name, value)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    fn directives(self: &Self) -> String {
+        self.defines
+            .iter()
+            .map(|(name, value)| format!("#define {} {}\n", name, value))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreprocessError {
+    pub file: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file.display(), self.line, self.message)
+    }
+}
+
+impl Error for PreprocessError {}
+
+// GLSL ready for the compiler, with the files its #line directives number
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PreprocessedSource {
+    pub source: String,
+    // files[n] is source string n, files[0] the shader itself
+    pub files: Vec<PathBuf>,
+}
+
+impl PreprocessedSource {
+    // rewrites the "0:12" and "0(12)" locations drivers report into "file:12"
+    pub fn remap_log(self: &Self, log: &str) -> String {
+        log.lines()
+            .map(|line| self.remap_line(line).unwrap_or_else(|| line.to_string()))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn remap_line(self: &Self, line: &str) -> Option<String> {
+        // anything before the location is a severity like "ERROR: "
+        let start = line.find(|c: char| c.is_ascii_digit())?;
+        let (prefix, rest) = line.split_at(start);
+        if !prefix
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c == ':' || c == ' ')
+        {
+            return None;
+        }
+
+        let (file, rest) = split_number(rest)?;
+        let (number, rest) = match rest.strip_prefix(':') {
+            Some(rest) => split_number(rest)?,
+            None => {
+                let (number, rest) = split_number(rest.strip_prefix('(')?)?;
+                (number, rest.strip_prefix(')')?)
+            }
+        };
+        let path = self.files.get(file)?;
+        Some(format!("{}{}:{}{}", prefix, path.display(), number, rest))
+    }
+}
+
+fn split_number(text: &str) -> Option<(usize, &str)> {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let number = text[..end].parse().ok()?;
+    Some((number, &text[end..]))
+}
+
+/*
+ * Resolves #include "file" against the including file's directory and
+ * then the search paths, #include <file> against the search paths only.
+ * Every file is included at most once per shader, so headers need no
+ * guards. The variant's defines go right after #version, and #line
+ * directives keep compiler errors pointing at the file they came from,
+ * with the GLSL 3.30 meaning of #line: it numbers the line after it.
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preprocessor {
+    pub search_paths: Vec<PathBuf>,
+}
+
+// state of one process() call
+struct Expansion {
+    files: Vec<PathBuf>,
+    // canonical paths of files, to spot the same file included two ways
+    canonical: Vec<PathBuf>,
+    source: String,
+}
+
+impl Preprocessor {
+    pub fn new() -> Self {
+        Preprocessor::default()
+    }
+
+    pub fn search_path(mut self: Self, path: impl Into<PathBuf>) -> Self {
+        self.search_paths.push(path.into());
+        self
+    }
+
+    pub fn process(
+        self: &Self,
+        path: &Path,
+        variant: &ShaderVariant,
+    ) -> Result<PreprocessedSource, PreprocessError> {
+        let contents = std::fs::read_to_string(path).map_err(|error| PreprocessError {
+            file: path.to_path_buf(),
+            line: 0,
+            message: error.to_string(),
+        })?;
+        let mut expansion = Expansion {
+            files: vec![path.to_path_buf()],
+            canonical: vec![canonical(path)],
+            source: String::new(),
+        };
+
+        // #version has to stay the first directive, only comments may come before it
+        let lines: Vec<&str> = contents.lines().collect();
+        let version = lines
+            .iter()
+            .position(|line| line.trim_start().starts_with("#version"));
+        let first_line = match version {
+            Some(index) => {
+                for line in &lines[..=index] {
+                    expansion.source.push_str(line);
+                    expansion.source.push('\n');
+                }
+                index + 2
+            }
+            None => 1,
+        };
+        expansion.source.push_str(&variant.directives());
+        expansion
+            .source
+            .push_str(&format!("#line {} 0\n", first_line));
+
+        self.expand(&mut expansion, 0, &lines[first_line - 1..], first_line)?;
+
+        Ok(PreprocessedSource {
+            source: expansion.source,
+            files: expansion.files,
+        })
+    }
+
+    fn expand(
+        self: &Self,
+        expansion: &mut Expansion,
+        file: usize,
+        lines: &[&str],
+        first_line: usize,
+    ) -> Result<(), PreprocessError> {
+        for (offset, line) in lines.iter().enumerate() {
+            let number = first_line + offset;
+            let error = |message: String| PreprocessError {
+                file: expansion.files[file].clone(),
+                line: number,
+                message,
+            };
+            let trimmed = line.trim_start();
+
+            if trimmed.starts_with("#version") {
+                return Err(error(
+                    "#version is only allowed at the top of the shader".to_string(),
+                ));
+            }
+            let Some(rest) = trimmed.strip_prefix("#include") else {
+                expansion.source.push_str(line);
+                expansion.source.push('\n');
+                continue;
+            };
+
+            let (name, quoted) = parse_include(rest)
+                .ok_or_else(|| error("expected #include \"file\" or <file>".to_string()))?;
+            let resolved = self
+                .resolve(&expansion.files[file], name, quoted)
+                .ok_or_else(|| error(format!("cannot find include {}", name)))?;
+
+            let canonical_path = canonical(&resolved);
+            if !expansion.canonical.contains(&canonical_path) {
+                let contents = std::fs::read_to_string(&resolved)
+                    .map_err(|read_error| error(format!("{}: {}", name, read_error)))?;
+                expansion.files.push(resolved);
+                expansion.canonical.push(canonical_path);
+                let index = expansion.files.len() - 1;
+
+                expansion.source.push_str(&format!("#line 1 {}\n", index));
+                let included: Vec<&str> = contents.lines().collect();
+                self.expand(expansion, index, &included, 1)?;
+            }
+            expansion
+                .source
+                .push_str(&format!("#line {} {}\n", number + 1, file));
+        }
+        Ok(())
+    }
+
+    fn resolve(self: &Self, including: &Path, name: &str, quoted: bool) -> Option<PathBuf> {
+        let local = including
+            .parent()
+            .filter(|_| quoted)
+            .map(|directory| directory.join(name));
+        local
+            .into_iter()
+            .chain(self.search_paths.iter().map(|path| path.join(name)))
+            .find(|candidate| candidate.is_file())
+    }
+}
+
+// the name and whether it was quoted, from what follows #include
+fn parse_include(rest: &str) -> Option<(&str, bool)> {
+    let rest = rest.trim();
+    if let Some(name) = rest
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+    {
+        return Some((name, true));
+    }
+    let name = rest.strip_prefix('<')?.strip_suffix('>')?;
+    Some((name, false))
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a fresh directory under the system temp dir holding the given files
+    fn shader_dir(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = std::env::temp_dir()
+            .join(format!("wme-preprocessor-{}", std::process::id()))
+            .join(test);
+        let _ = std::fs::remove_dir_all(&directory);
+        for (name, contents) in files {
+            let path = directory.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    fn defines_follow_the_version() {
+        let directory = shader_dir(
+            "version",
+            &[(
+                "main.glsl",
+                "// header\n#version 330 core\nvoid main() {}\n",
+            )],
+        );
+        let variant = ShaderVariant::new()
+            .define("HAS_NORMAL_MAP")
+            .define_value("NUM_LIGHTS", 4);
+        let processed = Preprocessor::new()
+            .process(&directory.join("main.glsl"), &variant)
+            .unwrap();
+
+        assert_eq!(
+            processed.source,
+            "// header\n#version 330 core\n\
+             #define HAS_NORMAL_MAP \n#define NUM_LIGHTS 4\n\
+             #line 3 0\nvoid main() {}\n"
+        );
+        assert_eq!(variant.key(), "HAS_NORMAL_MAP NUM_LIGHTS=4");
+    }
+
+    #[test]
+    fn version_after_the_top_is_an_error() {
+        let directory = shader_dir(
+            "late_version",
+            &[
+                ("main.glsl", "#version 330 core\n#include \"lib.glsl\"\n"),
+                ("lib.glsl", "float x;\n#version 450\n"),
+            ],
+        );
+        let error = Preprocessor::new()
+            .process(&directory.join("main.glsl"), &ShaderVariant::new())
+            .unwrap_err();
+        assert_eq!(error.file, directory.join("lib.glsl"));
+        assert_eq!(error.line, 2);
+    }
+
+    #[test]
+    fn nested_and_repeated_includes_expand_once() {
+        let directory = shader_dir(
+            "nested",
+            &[
+                (
+                    "main.glsl",
+                    "#version 330 core\n#include \"a.glsl\"\n#include <common.glsl>\nvoid main() {}\n",
+                ),
+                ("a.glsl", "#include <common.glsl>\nfloat a;\n"),
+                ("lib/common.glsl", "float common;\n"),
+            ],
+        );
+        let processed = Preprocessor::new()
+            .search_path(directory.join("lib"))
+            .process(&directory.join("main.glsl"), &ShaderVariant::new())
+            .unwrap();
+
+        assert_eq!(
+            processed.files,
+            vec![
+                directory.join("main.glsl"),
+                directory.join("a.glsl"),
+                directory.join("lib").join("common.glsl"),
+            ]
+        );
+        assert_eq!(processed.source.matches("float common;").count(), 1);
+        assert_eq!(
+            processed.source,
+            "#version 330 core\n#line 2 0\n\
+             #line 1 1\n\
+             #line 1 2\nfloat common;\n#line 2 1\n\
+             float a;\n#line 3 0\n\
+             #line 4 0\nvoid main() {}\n"
+        );
+    }
+
+    #[test]
+    fn line_numbers_continue_after_an_include() {
+        let directory = shader_dir(
+            "line",
+            &[
+                (
+                    "main.glsl",
+                    "#version 330 core\nfloat a;\n#include \"lib.glsl\"\nfloat b;\n",
+                ),
+                ("lib.glsl", "float x;\nfloat y;\n"),
+            ],
+        );
+        let processed = Preprocessor::new()
+            .process(&directory.join("main.glsl"), &ShaderVariant::new())
+            .unwrap();
+
+        // with GLSL 3.30 semantics each #line numbers the line after it
+        let lines: Vec<&str> = processed.source.lines().collect();
+        let after = lines.iter().position(|line| *line == "float b;").unwrap();
+        assert_eq!(lines[after - 1], "#line 4 0");
+        let included = lines.iter().position(|line| *line == "float x;").unwrap();
+        assert_eq!(lines[included - 1], "#line 1 1");
+    }
+
+    #[test]
+    fn missing_include_reports_the_including_line() {
+        let directory = shader_dir(
+            "missing",
+            &[("main.glsl", "#version 330 core\n\n#include \"nope.glsl\"\n")],
+        );
+        let error = Preprocessor::new()
+            .process(&directory.join("main.glsl"), &ShaderVariant::new())
+            .unwrap_err();
+        assert_eq!(error.file, directory.join("main.glsl"));
+        assert_eq!(error.line, 3);
+        assert!(error.message.contains("nope.glsl"));
+    }
+
+    #[test]
+    fn remap_log_understands_common_drivers() {
+        let processed = PreprocessedSource {
+            source: String::new(),
+            files: vec![PathBuf::from("main.glsl"), PathBuf::from("lighting.glsl")],
+        };
+
+        // Mesa
+        assert_eq!(
+            processed.remap_log("1:12(5): error: `x' undeclared"),
+            "lighting.glsl:12(5): error: `x' undeclared"
+        );
+        // NVIDIA
+        assert_eq!(
+            processed.remap_log("0(12) : error C1008: undefined variable \"x\""),
+            "main.glsl:12 : error C1008: undefined variable \"x\""
+        );
+        // AMD
+        assert_eq!(
+            processed.remap_log(
+                "ERROR: 1:12: 'x' : undeclared identifier\nERROR: 1 compilation errors."
+            ),
+            "ERROR: lighting.glsl:12: 'x' : undeclared identifier\nERROR: 1 compilation errors."
+        );
+        // unknown source strings and other text are left alone
+        assert_eq!(processed.remap_log("5:3(1): error"), "5:3(1): error");
+        assert_eq!(processed.remap_log("warning: 0:1"), "warning: 0:1");
+    }
+}
@@ -0,0 +1,253 @@
+extern crate nalgebra_glm as glm;
+
+use crate::bounds::{transform_point, Aabb};
+
+// below this the ray runs parallel to a triangle
+const PARALLEL_EPSILON: f32 = 1e-7;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: glm::Vec3,
+    pub direction: glm::Vec3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriangleHit {
+    pub distance: f32,
+    // weights of the second and third vertex, the first gets 1 - u - v
+    pub u: f32,
+    pub v: f32,
+}
+
+impl Ray {
+    pub fn new(origin: glm::Vec3, direction: glm::Vec3) -> Self {
+        Ray {
+            origin,
+            direction: glm::normalize(&direction),
+        }
+    }
+
+    pub fn at(self: &Self, distance: f32) -> glm::Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /*
+     * Moves the ray into another space without renormalizing, so
+     * distances found against the transformed ray are still measured
+     * in the original space. Pass the inverse of a model matrix to
+     * test against mesh data in object space.
+     */
+    pub fn transformed(self: &Self, matrix: &glm::Mat4) -> Ray {
+        Ray {
+            origin: transform_point(matrix, &self.origin),
+            direction: (matrix
+                * glm::vec4(self.direction.x, self.direction.y, self.direction.z, 0.0))
+            .xyz(),
+        }
+    }
+
+    // slab test, the distance to where the ray enters or 0 when it starts inside
+    pub fn intersect_aabb(self: &Self, aabb: &Aabb) -> Option<f32> {
+        if aabb.is_empty() {
+            return None;
+        }
+
+        let mut near = 0.0_f32;
+        let mut far = f32::INFINITY;
+        for axis in 0..3 {
+            let inverse = 1.0 / self.direction[axis];
+            let mut t0 = (aabb.min[axis] - self.origin[axis]) * inverse;
+            let mut t1 = (aabb.max[axis] - self.origin[axis]) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // NaN from 0 * inf on a slab boundary leaves the range untouched
+            near = if t0 > near { t0 } else { near };
+            far = if t1 < far { t1 } else { far };
+            if far < near {
+                return None;
+            }
+        }
+
+        Some(near)
+    }
+
+    // Möller–Trumbore, both faces count as hits
+    pub fn intersect_triangle(
+        self: &Self,
+        a: &glm::Vec3,
+        b: &glm::Vec3,
+        c: &glm::Vec3,
+    ) -> Option<TriangleHit> {
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let p = glm::cross(&self.direction, &edge2);
+        let determinant = glm::dot(&edge1, &p);
+        if determinant.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+
+        let inverse = 1.0 / determinant;
+        let s = self.origin - a;
+        let u = glm::dot(&s, &p) * inverse;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = glm::cross(&s, &edge1);
+        let v = glm::dot(&self.direction, &q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let distance = glm::dot(&edge2, &q) * inverse;
+        if distance < 0.0 {
+            return None;
+        }
+
+        Some(TriangleHit { distance, u, v })
+    }
+}
+
+/*
+ * World space ray through a point in the window, for example the
+ * cursor. x and y are in the same units as width and height with the
+ * origin at the top left, so pass the window size rather than the
+ * framebuffer size for GLFW cursor positions.
+ */
+pub fn screen_to_world_ray(
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    view: &glm::Mat4,
+    projection: &glm::Mat4,
+) -> Ray {
+    // the near plane and the middle of the depth range, both finite even without a far plane
+    screen_to_world_ray_between(x, y, width, height, view, projection, (-1.0, 0.0))
+}
+
+/*
+ * Same as screen_to_world_ray for projections with another NDC depth
+ * convention. depths holds the NDC depth of the near plane and of any
+ * finite depth further away, e.g. (1.0, 0.5) for reversed-Z.
+ */
+pub fn screen_to_world_ray_between(
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    view: &glm::Mat4,
+    projection: &glm::Mat4,
+    depths: (f32, f32),
+) -> Ray {
+    let ndc_x = 2.0 * x / width - 1.0;
+    let ndc_y = 1.0 - 2.0 * y / height;
+    let inverse = glm::inverse(&(projection * view));
+
+    let near = inverse * glm::vec4(ndc_x, ndc_y, depths.0, 1.0);
+    let middle = inverse * glm::vec4(ndc_x, ndc_y, depths.1, 1.0);
+    let near = near.xyz() / near.w;
+    let middle = middle.xyz() / middle.w;
+
+    Ray::new(near, middle - near)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(glm::Vec3::repeat(-1.0), glm::Vec3::repeat(1.0))
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn slab_test_hits_misses_and_starts_inside() {
+        let ray = Ray::new(glm::vec3(-5.0, 0.0, 0.0), glm::vec3(1.0, 0.0, 0.0));
+        assert_close(ray.intersect_aabb(&unit_box()).unwrap(), 4.0);
+
+        let away = Ray::new(glm::vec3(-5.0, 0.0, 0.0), glm::vec3(-1.0, 0.0, 0.0));
+        assert_eq!(away.intersect_aabb(&unit_box()), None);
+
+        let inside = Ray::new(glm::Vec3::zeros(), glm::vec3(1.0, 1.0, 0.0));
+        assert_eq!(inside.intersect_aabb(&unit_box()), Some(0.0));
+
+        assert_eq!(ray.intersect_aabb(&Aabb::empty()), None);
+    }
+
+    #[test]
+    fn slab_test_with_axis_parallel_directions() {
+        // 1 / 0 is infinite, a slab the ray runs along either contains it or not
+        let along = Ray::new(glm::vec3(0.5, -5.0, 0.5), glm::vec3(0.0, 1.0, 0.0));
+        assert_close(along.intersect_aabb(&unit_box()).unwrap(), 4.0);
+        let beside = Ray::new(glm::vec3(1.5, -5.0, 0.5), glm::vec3(0.0, 1.0, 0.0));
+        assert_eq!(beside.intersect_aabb(&unit_box()), None);
+
+        // 0 * inf is NaN when the origin lies on a slab boundary, that slab is ignored
+        let on_face = Ray::new(glm::vec3(1.0, -5.0, -1.0), glm::vec3(0.0, 1.0, 0.0));
+        assert_close(on_face.intersect_aabb(&unit_box()).unwrap(), 4.0);
+    }
+
+    #[test]
+    fn triangle_hits_report_distance_and_barycentrics() {
+        let (a, b, c) = (
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(1.0, 0.0, 0.0),
+            glm::vec3(0.0, 1.0, 0.0),
+        );
+        let ray = Ray::new(glm::vec3(0.25, 0.5, 3.0), glm::vec3(0.0, 0.0, -1.0));
+        let hit = ray.intersect_triangle(&a, &b, &c).unwrap();
+        assert_close(hit.distance, 3.0);
+        assert_close(hit.u, 0.25);
+        assert_close(hit.v, 0.5);
+
+        // back faces count too
+        let below = Ray::new(glm::vec3(0.25, 0.25, -3.0), glm::vec3(0.0, 0.0, 1.0));
+        assert!(below.intersect_triangle(&a, &b, &c).is_some());
+
+        let outside = Ray::new(glm::vec3(0.75, 0.75, 3.0), glm::vec3(0.0, 0.0, -1.0));
+        assert_eq!(outside.intersect_triangle(&a, &b, &c), None);
+        let parallel = Ray::new(glm::vec3(0.0, 0.0, 1.0), glm::vec3(1.0, 0.0, 0.0));
+        assert_eq!(parallel.intersect_triangle(&a, &b, &c), None);
+        let behind = Ray::new(glm::vec3(0.25, 0.25, -3.0), glm::vec3(0.0, 0.0, -1.0));
+        assert_eq!(behind.intersect_triangle(&a, &b, &c), None);
+    }
+
+    #[test]
+    fn transformed_rays_keep_world_distances() {
+        // a unit box scaled by 2 and moved to x = 10
+        let model = glm::scale(
+            &glm::translation(&glm::vec3(10.0, 0.0, 0.0)),
+            &glm::Vec3::repeat(2.0),
+        );
+        let ray = Ray::new(glm::Vec3::zeros(), glm::vec3(1.0, 0.0, 0.0));
+        let local = ray.transformed(&glm::inverse(&model));
+
+        let distance = local.intersect_aabb(&unit_box()).unwrap();
+        assert_close(distance, 8.0);
+        assert_close(ray.at(distance).x, 8.0);
+    }
+
+    #[test]
+    fn screen_rays_start_on_the_near_plane() {
+        let view = glm::look_at(
+            &glm::vec3(0.0, 0.0, 5.0),
+            &glm::Vec3::zeros(),
+            &glm::Vec3::y(),
+        );
+        let projection = glm::perspective(2.0, 90.0_f32.to_radians(), 0.1, 100.0);
+
+        let center = screen_to_world_ray(400.0, 200.0, 800.0, 400.0, &view, &projection);
+        assert!(glm::distance(&center.origin, &glm::vec3(0.0, 0.0, 4.9)) < 1e-4);
+        assert!(glm::distance(&center.direction, &glm::vec3(0.0, 0.0, -1.0)) < 1e-4);
+
+        // the top right corner is 45 degrees up and, at aspect 2, further right
+        let corner = screen_to_world_ray(800.0, 0.0, 800.0, 400.0, &view, &projection);
+        let expected = glm::normalize(&glm::vec3(2.0, 1.0, -1.0));
+        assert!(glm::distance(&corner.direction, &expected) < 1e-4);
+    }
+}
@@ -0,0 +1,359 @@
+use std::{collections::HashMap, error::Error, ffi::CStr};
+
+use crate::render_device::{
+    ActiveAttribute, ActiveUniform, BlendMode, BufferTarget, ClearState, ClipDepth, CullMode,
+    DepthState, ObjectKind, PrimitiveType, RenderDevice, ShaderStage, TextureFormat, UniformValue,
+    VertexAttribute, Viewport,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenderCommand {
+    CreateBuffer {
+        id: u32,
+        target: BufferTarget,
+        size: usize,
+    },
+    DeleteBuffer(u32),
+    CreateVertexArray {
+        id: u32,
+        vertex_buffer: u32,
+        index_buffer: Option<u32>,
+        attributes: Vec<VertexAttribute>,
+    },
+    DeleteVertexArray(u32),
+    CreateTexture {
+        id: u32,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+    },
+    DeleteTexture(u32),
+    CreateShader {
+        id: u32,
+        stage: ShaderStage,
+    },
+    DeleteShader(u32),
+    CreateProgram {
+        id: u32,
+        shaders: Vec<u32>,
+    },
+    DeleteProgram(u32),
+    UseProgram(u32),
+    SetUniform {
+        program: u32,
+        name: String,
+        value: UniformValue,
+    },
+    BindTexture {
+        unit: u32,
+        texture: u32,
+    },
+    BindVertexArray(u32),
+    SetBlend(BlendMode),
+    SetDepth(DepthState),
+    SetCull(CullMode),
+    SetClipDepth(ClipDepth),
+    SetViewport(Viewport),
+    SetScissor(Option<Viewport>),
+    Clear(ClearState),
+    DrawArrays {
+        primitive: PrimitiveType,
+        first: i32,
+        count: i32,
+    },
+    DrawElements {
+        primitive: PrimitiveType,
+        count: i32,
+    },
+    Label {
+        kind: ObjectKind,
+        id: u32,
+        name: String,
+    },
+}
+
+/*
+ * RenderDevice that never touches GL. Every call is appended to
+ * commands and handles are handed out from a counter, so code that
+ * creates and draws resources can run and be inspected without a
+ * context. Uniform locations are resolved back to names when recorded.
+ */
+#[derive(Default)]
+pub struct RecordingDevice {
+    pub commands: Vec<RenderCommand>,
+    next_id: u32,
+    current_program: u32,
+    uniform_names: HashMap<i32, (u32, String)>,
+    uniform_locations: HashMap<(u32, String), i32>,
+}
+
+impl RecordingDevice {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear_commands(self: &mut Self) {
+        self.commands.clear();
+    }
+
+    pub fn take_commands(self: &mut Self) -> Vec<RenderCommand> {
+        std::mem::take(&mut self.commands)
+    }
+
+    pub fn draw_calls(self: &Self) -> usize {
+        self.commands
+            .iter()
+            .filter(|command| {
+                matches!(
+                    command,
+                    RenderCommand::DrawArrays { .. } | RenderCommand::DrawElements { .. }
+                )
+            })
+            .count()
+    }
+
+    fn next_id(self: &mut Self) -> u32 {
+        self.next_id += 1;
+        self.next_id
+    }
+}
+
+impl RenderDevice for RecordingDevice {
+    fn create_buffer(self: &mut Self, target: BufferTarget, data: &[u8]) -> u32 {
+        let id = self.next_id();
+        self.commands.push(RenderCommand::CreateBuffer {
+            id,
+            target,
+            size: data.len(),
+        });
+        id
+    }
+
+    fn delete_buffer(self: &mut Self, buffer: u32) {
+        self.commands.push(RenderCommand::DeleteBuffer(buffer));
+    }
+
+    fn create_vertex_array(
+        self: &mut Self,
+        vertex_buffer: u32,
+        index_buffer: Option<u32>,
+        attributes: &[VertexAttribute],
+    ) -> u32 {
+        let id = self.next_id();
+        self.commands.push(RenderCommand::CreateVertexArray {
+            id,
+            vertex_buffer,
+            index_buffer,
+            attributes: attributes.to_vec(),
+        });
+        id
+    }
+
+    fn delete_vertex_array(self: &mut Self, vertex_array: u32) {
+        self.commands
+            .push(RenderCommand::DeleteVertexArray(vertex_array));
+    }
+
+    fn create_texture(
+        self: &mut Self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        _pixels: &[u8],
+        label: &str,
+    ) -> Result<u32, Box<dyn Error>> {
+        let id = self.next_id();
+        self.commands.push(RenderCommand::CreateTexture {
+            id,
+            width,
+            height,
+            format,
+        });
+        self.label(ObjectKind::Texture, id, label);
+        Ok(id)
+    }
+
+    fn delete_texture(self: &mut Self, texture: u32) {
+        self.commands.push(RenderCommand::DeleteTexture(texture));
+    }
+
+    fn create_shader(self: &mut Self, stage: ShaderStage, _source: &str) -> Result<u32, String> {
+        let id = self.next_id();
+        self.commands
+            .push(RenderCommand::CreateShader { id, stage });
+        Ok(id)
+    }
+
+    fn delete_shader(self: &mut Self, shader: u32) {
+        self.commands.push(RenderCommand::DeleteShader(shader));
+    }
+
+    fn create_program(self: &mut Self, shaders: &[u32]) -> Result<u32, String> {
+        let id = self.next_id();
+        self.commands.push(RenderCommand::CreateProgram {
+            id,
+            shaders: shaders.to_vec(),
+        });
+        Ok(id)
+    }
+
+    fn delete_program(self: &mut Self, program: u32) {
+        self.commands.push(RenderCommand::DeleteProgram(program));
+    }
+
+    fn use_program(self: &mut Self, program: u32) {
+        self.current_program = program;
+        self.commands.push(RenderCommand::UseProgram(program));
+    }
+
+    fn uniform_location(self: &mut Self, program: u32, name: &CStr) -> i32 {
+        let name = name.to_string_lossy().to_string();
+        if let Some(location) = self.uniform_locations.get(&(program, name.clone())) {
+            return *location;
+        }
+
+        let location = self.uniform_locations.len() as i32;
+        self.uniform_locations
+            .insert((program, name.clone()), location);
+        self.uniform_names.insert(location, (program, name));
+        location
+    }
+
+    // nothing is compiled, so there is nothing to reflect
+    fn active_uniforms(self: &mut Self, _program: u32) -> Option<Vec<ActiveUniform>> {
+        None
+    }
+
+    fn active_attributes(self: &mut Self, _program: u32) -> Option<Vec<ActiveAttribute>> {
+        None
+    }
+
+    fn set_uniform(self: &mut Self, location: i32, value: UniformValue) {
+        if location == -1 {
+            return;
+        }
+        let (program, name) = match self.uniform_names.get(&location) {
+            Some((program, name)) => (*program, name.clone()),
+            None => (self.current_program, format!("location {}", location)),
+        };
+        self.commands.push(RenderCommand::SetUniform {
+            program,
+            name,
+            value,
+        });
+    }
+
+    fn bind_texture(self: &mut Self, unit: u32, texture: u32) {
+        self.commands
+            .push(RenderCommand::BindTexture { unit, texture });
+    }
+
+    fn bind_vertex_array(self: &mut Self, vertex_array: u32) {
+        self.commands
+            .push(RenderCommand::BindVertexArray(vertex_array));
+    }
+
+    fn set_blend(self: &mut Self, blend: BlendMode) {
+        self.commands.push(RenderCommand::SetBlend(blend));
+    }
+
+    fn set_depth(self: &mut Self, depth: DepthState) {
+        self.commands.push(RenderCommand::SetDepth(depth));
+    }
+
+    fn set_cull(self: &mut Self, cull: CullMode) {
+        self.commands.push(RenderCommand::SetCull(cull));
+    }
+
+    fn set_clip_depth(self: &mut Self, clip_depth: ClipDepth) -> bool {
+        self.commands.push(RenderCommand::SetClipDepth(clip_depth));
+        true
+    }
+
+    fn set_viewport(self: &mut Self, viewport: Viewport) {
+        self.commands.push(RenderCommand::SetViewport(viewport));
+    }
+
+    fn set_scissor(self: &mut Self, scissor: Option<Viewport>) {
+        self.commands.push(RenderCommand::SetScissor(scissor));
+    }
+
+    fn clear(self: &mut Self, clear: ClearState) {
+        self.commands.push(RenderCommand::Clear(clear));
+    }
+
+    fn draw_arrays(self: &mut Self, primitive: PrimitiveType, first: i32, count: i32) {
+        self.commands.push(RenderCommand::DrawArrays {
+            primitive,
+            first,
+            count,
+        });
+    }
+
+    fn draw_elements(self: &mut Self, primitive: PrimitiveType, count: i32) {
+        self.commands
+            .push(RenderCommand::DrawElements { primitive, count });
+    }
+
+    fn label(self: &mut Self, kind: ObjectKind, id: u32, name: &str) {
+        self.commands.push(RenderCommand::Label {
+            kind,
+            id,
+            name: name.to_string(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render_device::as_bytes;
+    use crate::vertex::Vertex;
+    use nalgebra_glm as glm;
+
+    #[test]
+    fn uniforms_are_recorded_by_name() {
+        let mut device = RecordingDevice::new();
+        device.use_program(7);
+        let location = device.uniform_location(7, c"model");
+        assert_eq!(device.uniform_location(7, c"model"), location);
+
+        device.set_uniform(location, UniformValue::Int(3));
+        assert_eq!(
+            device.commands.last(),
+            Some(&RenderCommand::SetUniform {
+                program: 7,
+                name: "model".to_string(),
+                value: UniformValue::Int(3),
+            })
+        );
+    }
+
+    #[test]
+    fn location_minus_one_is_ignored() {
+        let mut device = RecordingDevice::new();
+        device.set_uniform(-1, UniformValue::Float(1.0));
+        assert!(device.commands.is_empty());
+    }
+
+    #[test]
+    fn buffers_are_created_with_the_byte_size() {
+        let mut device = RecordingDevice::new();
+        let vertices: Vec<Vertex> = (0..3)
+            .map(|_| Vertex {
+                position: glm::Vec3::zeros(),
+                texcoord: glm::Vec2::zeros(),
+                normal: glm::Vec3::zeros(),
+            })
+            .collect();
+        let id = device.create_buffer(BufferTarget::Array, as_bytes(&vertices));
+        assert_eq!(
+            device.take_commands(),
+            vec![RenderCommand::CreateBuffer {
+                id,
+                target: BufferTarget::Array,
+                size: 3 * 8 * std::mem::size_of::<f32>(),
+            }]
+        );
+    }
+}
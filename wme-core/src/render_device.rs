@@ -0,0 +1,328 @@
+extern crate nalgebra_glm as glm;
+
+use std::{error::Error, ffi::CStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BufferTarget {
+    Array,
+    ElementArray,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureFormat {
+    R8,
+    Rg8,
+    Rgb8,
+    Rgba8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrimitiveType {
+    Triangles,
+    Lines,
+    Points,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ObjectKind {
+    Buffer,
+    VertexArray,
+    Texture,
+    Shader,
+    Program,
+}
+
+// float vertex attribute read from the bound vertex buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VertexAttribute {
+    pub index: u32,
+    pub components: i32,
+    pub stride: usize,
+    pub offset: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UniformValue {
+    Int(i32),
+    IVec2(glm::IVec2),
+    IVec3(glm::IVec3),
+    IVec4(glm::IVec4),
+    UInt(u32),
+    UVec2(glm::UVec2),
+    UVec3(glm::UVec3),
+    UVec4(glm::UVec4),
+    Float(f32),
+    Vec2(glm::Vec2),
+    Vec3(glm::Vec3),
+    Vec4(glm::Vec4),
+    Mat3(glm::Mat3),
+    Mat4(glm::Mat4),
+}
+
+// GLSL types of active uniforms and attributes, Other holds the GL enum of anything else
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UniformType {
+    Bool,
+    BVec2,
+    BVec3,
+    BVec4,
+    Int,
+    IVec2,
+    IVec3,
+    IVec4,
+    UInt,
+    UVec2,
+    UVec3,
+    UVec4,
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Mat3,
+    Mat4,
+    Sampler2D,
+    SamplerCube,
+    // every other sampler or image unit, with its GL enum, e.g. sampler2DShadow
+    Sampler(u32),
+    Other(u32),
+}
+
+impl UniformType {
+    // bools and samplers are set as ints, like glUniform1i does
+    pub fn accepts(self: &Self, value: &UniformValue) -> bool {
+        matches!(
+            (self, value),
+            (
+                UniformType::Bool
+                    | UniformType::Int
+                    | UniformType::Sampler2D
+                    | UniformType::SamplerCube
+                    | UniformType::Sampler(_),
+                UniformValue::Int(_)
+            ) | (
+                UniformType::BVec2 | UniformType::IVec2,
+                UniformValue::IVec2(_)
+            ) | (
+                UniformType::BVec3 | UniformType::IVec3,
+                UniformValue::IVec3(_)
+            ) | (
+                UniformType::BVec4 | UniformType::IVec4,
+                UniformValue::IVec4(_)
+            ) | (UniformType::UInt, UniformValue::UInt(_))
+                | (UniformType::UVec2, UniformValue::UVec2(_))
+                | (UniformType::UVec3, UniformValue::UVec3(_))
+                | (UniformType::UVec4, UniformValue::UVec4(_))
+                | (UniformType::Float, UniformValue::Float(_))
+                | (UniformType::Vec2, UniformValue::Vec2(_))
+                | (UniformType::Vec3, UniformValue::Vec3(_))
+                | (UniformType::Vec4, UniformValue::Vec4(_))
+                | (UniformType::Mat3, UniformValue::Mat3(_))
+                | (UniformType::Mat4, UniformValue::Mat4(_))
+        )
+    }
+}
+
+// an array has one location per element, name drops the [0] GL reports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveUniform {
+    pub name: String,
+    pub kind: UniformType,
+    pub locations: Vec<i32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveAttribute {
+    pub name: String,
+    pub kind: UniformType,
+    pub location: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    Disabled,
+    Alpha,
+    Premultiplied,
+    Additive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DepthFunc {
+    Never,
+    Less,
+    LessEqual,
+    Equal,
+    Greater,
+    GreaterEqual,
+    Always,
+}
+
+// test None disables depth testing entirely, which also disables writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DepthState {
+    pub test: Option<DepthFunc>,
+    pub write: bool,
+}
+
+impl Default for DepthState {
+    fn default() -> Self {
+        DepthState {
+            test: Some(DepthFunc::Less),
+            write: true,
+        }
+    }
+}
+
+// the NDC depth range clip space maps to, ZeroToOne is what reversed-Z relies on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClipDepth {
+    NegativeOneToOne,
+    ZeroToOne,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CullMode {
+    Disabled,
+    Back,
+    Front,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Viewport {
+    pub fn new(x: i32, y: i32, width: i32, height: i32) -> Self {
+        Viewport {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+// depth is the value to clear to, 1.0 normally and 0.0 for reversed-Z
+pub struct ClearState {
+    pub color: Option<glm::Vec4>,
+    pub depth: Option<f32>,
+}
+
+/*
+ * Every GL resource and draw call in the engine goes through a
+ * RenderDevice. GlDevice issues the real calls, RecordingDevice only
+ * logs them so draw code can be checked without a context.
+ * Ids are backend handles, 0 is never a valid object.
+ */
+pub trait RenderDevice {
+    fn create_buffer(self: &mut Self, target: BufferTarget, data: &[u8]) -> u32;
+
+    fn delete_buffer(self: &mut Self, buffer: u32);
+
+    // records the attribute layout and element buffer into a new vertex array
+    fn create_vertex_array(
+        self: &mut Self,
+        vertex_buffer: u32,
+        index_buffer: Option<u32>,
+        attributes: &[VertexAttribute],
+    ) -> u32;
+
+    fn delete_vertex_array(self: &mut Self, vertex_array: u32);
+
+    // repeat wrapping, trilinear filtering and generated mipmaps
+    fn create_texture(
+        self: &mut Self,
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        pixels: &[u8],
+        label: &str,
+    ) -> Result<u32, Box<dyn Error>>;
+
+    fn delete_texture(self: &mut Self, texture: u32);
+
+    // Err holds the compiler info log
+    fn create_shader(self: &mut Self, stage: ShaderStage, source: &str) -> Result<u32, String>;
+
+    // only needed for shaders that never reach create_program
+    fn delete_shader(self: &mut Self, shader: u32);
+
+    // links and then deletes the shaders, Err holds the linker info log
+    fn create_program(self: &mut Self, shaders: &[u32]) -> Result<u32, String>;
+
+    fn delete_program(self: &mut Self, program: u32);
+
+    fn use_program(self: &mut Self, program: u32);
+
+    fn uniform_location(self: &mut Self, program: u32, name: &CStr) -> i32;
+
+    // uniforms a linked program uses, None when the backend can't tell
+    fn active_uniforms(self: &mut Self, program: u32) -> Option<Vec<ActiveUniform>>;
+
+    fn active_attributes(self: &mut Self, program: u32) -> Option<Vec<ActiveAttribute>>;
+
+    // applies to the program in use, location -1 is ignored like in GL
+    fn set_uniform(self: &mut Self, location: i32, value: UniformValue);
+
+    fn bind_texture(self: &mut Self, unit: u32, texture: u32);
+
+    fn bind_vertex_array(self: &mut Self, vertex_array: u32);
+
+    fn set_blend(self: &mut Self, blend: BlendMode);
+
+    fn set_depth(self: &mut Self, depth: DepthState);
+
+    fn set_cull(self: &mut Self, cull: CullMode);
+
+    // false if the backend cannot change it, NegativeOneToOne always works
+    fn set_clip_depth(self: &mut Self, clip_depth: ClipDepth) -> bool;
+
+    fn set_viewport(self: &mut Self, viewport: Viewport);
+
+    // limits clears and draws to the rectangle, None disables the scissor test
+    fn set_scissor(self: &mut Self, scissor: Option<Viewport>);
+
+    fn clear(self: &mut Self, clear: ClearState);
+
+    fn draw_arrays(self: &mut Self, primitive: PrimitiveType, first: i32, count: i32);
+
+    // indices are u32 from the element buffer of the bound vertex array
+    fn draw_elements(self: &mut Self, primitive: PrimitiveType, count: i32);
+
+    fn label(self: &mut Self, kind: ObjectKind, id: u32, name: &str);
+
+    // forget any cached state after GL was touched outside the device
+    fn reset_state(self: &mut Self) {}
+}
+
+/// Plain data that as_bytes can view as raw bytes.
+///
+/// # Safety
+///
+/// Every byte of the type must be initialized data: no padding and no
+/// pointers, or as_bytes reads uninitialized memory.
+pub unsafe trait Pod {}
+
+unsafe impl Pod for u8 {}
+unsafe impl Pod for u16 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for i8 {}
+unsafe impl Pod for i16 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for f32 {}
+unsafe impl Pod for f64 {}
+
+// views a slice of plain vertex or index data as the bytes uploaded to a buffer
+pub fn as_bytes<T: Pod>(data: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(data.as_ptr().cast(), std::mem::size_of_val(data)) }
+}
@@ -1,120 +1,462 @@
 extern crate nalgebra_glm as glm;
 
-use std::{error::Error, ffi::CStr, io::Read};
+use std::{
+    collections::HashMap,
+    error::Error,
+    ffi::{CStr, CString},
+    fmt,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
 
+use crate::preprocessor::{Preprocessor, ShaderVariant};
+use crate::render_device::{
+    ActiveAttribute, ActiveUniform, ObjectKind, RenderDevice, ShaderStage, UniformType,
+    UniformValue,
+};
+
+type Uniforms = Option<HashMap<String, ActiveUniform>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum UniformError {
+    // not an active uniform, possibly optimized out by the compiler
+    Unknown(String),
+    TypeMismatch {
+        name: String,
+        expected: UniformType,
+        found: UniformValue,
+    },
+    TooManyElements {
+        name: String,
+        size: usize,
+        given: usize,
+    },
+}
+
+impl fmt::Display for UniformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UniformError::Unknown(name) => write!(f, "No active uniform {}", name),
+            UniformError::TypeMismatch {
+                name,
+                expected,
+                found,
+            } => write!(f, "Uniform {} is a {:?}, got {:?}", name, expected, found),
+            UniformError::TooManyElements { name, size, given } => {
+                write!(f, "Uniform {} has {} elements, got {}", name, size, given)
+            }
+        }
+    }
+}
+
+impl Error for UniformError {}
+
+/*
+ * A linked program built from a vertex and a fragment shader file, run
+ * through the preprocessor with one variant's defines. The files and
+ * everything they include can be reloaded while running: reload() swaps
+ * in a new program under the same Shader, so the id changes and any
+ * uniforms have to be set again before the next draw.
+ */
 pub struct Shader {
     pub id: u32,
+    paths: Vec<PathBuf>,
+    preprocessor: Preprocessor,
+    variant: ShaderVariant,
+    // the shader files and their includes as of the last build
+    watched: Vec<PathBuf>,
+    // modification times of watched, None when unreadable
+    modified: Vec<Option<SystemTime>>,
+    // reflected at link time, None when the device can't reflect
+    uniforms: Uniforms,
+    attributes: Option<Vec<ActiveAttribute>>,
 }
 
 impl Shader {
-    pub fn new(shaders: &[&str]) -> Result<Self, Box<dyn Error>> {
-        let vertex_shader = Self::create_shader(shaders[0], gl::VERTEX_SHADER)?;
-        let fragment_shader = Self::create_shader(shaders[1], gl::FRAGMENT_SHADER)?;
-        let id = Self::create_program(vertex_shader, fragment_shader);
+    pub fn new(device: &mut dyn RenderDevice, shaders: &[&str]) -> Result<Self, Box<dyn Error>> {
+        Self::with_variant(device, shaders, &Preprocessor::new(), &ShaderVariant::new())
+    }
+
+    pub fn with_variant(
+        device: &mut dyn RenderDevice,
+        shaders: &[&str],
+        preprocessor: &Preprocessor,
+        variant: &ShaderVariant,
+    ) -> Result<Self, Box<dyn Error>> {
+        let paths: Vec<PathBuf> = shaders.iter().map(PathBuf::from).collect();
+        let (id, watched) = Self::build(device, &paths, preprocessor, variant)?;
+        let modified = Self::modified_times(&watched);
+        let (uniforms, attributes) = Self::reflect(device, id);
 
-        Ok(Shader { id })
+        Ok(Shader {
+            id,
+            paths,
+            preprocessor: preprocessor.clone(),
+            variant: variant.clone(),
+            watched,
+            modified,
+            uniforms,
+            attributes,
+        })
+    }
+
+    /*
+     * Rebuilds the program from the files. On failure the old program is
+     * kept, so a typo in a shader leaves the last working version on screen.
+     */
+    pub fn reload(self: &mut Self, device: &mut dyn RenderDevice) -> Result<(), Box<dyn Error>> {
+        let built = Self::build(device, &self.paths, &self.preprocessor, &self.variant);
+        let (id, watched) = match built {
+            Ok(built) => built,
+            Err(error) => {
+                // not retried until one of the files changes again
+                self.modified = Self::modified_times(&self.watched);
+                return Err(error);
+            }
+        };
+        self.modified = Self::modified_times(&watched);
+        self.watched = watched;
+        device.delete_program(self.id);
+        self.id = id;
+        (self.uniforms, self.attributes) = Self::reflect(device, id);
+        Ok(())
     }
 
-    pub fn use_program(self: &Self) {
-        unsafe {
-            gl::UseProgram(self.id);
+    // reloads when a file changed since the last build, true when the program was swapped
+    pub fn reload_if_changed(self: &mut Self, device: &mut dyn RenderDevice) -> bool {
+        if Self::modified_times(&self.watched) == self.modified {
+            return false;
+        }
+        let name = Self::name(&self.paths, &self.variant);
+        match self.reload(device) {
+            Ok(()) => {
+                log::info!("Reloaded shader {}", name);
+                true
+            }
+            Err(error) => {
+                log::error!("Keeping the previous shader {}: {}", name, error);
+                false
+            }
         }
     }
 
-    pub fn set_bool(self: &Self, name: &CStr, value: bool) {
-        self.set_int(name, value as i32)
+    pub fn use_program(self: &Self, device: &mut dyn RenderDevice) {
+        device.use_program(self.id);
     }
 
-    pub fn set_int(self: &Self, name: &CStr, value: i32) {
-        unsafe {
-            gl::Uniform1i(gl::GetUniformLocation(self.id, name.as_ptr()), value);
+    pub fn set_bool(self: &Self, device: &mut dyn RenderDevice, name: &CStr, value: bool) {
+        self.set_int(device, name, value as i32)
+    }
+
+    pub fn set_int(self: &Self, device: &mut dyn RenderDevice, name: &CStr, value: i32) {
+        self.set_ignoring_errors(device, name, UniformValue::Int(value));
+    }
+
+    pub fn set_float(self: &Self, device: &mut dyn RenderDevice, name: &CStr, value: f32) {
+        self.set_ignoring_errors(device, name, UniformValue::Float(value));
+    }
+
+    pub fn set_vec2(self: &Self, device: &mut dyn RenderDevice, name: &CStr, vector: glm::Vec2) {
+        self.set_ignoring_errors(device, name, UniformValue::Vec2(vector));
+    }
+
+    pub fn set_vec3(self: &Self, device: &mut dyn RenderDevice, name: &CStr, vector: glm::Vec3) {
+        self.set_ignoring_errors(device, name, UniformValue::Vec3(vector));
+    }
+
+    pub fn set_vec4(self: &Self, device: &mut dyn RenderDevice, name: &CStr, vector: glm::Vec4) {
+        self.set_ignoring_errors(device, name, UniformValue::Vec4(vector));
+    }
+
+    pub fn set_mat3(self: &Self, device: &mut dyn RenderDevice, name: &CStr, matrix: glm::Mat3) {
+        self.set_ignoring_errors(device, name, UniformValue::Mat3(matrix));
+    }
+
+    pub fn set_mat4(self: &Self, device: &mut dyn RenderDevice, name: &CStr, matrix: glm::Mat4) {
+        self.set_ignoring_errors(device, name, UniformValue::Mat4(matrix));
+    }
+
+    // the program must be in use, like for the set_* shorthands
+    pub fn set_uniform(
+        self: &Self,
+        device: &mut dyn RenderDevice,
+        name: &str,
+        value: UniformValue,
+    ) -> Result<(), UniformError> {
+        self.set_uniform_array(device, name, &[value])
+    }
+
+    /*
+     * Sets elements 0..values.len() of a uniform array, or a plain uniform
+     * from a single value. Checked against the types reflected at link
+     * time; without reflection the values are passed on unchecked.
+     */
+    pub fn set_uniform_array(
+        self: &Self,
+        device: &mut dyn RenderDevice,
+        name: &str,
+        values: &[UniformValue],
+    ) -> Result<(), UniformError> {
+        let uniforms = match &self.uniforms {
+            Some(uniforms) => uniforms,
+            None => {
+                for (index, value) in values.iter().enumerate() {
+                    let element = if index == 0 {
+                        name.to_string()
+                    } else {
+                        format!("{}[{}]", name, index)
+                    };
+                    let element = CString::new(element)
+                        .map_err(|_| UniformError::Unknown(name.to_string()))?;
+                    let location = device.uniform_location(self.id, &element);
+                    device.set_uniform(location, *value);
+                }
+                return Ok(());
+            }
+        };
+
+        let uniform = uniforms
+            .get(name)
+            .ok_or_else(|| UniformError::Unknown(name.to_string()))?;
+        if values.len() > uniform.locations.len() {
+            return Err(UniformError::TooManyElements {
+                name: name.to_string(),
+                size: uniform.locations.len(),
+                given: values.len(),
+            });
+        }
+        if let Some(value) = values.iter().find(|value| !uniform.kind.accepts(value)) {
+            return Err(UniformError::TypeMismatch {
+                name: name.to_string(),
+                expected: uniform.kind,
+                found: *value,
+            });
+        }
+        for (location, value) in uniform.locations.iter().zip(values) {
+            device.set_uniform(*location, *value);
         }
+        Ok(())
+    }
+
+    // None when the device can't reflect programs
+    pub fn uniform(self: &Self, name: &str) -> Option<&ActiveUniform> {
+        self.uniforms.as_ref()?.get(name)
     }
 
-    pub fn set_float(self: &Self, name: &str, value: f32) {
-        unsafe {
-            gl::Uniform1f(gl::GetUniformLocation(self.id, name.as_ptr().cast()), value);
+    pub fn attributes(self: &Self) -> Option<&[ActiveAttribute]> {
+        self.attributes.as_deref()
+    }
+
+    // names in expected the program doesn't use, empty without reflection
+    pub fn missing_uniforms(self: &Self, expected: &[&str]) -> Vec<String> {
+        match &self.uniforms {
+            Some(uniforms) => expected
+                .iter()
+                .filter(|name| !uniforms.contains_key(**name))
+                .map(|name| name.to_string())
+                .collect(),
+            None => Vec::new(),
         }
     }
 
-    pub fn set_vec3(self: &Self, name: &CStr, vector: glm::Vec3) {
-        unsafe {
-            gl::Uniform3f(
-                gl::GetUniformLocation(self.id, name.as_ptr()),
-                vector.x,
-                vector.y,
-                vector.z,
+    // for callers that know which uniforms they will set, e.g. a material's textures
+    pub fn warn_missing_uniforms(self: &Self, expected: &[&str]) {
+        for name in self.missing_uniforms(expected) {
+            log::warn!(
+                "Shader {} has no uniform {}",
+                Self::name(&self.paths, &self.variant),
+                name
             );
         }
     }
 
-    pub fn set_mat4(self: &Self, name: &CStr, matrix: glm::Mat4) {
-        unsafe {
-            gl::UniformMatrix4fv(
-                gl::GetUniformLocation(self.id, name.as_ptr()),
-                1,
-                gl::FALSE,
-                matrix.as_ptr(),
-            );
+    pub fn destroy(self: &Self, device: &mut dyn RenderDevice) {
+        device.delete_program(self.id);
+    }
+
+    /*
+     * Like GL with a -1 location, uniforms the program doesn't use are
+     * skipped. Setting a uniform with the wrong type is a bug though,
+     * so that is logged instead.
+     */
+    fn set_ignoring_errors(
+        self: &Self,
+        device: &mut dyn RenderDevice,
+        name: &CStr,
+        value: UniformValue,
+    ) {
+        let Ok(name) = name.to_str() else {
+            return;
+        };
+        match self.set_uniform(device, name, value) {
+            Ok(()) | Err(UniformError::Unknown(_)) => {}
+            Err(error) => log::warn!("{}: {}", Self::name(&self.paths, &self.variant), error),
         }
     }
 
-    fn create_shader(path: &str, shader_type: gl::types::GLenum) -> Result<u32, Box<dyn Error>> {
-        let mut shader_file = std::fs::File::open(path)?;
-        let mut contents = String::new();
-        shader_file.read_to_string(&mut contents)?;
+    fn reflect(device: &mut dyn RenderDevice, id: u32) -> (Uniforms, Option<Vec<ActiveAttribute>>) {
+        let uniforms = device.active_uniforms(id).map(|uniforms| {
+            uniforms
+                .into_iter()
+                .map(|uniform| (uniform.name.clone(), uniform))
+                .collect()
+        });
+        (uniforms, device.active_attributes(id))
+    }
 
-        let shader_id = unsafe { gl::CreateShader(shader_type) };
-        unsafe {
-            gl::ShaderSource(
-                shader_id,
-                1,
-                &contents.as_bytes().as_ptr().cast(),
-                &contents.len().try_into().unwrap(),
-            );
-            gl::CompileShader(shader_id);
-
-            let mut success = 0;
-            gl::GetShaderiv(shader_id, gl::COMPILE_STATUS, &mut success);
-            if success == 0 {
-                let mut log_len = 0_i32;
-                let mut v: Vec<u8> = Vec::with_capacity(1024);
-                gl::GetShaderInfoLog(shader_id, 1024, &mut log_len, v.as_mut_ptr().cast());
-                v.set_len(log_len.try_into().unwrap());
-                panic!(
-                    "Shader compile error: {}\nError: {}",
-                    path,
-                    String::from_utf8_lossy(&v)
-                );
+    fn name(paths: &[PathBuf], variant: &ShaderVariant) -> String {
+        let name = paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        match variant.key() {
+            key if key.is_empty() => name,
+            key => format!("{} [{}]", name, key),
+        }
+    }
+
+    fn modified_times(paths: &[PathBuf]) -> Vec<Option<SystemTime>> {
+        paths
+            .iter()
+            .map(|path| {
+                std::fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok()
+            })
+            .collect()
+    }
+
+    // the program and every file that went into it
+    fn build(
+        device: &mut dyn RenderDevice,
+        paths: &[PathBuf],
+        preprocessor: &Preprocessor,
+        variant: &ShaderVariant,
+    ) -> Result<(u32, Vec<PathBuf>), Box<dyn Error>> {
+        if paths.len() != 2 {
+            return Err(format!(
+                "Expected a vertex and a fragment shader, got {} files",
+                paths.len()
+            )
+            .into());
+        }
+        let (vertex_shader, mut files) = Self::create_shader(
+            device,
+            &paths[0],
+            ShaderStage::Vertex,
+            preprocessor,
+            variant,
+        )?;
+        let fragment_shader = match Self::create_shader(
+            device,
+            &paths[1],
+            ShaderStage::Fragment,
+            preprocessor,
+            variant,
+        ) {
+            Ok((fragment_shader, fragment_files)) => {
+                for file in fragment_files {
+                    if !files.contains(&file) {
+                        files.push(file);
+                    }
+                }
+                fragment_shader
             }
+            Err(error) => {
+                device.delete_shader(vertex_shader);
+                return Err(error);
+            }
+        };
+        let name = Self::name(paths, variant);
+        let id = device
+            .create_program(&[vertex_shader, fragment_shader])
+            .map_err(|log| format!("Program linking error: {}\nError: {}", name, log))?;
+        device.label(ObjectKind::Program, id, &name);
+
+        Ok((id, files))
+    }
+
+    fn create_shader(
+        device: &mut dyn RenderDevice,
+        path: &Path,
+        stage: ShaderStage,
+        preprocessor: &Preprocessor,
+        variant: &ShaderVariant,
+    ) -> Result<(u32, Vec<PathBuf>), Box<dyn Error>> {
+        let source = preprocessor
+            .process(path, variant)
+            .map_err(|error| format!("Could not preprocess shader {}", error))?;
+
+        // the log points into the expanded source, remap it onto the original files
+        let shader_id = device.create_shader(stage, &source.source).map_err(|log| {
+            format!(
+                "Shader compile error: {}\nError: {}",
+                path.display(),
+                source.remap_log(&log)
+            )
+        })?;
+        device.label(ObjectKind::Shader, shader_id, &path.display().to_string());
+
+        Ok((shader_id, source.files))
+    }
+}
+
+/*
+ * The variants of one vertex and fragment shader pair, each compiled the
+ * first time it is asked for and kept until destroy(). A variant that
+ * fails to build is not cached, so asking again retries it.
+ */
+pub struct ShaderVariants {
+    shaders: Vec<String>,
+    pub preprocessor: Preprocessor,
+    variants: HashMap<ShaderVariant, Shader>,
+}
+
+impl ShaderVariants {
+    pub fn new(shaders: &[&str], preprocessor: Preprocessor) -> Self {
+        ShaderVariants {
+            shaders: shaders.iter().map(|path| path.to_string()).collect(),
+            preprocessor,
+            variants: HashMap::new(),
         }
+    }
 
-        Ok(shader_id)
+    pub fn get(
+        self: &mut Self,
+        device: &mut dyn RenderDevice,
+        variant: &ShaderVariant,
+    ) -> Result<&Shader, Box<dyn Error>> {
+        if !self.variants.contains_key(variant) {
+            let shaders: Vec<&str> = self.shaders.iter().map(String::as_str).collect();
+            let shader = Shader::with_variant(device, &shaders, &self.preprocessor, variant)?;
+            self.variants.insert(variant.clone(), shader);
+        }
+        Ok(&self.variants[variant])
     }
 
-    fn create_program(vertex_shader: u32, fragment_shader: u32) -> u32 {
-        let id = unsafe { gl::CreateProgram() };
-        unsafe {
-            gl::AttachShader(id, vertex_shader);
-            gl::AttachShader(id, fragment_shader);
-            gl::LinkProgram(id);
+    pub fn len(self: &Self) -> usize {
+        self.variants.len()
+    }
 
-            let mut success = 0;
-            gl::GetProgramiv(id, gl::LINK_STATUS, &mut success);
-            if success == 0 {
-                let mut log_len = 0_i32;
-                let mut v: Vec<u8> = Vec::with_capacity(1024);
-                gl::GetProgramInfoLog(id, 1024, &mut log_len, v.as_mut_ptr().cast());
-                v.set_len(log_len.try_into().unwrap());
-                panic!("Program linking error: {}", String::from_utf8_lossy(&v));
-            }
+    pub fn is_empty(self: &Self) -> bool {
+        self.variants.is_empty()
+    }
 
-            gl::DetachShader(id, vertex_shader);
-            gl::DetachShader(id, fragment_shader);
-            gl::DeleteShader(vertex_shader);
-            gl::DeleteShader(fragment_shader);
+    // true when any variant was swapped
+    pub fn reload_if_changed(self: &mut Self, device: &mut dyn RenderDevice) -> bool {
+        // every variant is checked, not just up to the first that changed
+        let mut reloaded = false;
+        for shader in self.variants.values_mut() {
+            reloaded |= shader.reload_if_changed(device);
         }
+        reloaded
+    }
 
-        id
+    pub fn destroy(self: &mut Self, device: &mut dyn RenderDevice) {
+        for shader in self.variants.values() {
+            shader.destroy(device);
+        }
+        self.variants.clear();
     }
 }
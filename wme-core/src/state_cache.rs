@@ -0,0 +1,253 @@
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString},
+};
+
+use crate::render_device::{BlendMode, ClipDepth, CullMode, DepthState, UniformValue, Viewport};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StateCounters {
+    pub issued: u64,
+    pub skipped: u64,
+}
+
+/*
+ * Shadow copy of the GL state GlDevice has set. Every setter returns
+ * whether the call has to reach GL and counts the outcome. None means
+ * unknown, so the next call is always issued, which is also what
+ * invalidate() falls back to after raw GL calls outside the device.
+ */
+#[derive(Default)]
+pub struct StateCache {
+    pub counters: StateCounters,
+    program: Option<u32>,
+    vertex_array: Option<u32>,
+    active_unit: Option<u32>,
+    textures: HashMap<u32, u32>,
+    blend: Option<BlendMode>,
+    depth: Option<DepthState>,
+    cull: Option<CullMode>,
+    clip_depth: Option<ClipDepth>,
+    viewport: Option<Viewport>,
+    scissor: Option<Option<Viewport>>,
+    uniforms: HashMap<(u32, i32), UniformValue>,
+    locations: HashMap<(u32, CString), i32>,
+}
+
+impl StateCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn invalidate(self: &mut Self) {
+        let counters = self.counters;
+        *self = StateCache::default();
+        self.counters = counters;
+    }
+
+    pub fn reset_counters(self: &mut Self) {
+        self.counters = StateCounters::default();
+    }
+
+    pub fn program(self: &Self) -> Option<u32> {
+        self.program
+    }
+
+    pub fn use_program(self: &mut Self, program: u32) -> bool {
+        track(&mut self.program, program, &mut self.counters)
+    }
+
+    pub fn bind_vertex_array(self: &mut Self, vertex_array: u32) -> bool {
+        track(&mut self.vertex_array, vertex_array, &mut self.counters)
+    }
+
+    pub fn active_texture(self: &mut Self, unit: u32) -> bool {
+        track(&mut self.active_unit, unit, &mut self.counters)
+    }
+
+    // the active unit is tracked separately through active_texture
+    pub fn bind_texture(self: &mut Self, unit: u32, texture: u32) -> bool {
+        let issue = self.textures.get(&unit) != Some(&texture);
+        self.textures.insert(unit, texture);
+        count(issue, &mut self.counters)
+    }
+
+    pub fn set_blend(self: &mut Self, blend: BlendMode) -> bool {
+        track(&mut self.blend, blend, &mut self.counters)
+    }
+
+    pub fn set_depth(self: &mut Self, depth: DepthState) -> bool {
+        track(&mut self.depth, depth, &mut self.counters)
+    }
+
+    pub fn set_cull(self: &mut Self, cull: CullMode) -> bool {
+        track(&mut self.cull, cull, &mut self.counters)
+    }
+
+    pub fn set_clip_depth(self: &mut Self, clip_depth: ClipDepth) -> bool {
+        track(&mut self.clip_depth, clip_depth, &mut self.counters)
+    }
+
+    pub fn set_viewport(self: &mut Self, viewport: Viewport) -> bool {
+        track(&mut self.viewport, viewport, &mut self.counters)
+    }
+
+    pub fn set_scissor(self: &mut Self, scissor: Option<Viewport>) -> bool {
+        track(&mut self.scissor, scissor, &mut self.counters)
+    }
+
+    // uniform values are per program, only known once a program is in use
+    pub fn set_uniform(self: &mut Self, location: i32, value: UniformValue) -> bool {
+        let program = match self.program {
+            Some(program) => program,
+            None => return count(true, &mut self.counters),
+        };
+        let issue = self.uniforms.get(&(program, location)) != Some(&value);
+        if issue {
+            self.uniforms.insert((program, location), value);
+        }
+        count(issue, &mut self.counters)
+    }
+
+    pub fn uniform_location(self: &mut Self, program: u32, name: &CStr) -> Option<i32> {
+        let location = self.locations.get(&(program, name.to_owned())).cloned();
+        count(location.is_none(), &mut self.counters);
+        location
+    }
+
+    pub fn store_uniform_location(self: &mut Self, program: u32, name: &CStr, location: i32) {
+        self.locations.insert((program, name.to_owned()), location);
+    }
+
+    // for calls that bind as a side effect and should not count as state changes
+    pub fn vertex_array_changed(self: &mut Self, vertex_array: u32) {
+        self.vertex_array = Some(vertex_array);
+    }
+
+    pub fn forget_texture_units(self: &mut Self) {
+        self.textures.clear();
+    }
+
+    // GL unbinds deleted objects, and their names can be handed out again
+    pub fn forget_program(self: &mut Self, program: u32) {
+        if self.program == Some(program) {
+            self.program = None;
+        }
+        self.uniforms
+            .retain(|(uniform_program, _), _| *uniform_program != program);
+        self.locations
+            .retain(|(location_program, _), _| *location_program != program);
+    }
+
+    pub fn forget_vertex_array(self: &mut Self, vertex_array: u32) {
+        if self.vertex_array == Some(vertex_array) {
+            self.vertex_array = Some(0);
+        }
+    }
+
+    pub fn forget_texture(self: &mut Self, texture: u32) {
+        self.textures
+            .retain(|_, bound_texture| *bound_texture != texture);
+    }
+}
+
+fn track<T: PartialEq>(slot: &mut Option<T>, value: T, counters: &mut StateCounters) -> bool {
+    let issue = slot.as_ref() != Some(&value);
+    *slot = Some(value);
+    count(issue, counters)
+}
+
+fn count(issue: bool, counters: &mut StateCounters) -> bool {
+    if issue {
+        counters.issued += 1;
+    } else {
+        counters.skipped += 1;
+    }
+    issue
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counters(issued: u64, skipped: u64) -> StateCounters {
+        StateCounters { issued, skipped }
+    }
+
+    #[test]
+    fn repeated_calls_are_skipped_and_counted() {
+        let mut cache = StateCache::new();
+        assert!(cache.use_program(3));
+        assert!(!cache.use_program(3));
+        assert!(cache.use_program(4));
+        assert_eq!(cache.program(), Some(4));
+
+        assert!(cache.bind_texture(0, 7));
+        assert!(!cache.bind_texture(0, 7));
+        assert!(cache.bind_texture(1, 7));
+        assert!(cache.bind_texture(0, 8));
+        assert_eq!(cache.counters, counters(5, 2));
+
+        cache.reset_counters();
+        assert_eq!(cache.counters, counters(0, 0));
+        assert!(!cache.use_program(4));
+        assert_eq!(cache.counters, counters(0, 1));
+    }
+
+    #[test]
+    fn invalidate_forces_the_next_call_and_keeps_the_counters() {
+        let mut cache = StateCache::new();
+        cache.use_program(3);
+        cache.bind_texture(0, 7);
+        cache.set_blend(BlendMode::Alpha);
+        cache.set_uniform(0, UniformValue::Float(1.0));
+        cache.use_program(3);
+        let before = cache.counters;
+
+        cache.invalidate();
+        assert_eq!(cache.counters, before);
+        assert_eq!(cache.program(), None);
+        assert!(cache.use_program(3));
+        assert!(cache.bind_texture(0, 7));
+        assert!(cache.set_blend(BlendMode::Alpha));
+        assert!(cache.set_uniform(0, UniformValue::Float(1.0)));
+        assert_eq!(cache.counters, counters(before.issued + 4, before.skipped));
+    }
+
+    #[test]
+    fn forget_program_drops_its_uniforms_and_locations() {
+        let mut cache = StateCache::new();
+        let name = c"model";
+        cache.store_uniform_location(3, name, 2);
+        cache.store_uniform_location(4, name, 5);
+        cache.use_program(3);
+        cache.set_uniform(2, UniformValue::Int(1));
+        cache.use_program(4);
+        cache.set_uniform(5, UniformValue::Int(1));
+
+        cache.forget_program(3);
+        assert_eq!(cache.program(), Some(4));
+        assert_eq!(cache.uniform_location(3, name), None);
+        assert_eq!(cache.uniform_location(4, name), Some(5));
+        assert!(!cache.set_uniform(5, UniformValue::Int(1)));
+
+        // the name comes back as a new program
+        assert!(cache.use_program(3));
+        assert!(cache.set_uniform(2, UniformValue::Int(1)));
+
+        cache.forget_program(3);
+        assert_eq!(cache.program(), None);
+    }
+
+    #[test]
+    fn forget_vertex_array_falls_back_to_zero() {
+        let mut cache = StateCache::new();
+        cache.bind_vertex_array(6);
+        cache.forget_vertex_array(2);
+        assert!(!cache.bind_vertex_array(6));
+
+        cache.forget_vertex_array(6);
+        assert!(!cache.bind_vertex_array(0));
+        assert!(cache.bind_vertex_array(6));
+    }
+}
@@ -3,6 +3,8 @@ use std::{error::Error, ffi::CString};
 use image::DynamicImage::*;
 use image::ImageReader;
 
+use crate::render_device::{RenderDevice, TextureFormat};
+
 #[derive(Hash, Eq, PartialEq, Debug, Clone)]
 pub struct Texture {
     pub id: u32,
@@ -13,46 +15,31 @@ pub struct Texture {
 }
 
 impl Texture {
-    pub fn new(path: &str, name: &str) -> Result<Self, Box<dyn Error>> {
+    pub fn new(
+        device: &mut dyn RenderDevice,
+        path: &str,
+        name: &str,
+    ) -> Result<Self, Box<dyn Error>> {
         let image = ImageReader::open(path)?.decode()?;
-        let filter = match image.flipv() {
-            ImageLuma8(_) => gl::RED,
-            ImageLumaA8(_) => gl::RG,
-            ImageRgb8(_) => gl::RGB,
-            ImageRgba8(_) => gl::RGBA,
-            _ => 0,
+        let format = match image {
+            ImageLuma8(_) => TextureFormat::R8,
+            ImageLumaA8(_) => TextureFormat::Rg8,
+            ImageRgb8(_) => TextureFormat::Rgb8,
+            ImageRgba8(_) => TextureFormat::Rgba8,
+            _ => {
+                return Err(
+                    format!("Unsupported pixel format {:?} ({})", image.color(), path).into(),
+                )
+            }
         };
 
-        let mut id: u32 = 0;
-
-        // load texture from stbi
-        unsafe {
-            // manipulate the texture
-            gl::GenTextures(1, &mut id);
-
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
-            gl::TexParameteri(
-                gl::TEXTURE_2D,
-                gl::TEXTURE_MIN_FILTER,
-                gl::LINEAR_MIPMAP_LINEAR as i32,
-            );
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
-
-            gl::BindTexture(gl::TEXTURE_2D, id);
-            gl::TexImage2D(
-                gl::TEXTURE_2D,
-                0,
-                filter as i32,
-                image.width() as i32,
-                image.height() as i32,
-                0,
-                filter,
-                gl::UNSIGNED_BYTE,
-                image.as_bytes().as_ptr().cast(),
-            );
-            gl::GenerateMipmap(gl::TEXTURE_2D);
-        }
+        let id = device.create_texture(
+            image.width(),
+            image.height(),
+            format,
+            image.as_bytes(),
+            path,
+        )?;
 
         let name: CString = CString::new(name.to_string()).unwrap();
 
@@ -64,4 +51,8 @@ impl Texture {
             name,
         })
     }
+
+    pub fn destroy(self: &Self, device: &mut dyn RenderDevice) {
+        device.delete_texture(self.id);
+    }
 }
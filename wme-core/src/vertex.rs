@@ -1,5 +1,7 @@
 use nalgebra_glm as glm;
 
+use crate::render_device::Pod;
+
 /*
  * packing allows for an array like layout
  * make sure vertex shader in vars match
@@ -13,3 +15,6 @@ pub struct Vertex {
     pub texcoord: glm::Vec2,
     pub normal: glm::Vec3,
 }
+
+// packed floats, so there is no padding to leak
+unsafe impl Pod for Vertex {}